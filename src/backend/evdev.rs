@@ -0,0 +1,547 @@
+//! evdev 后端（Linux）：独占（EVIOCGRAB）选中的 `/dev/input/event*` 键盘，
+//! 处理后的事件通过 `/dev/uinput` 创建的虚拟键盘重新发出。
+
+use super::{DeviceInfo, DeviceKind, Input, InputBackend};
+use crate::config::{DeviceAction, DeviceRule, device_action};
+use crate::engine::{DeviceId, KeyEvent, OutputEvent};
+use crate::oscode::OsCode;
+use anyhow::{Context, Result};
+use evdev::uinput::VirtualDevice;
+use evdev::{AttributeSet, Device, EventType, InputEvent, KeyCode, RelativeAxisCode};
+use std::collections::HashSet;
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// 虚拟键盘的设备名，枚举键盘时据此跳过自己
+const VIRTUAL_DEVICE_NAME: &str = "nuna virtual keyboard";
+
+/// evdev 中 EV_KEY 事件的值
+const KEY_UP: i32 = 0;
+const KEY_DOWN: i32 = 1;
+const KEY_REPEAT: i32 = 2;
+
+/// 被独占的物理键盘
+struct Keyboard {
+    /// 设备编号，即 `/dev/input/eventN` 中的 N
+    id: DeviceId,
+    path: PathBuf,
+    name: String,
+    device: Device,
+    /// 设备被拔出后不再监听
+    connected: bool,
+}
+
+pub struct EvdevBackend {
+    keyboards: Vec<Keyboard>,
+    /// 输出用的虚拟键盘
+    output: VirtualDevice,
+    /// 虚拟键盘上已按下的键
+    pressed: HashSet<OsCode>,
+}
+
+impl EvdevBackend {
+    /// 打开并独占选中的键盘，`selectors` 为设备名或设备路径，为空时选中所有键盘。
+    /// 设备规则指定绕过映射的键盘不独占，由系统直接处理。
+    pub fn open(selectors: &[String], rules: &[DeviceRule]) -> Result<Self> {
+        let mut keyboards = Vec::new();
+        for (path, device) in evdev::enumerate() {
+            let name = device.name().unwrap_or_default().to_string();
+            if name == VIRTUAL_DEVICE_NAME || !is_selected(selectors, &path, &name, &device) {
+                continue;
+            }
+            let id = event_number(&path);
+            let hardware_id = hardware_id(&path, &device);
+            if let Some(DeviceAction::Bypass) = device_action(rules, id, Some(&hardware_id)) {
+                log::info!("键盘绕过映射，不拦截: {name} ({})", path.display());
+                continue;
+            }
+            log::info!("拦截键盘: {name} ({})", path.display());
+            keyboards.push(Keyboard {
+                id,
+                path,
+                name,
+                device,
+                connected: true,
+            });
+        }
+        if keyboards.is_empty() {
+            anyhow::bail!("没有找到要拦截的键盘: {selectors:?}");
+        }
+
+        let output = create_virtual_keyboard(&keyboards)?;
+        for keyboard in &mut keyboards {
+            grab(keyboard)?;
+        }
+        Ok(EvdevBackend {
+            keyboards,
+            output,
+            pressed: HashSet::new(),
+        })
+    }
+}
+
+/// 设备是否被选中：指定了设备时按路径或设备名匹配，否则选中所有键盘
+fn is_selected(selectors: &[String], path: &Path, name: &str, device: &Device) -> bool {
+    if selectors.is_empty() {
+        return is_keyboard(device);
+    }
+    selectors
+        .iter()
+        .any(|selector| matches_device(selector, path, name))
+}
+
+/// 以 `/` 开头的按路径匹配（会解析符号链接，支持 `/dev/input/by-id/*`），否则按设备名完整匹配
+fn matches_device(selector: &str, path: &Path, name: &str) -> bool {
+    if selector.starts_with('/') {
+        let canonical = |p: &Path| p.canonicalize().unwrap_or_else(|_| p.to_path_buf());
+        canonical(Path::new(selector)) == canonical(path)
+    } else {
+        selector == name
+    }
+}
+
+/// 枚举所有输入设备（包括 nuna 自己的虚拟键盘之外的鼠标等设备），按编号排序
+pub fn list_devices() -> Vec<DeviceInfo> {
+    let mut devices: Vec<DeviceInfo> = evdev::enumerate()
+        .filter(|(_, device)| device.name() != Some(VIRTUAL_DEVICE_NAME))
+        .map(|(path, device)| {
+            let kind = if is_keyboard(&device) {
+                DeviceKind::Keyboard
+            } else if is_mouse(&device) {
+                DeviceKind::Mouse
+            } else {
+                DeviceKind::Other
+            };
+            DeviceInfo {
+                id: event_number(&path),
+                kind,
+                hardware_id: hardware_id(&path, &device),
+            }
+        })
+        .collect();
+    devices.sort_by_key(|info| info.id);
+    devices
+}
+
+/// `/dev/input/eventN` 中的 N，无法解析时为 -1
+fn event_number(path: &Path) -> DeviceId {
+    path.file_name()
+        .and_then(|name| name.to_str()?.strip_prefix("event")?.parse().ok())
+        .unwrap_or(-1)
+}
+
+/// 硬件 ID：厂商号、产品号、设备名和路径，例如 `046d:c31c Logitech USB Keyboard (/dev/input/event3)`
+fn hardware_id(path: &Path, device: &Device) -> String {
+    let input_id = device.input_id();
+    format!(
+        "{:04x}:{:04x} {} ({})",
+        input_id.vendor(),
+        input_id.product(),
+        device.name().unwrap_or_default(),
+        path.display()
+    )
+}
+
+/// 能产生横向相对位移的设备视为鼠标
+fn is_mouse(device: &Device) -> bool {
+    device
+        .supported_relative_axes()
+        .is_some_and(|axes| axes.contains(RelativeAxisCode::REL_X))
+}
+
+/// 能产生字母键和 CapsLock 的设备视为键盘，以排除电源键、鼠标等设备
+fn is_keyboard(device: &Device) -> bool {
+    device
+        .supported_keys()
+        .is_some_and(|keys| keys.contains(KeyCode::KEY_A) && keys.contains(KeyCode::KEY_CAPSLOCK))
+}
+
+/// 创建虚拟键盘，支持物理键盘能产生的所有键以及映射能输出的所有键
+fn create_virtual_keyboard(keyboards: &[Keyboard]) -> Result<VirtualDevice> {
+    let mut keys = AttributeSet::<KeyCode>::new();
+    for keyboard in keyboards {
+        if let Some(supported) = keyboard.device.supported_keys() {
+            supported.iter().for_each(|key| keys.insert(key));
+        }
+    }
+    for &code in OsCode::ALL {
+        // 与配置校验一致：能编码为扫描码的键才可能作为映射输出
+        if code.is_encodable() {
+            keys.insert(KeyCode::new(code as u16));
+        }
+    }
+    VirtualDevice::builder()
+        .and_then(|builder| builder.name(VIRTUAL_DEVICE_NAME).with_keys(&keys))
+        .and_then(|builder| builder.build())
+        .context("无法创建 uinput 虚拟键盘，请检查 /dev/uinput 的访问权限")
+}
+
+/// 等待键盘上的按键全部释放后独占，避免已按下的键在系统中卡住
+fn grab(keyboard: &mut Keyboard) -> Result<()> {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while keyboard
+        .device
+        .get_key_state()
+        .is_ok_and(|keys| keys.iter().next().is_some())
+        && Instant::now() < deadline
+    {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    keyboard
+        .device
+        .grab()
+        .with_context(|| format!("无法独占键盘 {}", keyboard.path.display()))?;
+    keyboard.device.set_nonblocking(true)?;
+    Ok(())
+}
+
+/// 将 evdev 事件转换为按键事件，非按键事件和无法识别的键返回 `None`
+fn to_key_event(event: &InputEvent, device: DeviceId) -> Option<KeyEvent> {
+    if event.event_type() != EventType::KEY {
+        return None;
+    }
+    let down = match event.value() {
+        KEY_UP => false,
+        // 自动重复按下一个已按下的键处理
+        KEY_DOWN | KEY_REPEAT => true,
+        _ => return None,
+    };
+    Some(KeyEvent {
+        code: OsCode::from_u16(event.code())?,
+        down,
+        device,
+        timestamp: event_instant(event),
+    })
+}
+
+/// 内核给出的事件时间为系统时间，换算为 `Instant`；无法换算时使用当前时间
+fn event_instant(event: &InputEvent) -> Instant {
+    let now = Instant::now();
+    SystemTime::now()
+        .duration_since(event.timestamp())
+        .ok()
+        .and_then(|age| now.checked_sub(age))
+        .unwrap_or(now)
+}
+
+/// 将 evdev 事件转换为输入：无法识别的按键原样发回，
+/// 其他非按键事件丢弃，虚拟键盘发送时会自行产生同步事件
+fn to_input(event: &InputEvent, device: DeviceId) -> Option<Input<InputEvent>> {
+    match to_key_event(event, device) {
+        Some(key) => Some(Input::Key(key)),
+        None => (event.event_type() == EventType::KEY).then_some(Input::Raw(*event)),
+    }
+}
+
+/// 转换为 evdev 事件。再次按下已按下的键时发出自动重复：
+/// 内核会丢弃已按下的键的按下事件，只有自动重复能通过
+fn to_input_event(event: &OutputEvent, pressed: &mut HashSet<OsCode>) -> InputEvent {
+    let value = if !event.down {
+        pressed.remove(&event.code);
+        KEY_UP
+    } else if pressed.insert(event.code) {
+        KEY_DOWN
+    } else {
+        KEY_REPEAT
+    };
+    InputEvent::new(EventType::KEY.0, event.code as u16, value)
+}
+
+impl InputBackend for EvdevBackend {
+    type Raw = InputEvent;
+
+    fn wait(&mut self, timeout: Duration) -> Option<DeviceId> {
+        let mut fds: Vec<libc::pollfd> = self
+            .keyboards
+            .iter()
+            .map(|keyboard| libc::pollfd {
+                // 负数的 fd 会被 poll 忽略
+                fd: if keyboard.connected {
+                    keyboard.device.as_raw_fd()
+                } else {
+                    -1
+                },
+                events: libc::POLLIN,
+                revents: 0,
+            })
+            .collect();
+        let millis = libc::c_int::try_from(timeout.as_millis()).unwrap_or(libc::c_int::MAX);
+        let ready = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, millis) };
+        if ready <= 0 {
+            return None;
+        }
+        let index = fds.iter().position(|fd| fd.revents != 0)?;
+        if fds[index].revents & libc::POLLIN == 0 {
+            // 设备被拔出等错误，不再监听
+            let keyboard = &mut self.keyboards[index];
+            keyboard.connected = false;
+            log::warn!(
+                "键盘已断开: {} ({})",
+                keyboard.name,
+                keyboard.path.display()
+            );
+            return None;
+        }
+        Some(self.keyboards[index].id)
+    }
+
+    fn receive(&mut self, device: DeviceId) -> Vec<Input<InputEvent>> {
+        let Some(keyboard) = self.keyboards.iter_mut().find(|k| k.id == device) else {
+            return Vec::new();
+        };
+        match keyboard.device.fetch_events() {
+            Ok(events) => events
+                .filter_map(|event| to_input(&event, device))
+                .collect(),
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::WouldBlock {
+                    log::warn!("读取键盘事件失败: {e}");
+                }
+                Vec::new()
+            }
+        }
+    }
+
+    fn send(&mut self, _device: DeviceId, events: &[OutputEvent]) {
+        // 每个事件后各跟一个 SYN_REPORT，保证组合键的按下顺序
+        for event in events {
+            let event = to_input_event(event, &mut self.pressed);
+            if let Err(e) = self.output.emit(&[event]) {
+                log::error!("发送按键事件失败: {e}");
+            }
+        }
+    }
+
+    fn send_raw(&mut self, _device: DeviceId, raw: &[InputEvent]) {
+        for event in raw {
+            if let Err(e) = self.output.emit(std::slice::from_ref(event)) {
+                log::error!("发送按键事件失败: {e}");
+            }
+        }
+    }
+
+    fn devices(&self) -> Vec<DeviceInfo> {
+        self.keyboards
+            .iter()
+            .map(|keyboard| DeviceInfo {
+                id: keyboard.id,
+                kind: DeviceKind::Keyboard,
+                hardware_id: hardware_id(&keyboard.path, &keyboard.device),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::pump;
+    use crate::config::Keymap;
+    use crate::engine::Engine;
+
+    #[test]
+    fn selects_devices_by_name_or_path() {
+        let path = Path::new("/dev/input/event3");
+        let name = "AT Translated Set 2 keyboard";
+        assert!(matches_device("AT Translated Set 2 keyboard", path, name));
+        assert!(matches_device("/dev/input/event3", path, name));
+        assert!(!matches_device("/dev/input/event4", path, name));
+        assert!(!matches_device("AT Translated", path, name));
+    }
+
+    #[test]
+    fn device_id_is_the_event_number() {
+        assert_eq!(event_number(Path::new("/dev/input/event12")), 12);
+        assert_eq!(event_number(Path::new("/dev/input/mouse0")), -1);
+    }
+
+    #[test]
+    fn translates_key_events() {
+        let key = |code: OsCode, value| InputEvent::new(EventType::KEY.0, code as u16, value);
+        let to_key = |event| to_key_event(&event, 1).map(|e| (e.code, e.down));
+        assert_eq!(to_key(key(OsCode::KEY_H, 1)), Some((OsCode::KEY_H, true)));
+        assert_eq!(to_key(key(OsCode::KEY_H, 2)), Some((OsCode::KEY_H, true)));
+        assert_eq!(to_key(key(OsCode::KEY_H, 0)), Some((OsCode::KEY_H, false)));
+        // MSC_SCAN 等非按键事件被忽略
+        assert_eq!(to_key(InputEvent::new(EventType::MISC.0, 4, 0x23)), None);
+        assert_eq!(
+            to_input(&InputEvent::new(EventType::MISC.0, 4, 0x23), 1),
+            None
+        );
+        // 无法识别的按键原样发回
+        let unknown = InputEvent::new(EventType::KEY.0, 0x2fe, 1);
+        assert_eq!(to_input(&unknown, 1), Some(Input::Raw(unknown)));
+
+        let mut pressed = HashSet::new();
+        let output = to_input_event(&OutputEvent::press(OsCode::KEY_LEFT), &mut pressed);
+        assert_eq!(
+            (output.event_type(), output.code(), output.value()),
+            (EventType::KEY, OsCode::KEY_LEFT as u16, KEY_DOWN)
+        );
+        // 已按下的键再次按下时作为自动重复发出
+        let values: Vec<i32> = [
+            OutputEvent::press(OsCode::KEY_LEFT),
+            OutputEvent::release(OsCode::KEY_LEFT),
+            OutputEvent::press(OsCode::KEY_LEFT),
+        ]
+        .iter()
+        .map(|event| to_input_event(event, &mut pressed).value())
+        .collect();
+        assert_eq!(values, [KEY_REPEAT, KEY_UP, KEY_DOWN]);
+    }
+
+    /// 创建一个测试用的 uinput 键盘，没有 /dev/uinput 权限时返回 `None` 以跳过测试
+    fn test_keyboard(name: &str) -> Option<VirtualDevice> {
+        let mut keys = AttributeSet::<KeyCode>::new();
+        for code in [
+            OsCode::KEY_A,
+            OsCode::KEY_CAPSLOCK,
+            OsCode::KEY_B,
+            OsCode::KEY_H,
+        ] {
+            keys.insert(KeyCode::new(code as u16));
+        }
+        let keyboard = VirtualDevice::builder()
+            .and_then(|builder| builder.name(name).with_keys(&keys))
+            .and_then(|builder| builder.build());
+        match keyboard {
+            Ok(keyboard) => Some(keyboard),
+            Err(e) => {
+                eprintln!("跳过 uinput 测试: {e}");
+                None
+            }
+        }
+    }
+
+    /// 从虚拟键盘读取按键事件，直到读到 `count` 个或超时
+    fn read_keys(device: &mut Device, count: usize) -> Vec<(OsCode, bool)> {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let mut keys = Vec::new();
+        while keys.len() < count && Instant::now() < deadline {
+            if let Ok(events) = device.fetch_events() {
+                keys.extend(
+                    events
+                        .filter_map(|event| to_key_event(&event, 0))
+                        .map(|event| (event.code, event.down)),
+                );
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        keys
+    }
+
+    /// 从虚拟键盘读取 EV_KEY 事件的键和值，直到读到 `count` 个或超时
+    fn read_values(device: &mut Device, count: usize) -> Vec<(OsCode, i32)> {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let mut values = Vec::new();
+        while values.len() < count && Instant::now() < deadline {
+            if let Ok(events) = device.fetch_events() {
+                values.extend(
+                    events
+                        .filter(|event| event.event_type() == EventType::KEY)
+                        .filter_map(|event| Some((OsCode::from_u16(event.code())?, event.value()))),
+                );
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        values
+    }
+
+    /// 创建测试键盘并只拦截它，返回测试键盘、后端和后端的虚拟键盘；
+    /// 没有 /dev/uinput 权限时返回 `None` 以跳过测试
+    fn open_test_backend(test: &str) -> Option<(VirtualDevice, EvdevBackend, Device)> {
+        let name = format!("nuna test keyboard {} {test}", std::process::id());
+        let input = test_keyboard(&name)?;
+        // 设备节点由内核异步创建
+        std::thread::sleep(Duration::from_millis(200));
+        let mut backend = EvdevBackend::open(std::slice::from_ref(&name), &[]).unwrap();
+        assert_eq!(backend.devices().len(), 1);
+
+        let output_path = backend
+            .output
+            .enumerate_dev_nodes_blocking()
+            .unwrap()
+            .find_map(Result::ok)
+            .unwrap();
+        let output = Device::open(output_path).unwrap();
+        output.set_nonblocking(true).unwrap();
+        Some((input, backend, output))
+    }
+
+    /// 从测试键盘发出按键事件
+    fn emit_keys(input: &mut VirtualDevice, keys: &[(OsCode, i32)]) {
+        for &(code, value) in keys {
+            input
+                .emit(&[InputEvent::new(EventType::KEY.0, code as u16, value)])
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn remaps_keys_from_a_uinput_keyboard() {
+        let Some((mut input, mut backend, mut output)) = open_test_backend("remap") else {
+            return;
+        };
+        emit_keys(
+            &mut input,
+            &[
+                (OsCode::KEY_CAPSLOCK, KEY_DOWN),
+                (OsCode::KEY_B, KEY_DOWN),
+                (OsCode::KEY_B, KEY_UP),
+                (OsCode::KEY_CAPSLOCK, KEY_UP),
+                (OsCode::KEY_H, KEY_DOWN),
+                (OsCode::KEY_H, KEY_UP),
+            ],
+        );
+
+        let mut engine = Engine::new(Keymap::builtin());
+        while pump(&mut backend, &mut engine, Duration::from_millis(200)) {}
+        assert_eq!(
+            read_keys(&mut output, 6),
+            [
+                (OsCode::KEY_LEFTCTRL, true),
+                (OsCode::KEY_LEFT, true),
+                (OsCode::KEY_LEFT, false),
+                (OsCode::KEY_LEFTCTRL, false),
+                (OsCode::KEY_H, true),
+                (OsCode::KEY_H, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn key_repeat_reaches_the_uinput_output() {
+        let Some((mut input, mut backend, mut output)) = open_test_backend("repeat") else {
+            return;
+        };
+        emit_keys(
+            &mut input,
+            &[
+                (OsCode::KEY_H, KEY_DOWN),
+                (OsCode::KEY_H, KEY_REPEAT),
+                (OsCode::KEY_H, KEY_UP),
+                (OsCode::KEY_CAPSLOCK, KEY_DOWN),
+                (OsCode::KEY_H, KEY_DOWN),
+                (OsCode::KEY_H, KEY_REPEAT),
+                (OsCode::KEY_H, KEY_UP),
+                (OsCode::KEY_CAPSLOCK, KEY_UP),
+            ],
+        );
+
+        let mut engine = Engine::new(Keymap::builtin());
+        while pump(&mut backend, &mut engine, Duration::from_millis(200)) {}
+        // 原样输出的键和映射后的键的自动重复都以值 2 发出，不会被内核丢弃
+        assert_eq!(
+            read_values(&mut output, 6),
+            [
+                (OsCode::KEY_H, KEY_DOWN),
+                (OsCode::KEY_H, KEY_REPEAT),
+                (OsCode::KEY_H, KEY_UP),
+                (OsCode::KEY_LEFT, KEY_DOWN),
+                (OsCode::KEY_LEFT, KEY_REPEAT),
+                (OsCode::KEY_LEFT, KEY_UP),
+            ]
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! Interception 驱动后端（Windows）
+
+use super::{DeviceInfo, DeviceKind, Input, InputBackend};
+use crate::engine::{DeviceId, KeyEvent, OutputEvent};
+use crate::interception::{Interception, to_raw};
+use crate::oscode::{Decoded, StrokeDecoder};
+use kanata_interception::{KeyFilter, Stroke, raw::InterceptionKeyStroke};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Interception 最多支持 10 个键盘，设备编号为 1..=10
+const MAX_KEYBOARD: DeviceId = 10;
+/// 以及 10 个鼠标，设备编号为 11..=20
+const MAX_DEVICE: DeviceId = 20;
+
+pub struct InterceptionBackend {
+    intercept: Interception,
+    /// 按设备保存的解码器，用于识别跨多个事件的 Pause 序列和假 Shift
+    decoders: HashMap<DeviceId, StrokeDecoder>,
+}
+
+impl InterceptionBackend {
+    /// 连接驱动并拦截所有键盘事件，驱动未安装时返回 `None`
+    pub fn new() -> Option<Self> {
+        let intercept = Interception::new()?;
+        intercept.set_filter(KeyFilter::all());
+        Some(InterceptionBackend {
+            intercept,
+            decoders: HashMap::new(),
+        })
+    }
+}
+
+impl InputBackend for InterceptionBackend {
+    type Raw = InterceptionKeyStroke;
+
+    fn wait(&mut self, timeout: Duration) -> Option<DeviceId> {
+        // 超时返回 0
+        let device = self.intercept.wait_with_timeout(timeout);
+        (device > 0).then_some(device)
+    }
+
+    fn receive(&mut self, device: DeviceId) -> Vec<Input<InterceptionKeyStroke>> {
+        let decoder = self.decoders.entry(device).or_default();
+        // 驱动不提供事件时间，以接收的时间为准
+        let timestamp = Instant::now();
+        let mut inputs = Vec::new();
+        for received in self.intercept.receive(device) {
+            let stroke = match received {
+                Ok(stroke) => stroke,
+                Err(raw_stroke) => {
+                    inputs.push(Input::Raw(raw_stroke));
+                    continue;
+                }
+            };
+            match decoder.decode(stroke) {
+                Decoded::Key { code, down } => inputs.push(Input::Key(KeyEvent {
+                    code,
+                    down,
+                    device,
+                    timestamp,
+                })),
+                // 假 Shift 和 E1 前缀直接丢弃
+                Decoded::Swallowed => {}
+                // 无法识别的事件原样转发
+                Decoded::Unknown => inputs.extend(to_raw(&stroke).map(Input::Raw)),
+            }
+        }
+        inputs
+    }
+
+    /// 原始事件不经解码直接发回，保留假 Shift 和附加信息
+    fn forward(&mut self, device: DeviceId) {
+        self.intercept.forward(device);
+    }
+
+    fn send(&mut self, device: DeviceId, events: &[OutputEvent]) {
+        // 配置加载时已校验过每个键都能编码
+        let strokes: Vec<Stroke> = events
+            .iter()
+            .flat_map(|event| event.code.to_strokes(event.down))
+            .collect();
+        self.intercept.send(device, &strokes);
+    }
+
+    fn send_raw(&mut self, device: DeviceId, raw: &[InterceptionKeyStroke]) {
+        self.intercept.send_raw(device, raw);
+    }
+
+    fn devices(&self) -> Vec<DeviceInfo> {
+        device_infos(&self.intercept, 1..=MAX_KEYBOARD)
+    }
+}
+
+/// 枚举所有键盘和鼠标，驱动未安装时返回 `None`
+pub fn list_devices() -> Option<Vec<DeviceInfo>> {
+    let intercept = Interception::new()?;
+    Some(device_infos(&intercept, 1..=MAX_DEVICE))
+}
+
+fn device_infos(intercept: &Interception, ids: impl Iterator<Item = DeviceId>) -> Vec<DeviceInfo> {
+    ids.filter_map(|id| {
+        let hardware_id = intercept.get_hardware_id(id)?;
+        let kind = if id <= MAX_KEYBOARD {
+            DeviceKind::Keyboard
+        } else {
+            DeviceKind::Mouse
+        };
+        Some(DeviceInfo {
+            id,
+            kind,
+            hardware_id,
+        })
+    })
+    .collect()
+}
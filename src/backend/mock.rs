@@ -0,0 +1,114 @@
+//! 内存中的模拟后端：按脚本依次产生输入事件，并记录发送的所有事件，用于测试
+
+use super::{DeviceInfo, DeviceKind, Input, InputBackend};
+use crate::engine::{DeviceId, KeyEvent, OutputEvent};
+use crate::oscode::OsCode;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub struct MockBackend {
+    /// 待产生的输入及其设备
+    input: VecDeque<(DeviceId, Input<OutputEvent>)>,
+    /// 已发送的事件
+    sent: Vec<(DeviceId, OutputEvent)>,
+    /// 脚本中出现过的设备
+    devices: Vec<DeviceInfo>,
+    /// 原样发回的事件，不经过引擎
+    forwarded: Vec<KeyEvent>,
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        MockBackend {
+            input: VecDeque::new(),
+            sent: Vec::new(),
+            devices: Vec::new(),
+            forwarded: Vec::new(),
+        }
+    }
+
+    /// 在脚本末尾追加设备上的按键事件，事件时间为追加时的时间
+    pub fn script(&mut self, device: DeviceId, events: &[(OsCode, bool)]) {
+        self.add_device(device);
+        let timestamp = Instant::now();
+        for &(code, down) in events {
+            let event = KeyEvent {
+                code,
+                down,
+                device,
+                timestamp,
+            };
+            self.input.push_back((device, Input::Key(event)));
+        }
+    }
+
+    /// 在脚本末尾追加一个无法识别的原始事件，发回时与发送的按键事件记录在一起
+    pub fn script_raw(&mut self, device: DeviceId, event: OutputEvent) {
+        self.add_device(device);
+        self.input.push_back((device, Input::Raw(event)));
+    }
+
+    fn add_device(&mut self, device: DeviceId) {
+        if !self.devices.iter().any(|info| info.id == device) {
+            self.devices.push(DeviceInfo {
+                id: device,
+                kind: DeviceKind::Keyboard,
+                hardware_id: format!("MOCK\\KEYBOARD_{device}"),
+            });
+        }
+    }
+
+    /// 已发送的所有事件
+    pub fn sent(&self) -> &[(DeviceId, OutputEvent)] {
+        &self.sent
+    }
+
+    /// 原样发回的所有事件
+    pub fn forwarded(&self) -> &[KeyEvent] {
+        &self.forwarded
+    }
+}
+
+impl InputBackend for MockBackend {
+    type Raw = OutputEvent;
+
+    fn wait(&mut self, _timeout: Duration) -> Option<DeviceId> {
+        self.input.front().map(|&(device, _)| device)
+    }
+
+    /// 读取脚本开头连续属于该设备的输入
+    fn receive(&mut self, device: DeviceId) -> Vec<Input<OutputEvent>> {
+        let mut inputs = Vec::new();
+        while let Some((id, input)) = self.input.pop_front() {
+            if id != device {
+                self.input.push_front((id, input));
+                break;
+            }
+            inputs.push(input);
+        }
+        inputs
+    }
+
+    fn send(&mut self, device: DeviceId, events: &[OutputEvent]) {
+        self.sent
+            .extend(events.iter().map(|&event| (device, event)));
+    }
+
+    fn send_raw(&mut self, device: DeviceId, raw: &[OutputEvent]) {
+        self.send(device, raw);
+    }
+
+    fn forward(&mut self, device: DeviceId) {
+        for input in self.receive(device) {
+            match input {
+                Input::Key(event) => self.forwarded.push(event),
+                Input::Raw(raw) => self.send_raw(device, &[raw]),
+            }
+        }
+    }
+
+    fn devices(&self) -> Vec<DeviceInfo> {
+        self.devices.clone()
+    }
+}
@@ -0,0 +1,356 @@
+//! 输入后端：负责从键盘读取按键事件、向系统发送按键事件。
+//! 拦截主循环只依赖 `InputBackend`，因此可以用内存中的模拟后端在没有驱动的环境下运行。
+
+#[cfg(target_os = "linux")]
+mod evdev;
+#[cfg(all(windows, feature = "interception"))]
+mod interception;
+#[cfg(test)]
+pub mod mock;
+
+#[cfg(target_os = "linux")]
+pub use evdev::{EvdevBackend, list_devices};
+#[cfg(all(windows, feature = "interception"))]
+pub use interception::{InterceptionBackend, list_devices};
+
+use crate::engine::{DeviceId, Engine, KeyEvent, OutputEvent};
+use crossbeam_channel::Receiver;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// 设备类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceKind {
+    Keyboard,
+    Mouse,
+    /// 既非键盘也非鼠标，例如电源键（仅 Linux）
+    #[cfg_attr(not(target_os = "linux"), allow(unused))]
+    Other,
+}
+
+impl fmt::Display for DeviceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DeviceKind::Keyboard => "键盘",
+            DeviceKind::Mouse => "鼠标",
+            DeviceKind::Other => "其他",
+        })
+    }
+}
+
+/// 输入设备信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceInfo {
+    pub id: DeviceId,
+    pub kind: DeviceKind,
+    pub hardware_id: String,
+}
+
+/// 从设备读取的一个输入
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input<R> {
+    /// 交给引擎处理的按键事件
+    Key(KeyEvent),
+    /// 无法识别的原始事件，按在输入中的位置原样发回
+    Raw(R),
+}
+
+pub trait InputBackend {
+    /// 后端无法识别为按键事件的原始事件
+    type Raw;
+
+    /// 等待任意设备产生输入，超时返回 `None`
+    fn wait(&mut self, timeout: Duration) -> Option<DeviceId>;
+
+    /// 按顺序读取设备上已产生的输入
+    fn receive(&mut self, device: DeviceId) -> Vec<Input<Self::Raw>>;
+
+    /// 向设备发送按键事件
+    fn send(&mut self, device: DeviceId, events: &[OutputEvent]);
+
+    /// 向设备原样发回原始事件
+    fn send_raw(&mut self, device: DeviceId, raw: &[Self::Raw]);
+
+    /// 将设备上已产生的输入原样发回，用于绕过映射的设备
+    fn forward(&mut self, device: DeviceId) {
+        let inputs = self.receive(device);
+        send_back(self, device, inputs);
+    }
+
+    /// 枚举当前可用的键盘设备
+    fn devices(&self) -> Vec<DeviceInfo>;
+}
+
+/// 按读取时的顺序原样发回输入
+fn send_back<B: InputBackend + ?Sized>(
+    backend: &mut B,
+    device: DeviceId,
+    inputs: Vec<Input<B::Raw>>,
+) {
+    for input in inputs {
+        match input {
+            Input::Key(event) => backend.send(
+                device,
+                &[OutputEvent {
+                    code: event.code,
+                    down: event.down,
+                }],
+            ),
+            Input::Raw(raw) => backend.send_raw(device, &[raw]),
+        }
+    }
+}
+
+/// 拦截主循环：等待输入、交给引擎处理、发送输出，直到收到退出信号
+pub fn run(backend: &mut impl InputBackend, engine: &mut Engine, exit_rx: &Receiver<()>) {
+    loop {
+        // 检查退出信号
+        if exit_rx.try_recv().is_ok() {
+            log::info!("收到退出信号，停止键盘拦截");
+            for (device, output) in engine.stop_macros() {
+                backend.send(device, &output);
+            }
+            return;
+        }
+        // 超时时间为 1 毫秒，避免阻塞过久而无法响应退出信号
+        pump(backend, engine, Duration::from_millis(1));
+    }
+}
+
+/// 处理一批输入事件，等待超时返回 `false`
+pub fn pump(backend: &mut impl InputBackend, engine: &mut Engine, timeout: Duration) -> bool {
+    // 没有输入时也要处理 tap-hold 等判定超时
+    for (device, output) in engine.tick() {
+        backend.send(device, &output);
+    }
+    let Some(device) = backend.wait(timeout) else {
+        return false;
+    };
+    if !engine.is_attached(device) {
+        // 设备首次出现时按硬件 ID 选择映射
+        let hardware_id = backend
+            .devices()
+            .into_iter()
+            .find(|info| info.id == device)
+            .map(|info| info.hardware_id);
+        engine.attach(device, hardware_id.as_deref());
+    }
+    if engine.is_bypassed(device) {
+        backend.forward(device);
+        return true;
+    }
+    for input in backend.receive(device) {
+        match input {
+            Input::Key(event) => {
+                let output = engine.process(event);
+                backend.send(device, &output);
+            }
+            // 无法识别的事件不经过引擎，但仍按原来的顺序发出
+            Input::Raw(raw) => backend.send_raw(device, &[raw]),
+        }
+    }
+    true
+}
+
+/// 等待任意键盘按下一个键，返回该键盘的信息，超时返回 `None`。
+/// 期间收到的事件都原样发回，不影响正常输入。
+pub fn identify(backend: &mut impl InputBackend, timeout: Duration) -> Option<DeviceInfo> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let remaining = deadline.checked_duration_since(Instant::now())?;
+        let Some(device) = backend.wait(remaining.min(Duration::from_millis(100))) else {
+            continue;
+        };
+        let inputs = backend.receive(device);
+        // 只认按下：启动命令时回车键的抬起也会被收到
+        let pressed = inputs
+            .iter()
+            .any(|input| matches!(input, Input::Key(event) if event.down));
+        send_back(backend, device, inputs);
+        if pressed {
+            return backend.devices().into_iter().find(|info| info.id == device);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::MockBackend;
+    use super::*;
+    use crate::config::Keymap;
+    use crate::engine::FakeClock;
+    use crate::oscode::OsCode::*;
+
+    const KEYBOARD: DeviceId = 1;
+
+    /// 处理完脚本中的所有输入
+    fn drain(backend: &mut MockBackend, engine: &mut Engine) {
+        while pump(backend, engine, Duration::ZERO) {}
+    }
+
+    #[test]
+    fn caps_b_produces_ctrl_left_then_full_release() {
+        let mut backend = MockBackend::new();
+        backend.script(
+            KEYBOARD,
+            &[
+                (KEY_CAPSLOCK, true),
+                (KEY_B, true),
+                (KEY_B, false),
+                (KEY_CAPSLOCK, false),
+            ],
+        );
+        drain(&mut backend, &mut Engine::new(Keymap::builtin()));
+        assert_eq!(
+            backend.sent(),
+            [
+                (KEYBOARD, OutputEvent::press(KEY_LEFTCTRL)),
+                (KEYBOARD, OutputEvent::press(KEY_LEFT)),
+                (KEYBOARD, OutputEvent::release(KEY_LEFT)),
+                (KEYBOARD, OutputEvent::release(KEY_LEFTCTRL)),
+            ]
+        );
+    }
+
+    #[test]
+    fn typing_passes_through_unchanged() {
+        let mut backend = MockBackend::new();
+        let script = [(KEY_H, true), (KEY_H, false), (KEY_I, true), (KEY_I, false)];
+        backend.script(KEYBOARD, &script);
+        drain(&mut backend, &mut Engine::new(Keymap::builtin()));
+        let expected: Vec<_> = script
+            .iter()
+            .map(|&(code, down)| (KEYBOARD, OutputEvent { code, down }))
+            .collect();
+        assert_eq!(backend.sent(), expected);
+    }
+
+    #[test]
+    fn output_goes_back_to_the_source_device() {
+        let mut backend = MockBackend::new();
+        backend.script(2, &[(KEY_CAPSLOCK, true), (KEY_H, true)]);
+        backend.script(3, &[(KEY_G, true)]);
+        drain(&mut backend, &mut Engine::new(Keymap::builtin()));
+        assert_eq!(
+            backend.sent(),
+            [
+                (2, OutputEvent::press(KEY_LEFT)),
+                (3, OutputEvent::press(KEY_G)),
+            ]
+        );
+    }
+
+    #[test]
+    fn raw_input_keeps_its_position() {
+        let mut backend = MockBackend::new();
+        backend.script(KEYBOARD, &[(KEY_CAPSLOCK, true), (KEY_H, true)]);
+        backend.script_raw(KEYBOARD, OutputEvent::press(KEY_F24));
+        backend.script(KEYBOARD, &[(KEY_H, false), (KEY_CAPSLOCK, false)]);
+        drain(&mut backend, &mut Engine::new(Keymap::builtin()));
+        assert_eq!(
+            backend.sent(),
+            [
+                (KEYBOARD, OutputEvent::press(KEY_LEFT)),
+                (KEYBOARD, OutputEvent::press(KEY_F24)),
+                (KEYBOARD, OutputEvent::release(KEY_LEFT)),
+            ]
+        );
+    }
+
+    #[test]
+    fn layer_output_goes_to_the_trigger_device() {
+        let mut backend = MockBackend::new();
+        backend.script(1, &[(KEY_CAPSLOCK, true)]);
+        backend.script(2, &[(KEY_H, true)]);
+        backend.script(1, &[(KEY_J, true)]);
+        let mut engine = Engine::new(Keymap::builtin()).with_shared_layers(true);
+        drain(&mut backend, &mut engine);
+        assert_eq!(
+            backend.sent(),
+            [
+                (2, OutputEvent::press(KEY_LEFT)),
+                (1, OutputEvent::press(KEY_UP)),
+            ]
+        );
+    }
+
+    #[test]
+    fn identify_reports_the_first_device_pressing_a_key() {
+        let mut backend = MockBackend::new();
+        // 启动命令时按下的回车在另一个键盘上抬起
+        backend.script(1, &[(KEY_ENTER, false)]);
+        backend.script(2, &[(KEY_H, true), (KEY_H, false)]);
+        backend.script(3, &[(KEY_G, true)]);
+        let info = identify(&mut backend, Duration::from_secs(1)).unwrap();
+        assert_eq!(info.id, 2);
+        assert_eq!(info.hardware_id, "MOCK\\KEYBOARD_2");
+        // 识别期间的按键照常发送
+        assert_eq!(
+            backend.sent(),
+            [
+                (1, OutputEvent::release(KEY_ENTER)),
+                (2, OutputEvent::press(KEY_H)),
+                (2, OutputEvent::release(KEY_H)),
+            ]
+        );
+    }
+
+    #[test]
+    fn identify_times_out_without_key_press() {
+        let mut backend = MockBackend::new();
+        backend.script(1, &[(KEY_ENTER, false)]);
+        assert_eq!(identify(&mut backend, Duration::from_millis(10)), None);
+    }
+
+    #[test]
+    fn devices_are_matched_by_hardware_id() {
+        let config = crate::config::Config::parse(
+            "[bindings]\nH = \"Left\"\n\n[[devices]]\nhardware_id = \"keyboard_2\"\nbypass = true\n",
+            "nuna.toml",
+        )
+        .unwrap();
+        let mut engine = Engine::from_config(config, FakeClock::new());
+        let mut backend = MockBackend::new();
+        backend.script(1, &[(KEY_CAPSLOCK, true), (KEY_H, true)]);
+        backend.script(2, &[(KEY_CAPSLOCK, true), (KEY_H, true)]);
+        drain(&mut backend, &mut engine);
+        assert_eq!(backend.sent(), [(1, OutputEvent::press(KEY_LEFT))]);
+        // 绕过的设备的输入不经过引擎，由后端原样发回
+        let forwarded: Vec<_> = backend
+            .forwarded()
+            .iter()
+            .map(|event| (event.device, event.code, event.down))
+            .collect();
+        assert_eq!(forwarded, [(2, KEY_CAPSLOCK, true), (2, KEY_H, true)]);
+    }
+
+    #[test]
+    fn buffered_keys_are_sent_when_tapping_term_expires() {
+        let config = crate::config::Config::parse(
+            "[bindings]\nH = \"Left\"\n\n[tap_hold.CapsLock]\ntap = \"Esc\"\ntapping_term = 100\n",
+            "nuna.toml",
+        )
+        .unwrap();
+        let clock = FakeClock::new();
+        let mut engine = Engine::from_config(config, clock.clone());
+        let mut backend = MockBackend::new();
+        backend.script(KEYBOARD, &[(KEY_CAPSLOCK, true), (KEY_H, true)]);
+        drain(&mut backend, &mut engine);
+        assert!(backend.sent().is_empty());
+
+        // 没有新的输入，等待超时后照样判定为按住
+        clock.advance(Duration::from_millis(100));
+        assert!(!pump(&mut backend, &mut engine, Duration::ZERO));
+        assert_eq!(backend.sent(), [(KEYBOARD, OutputEvent::press(KEY_LEFT))]);
+    }
+
+    #[test]
+    fn run_stops_on_exit_signal() {
+        let (exit_tx, exit_rx) = crossbeam_channel::unbounded();
+        exit_tx.send(()).unwrap();
+        let mut backend = MockBackend::new();
+        backend.script(KEYBOARD, &[(KEY_H, true)]);
+        run(&mut backend, &mut Engine::new(Keymap::builtin()), &exit_rx);
+        assert!(backend.sent().is_empty());
+    }
+}
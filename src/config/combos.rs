@@ -0,0 +1,97 @@
+//! 组合：`[[combos]]` 中的几个键在判定时间内同时按下时输出另一个键，例如 J+K 输出 Esc。
+//! 不写 `layer` 的组合只在没有任何层生效时触发，写了的只在该层生效时触发。
+
+use super::{ConfigError, Layer, is_layer_key, parse_output};
+use crate::oscode::{KeyChord, OsCode};
+use serde::Deserialize;
+use std::time::Duration;
+use toml::Spanned;
+
+/// 默认的判定时间：第一个键按下后，其余的键需在该时间内按下
+const DEFAULT_TERM: u64 = 50;
+
+/// 一个组合
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Combo {
+    /// 需要同时按下的键，已排序
+    pub keys: Vec<OsCode>,
+    /// 触发时输出一次的键或组合键
+    pub output: KeyChord,
+    /// 生效的层，`None` 为没有任何层生效时
+    pub layer: Option<String>,
+    pub term: Duration,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct RawCombo {
+    keys: Spanned<Vec<String>>,
+    output: Spanned<String>,
+    layer: Option<Spanned<String>>,
+    #[serde(default = "default_term")]
+    term: u64,
+}
+
+fn default_term() -> u64 {
+    DEFAULT_TERM
+}
+
+pub(super) fn combos_from_raw(
+    raw_combos: Vec<RawCombo>,
+    layers: &[Layer],
+    layer_names: &[String],
+    src: &str,
+    origin: &str,
+) -> Result<Vec<Combo>, ConfigError> {
+    let mut combos: Vec<Combo> = Vec::with_capacity(raw_combos.len());
+    for raw in raw_combos {
+        let err = |span, msg: String| ConfigError::new(src, origin, Some(span), msg);
+        let mut keys = raw
+            .keys
+            .get_ref()
+            .iter()
+            .map(|name| name.parse::<OsCode>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| err(raw.keys.span(), e.to_string()))?;
+        keys.sort();
+        keys.dedup();
+        if keys.len() < 2 {
+            return Err(err(raw.keys.span(), "组合至少需要两个不同的键".to_string()));
+        }
+        // 修饰键和层键需要立即生效，不能等待组合判定
+        if let Some(key) = keys
+            .iter()
+            .find(|&&key| key.is_modifier() || is_layer_key(key, layers))
+        {
+            return Err(err(
+                raw.keys.span(),
+                format!("\"{key}\" 是修饰键或层键，不能用于组合"),
+            ));
+        }
+        let output =
+            parse_output(raw.output.get_ref()).map_err(|msg| err(raw.output.span(), msg))?;
+        let layer = match &raw.layer {
+            Some(name) if !layer_names.contains(name.get_ref()) => {
+                return Err(err(
+                    name.span(),
+                    format!("未定义的层 \"{}\"", name.get_ref()),
+                ));
+            }
+            Some(name) => Some(name.get_ref().clone()),
+            None => None,
+        };
+        if combos
+            .iter()
+            .any(|combo| combo.keys == keys && combo.layer == layer)
+        {
+            return Err(err(raw.keys.span(), "同一层中的组合重复".to_string()));
+        }
+        combos.push(Combo {
+            keys,
+            output,
+            layer,
+            term: Duration::from_millis(raw.term),
+        });
+    }
+    Ok(combos)
+}
@@ -0,0 +1,102 @@
+//! 按设备选择映射：`[[devices]]` 按硬件 ID 子串或设备编号匹配键盘，
+//! 匹配的键盘可以完全绕过 nuna，或使用 `[profiles.<名称>]` 中的另一套映射。
+
+use super::{ConfigError, Keymap};
+use crate::engine::DeviceId;
+use serde::Deserialize;
+use std::collections::HashMap;
+use toml::Spanned;
+
+/// 匹配设备的方式
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeviceMatch {
+    /// 硬件 ID 包含该子串（不区分大小写），例如 `VID_1050`
+    HardwareId(String),
+    /// 设备编号，即 `nuna devices` 中的编号
+    Index(DeviceId),
+}
+
+impl DeviceMatch {
+    pub fn matches(&self, device: DeviceId, hardware_id: Option<&str>) -> bool {
+        match self {
+            DeviceMatch::HardwareId(pattern) => {
+                hardware_id.is_some_and(|id| id.to_lowercase().contains(&pattern.to_lowercase()))
+            }
+            DeviceMatch::Index(index) => *index == device,
+        }
+    }
+}
+
+/// 匹配的设备如何处理
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeviceAction {
+    /// 事件原样发回，不经过 CapsLock 层
+    Bypass,
+    /// 使用指定名称的映射
+    Profile(String),
+}
+
+/// 一条设备规则，按配置文件中的顺序匹配，第一条匹配的生效
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceRule {
+    pub matcher: DeviceMatch,
+    pub action: DeviceAction,
+}
+
+/// 按规则确定设备如何处理，没有匹配的规则时返回 `None`
+pub fn device_action<'a>(
+    rules: &'a [DeviceRule],
+    device: DeviceId,
+    hardware_id: Option<&str>,
+) -> Option<&'a DeviceAction> {
+    rules
+        .iter()
+        .find(|rule| rule.matcher.matches(device, hardware_id))
+        .map(|rule| &rule.action)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct RawDevice {
+    hardware_id: Option<String>,
+    index: Option<DeviceId>,
+    #[serde(default)]
+    bypass: bool,
+    profile: Option<Spanned<String>>,
+}
+
+pub(super) fn rules_from_raw(
+    raw_devices: Vec<Spanned<RawDevice>>,
+    profiles: &HashMap<String, Keymap>,
+    src: &str,
+    origin: &str,
+) -> Result<Vec<DeviceRule>, ConfigError> {
+    let mut rules = Vec::with_capacity(raw_devices.len());
+    for raw in raw_devices {
+        let err = |msg: &str| ConfigError::new(src, origin, Some(raw.span()), msg.to_string());
+        let device = raw.get_ref();
+        let matcher = match (&device.hardware_id, device.index) {
+            (Some(id), None) if !id.is_empty() => DeviceMatch::HardwareId(id.clone()),
+            (None, Some(index)) => DeviceMatch::Index(index),
+            (Some(_), None) => return Err(err("hardware_id 不能为空")),
+            _ => return Err(err("设备需要指定 hardware_id 或 index 中的一个")),
+        };
+        let action = match (device.bypass, &device.profile) {
+            (true, None) => DeviceAction::Bypass,
+            (false, Some(name)) if profiles.contains_key(name.get_ref()) => {
+                DeviceAction::Profile(name.get_ref().clone())
+            }
+            (false, Some(name)) => {
+                return Err(ConfigError::new(
+                    src,
+                    origin,
+                    Some(name.span()),
+                    format!("未定义的映射 \"{}\"", name.get_ref()),
+                ));
+            }
+            _ => return Err(err("设备需要指定 bypass = true 或 profile 中的一个")),
+        };
+        rules.push(DeviceRule { matcher, action });
+    }
+    Ok(rules)
+}
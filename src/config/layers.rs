@@ -0,0 +1,118 @@
+//! 具名的层：`[layers.<名称>]` 指定激活键、激活方式和未映射键的处理方式，
+//! 映射写在 `[layers.<名称>.bindings]` 中，格式与 `[bindings]` 相同。
+//! `[bindings]` 本身是名为 `caps` 的层，由 CapsLock 按住激活。
+
+use super::{ConfigError, Keymap, Macro, RawBinding};
+use crate::oscode::OsCode;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use toml::Spanned;
+
+/// `[bindings]` 对应的层名
+pub const CAPS_LAYER: &str = "caps";
+
+/// 层的激活方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LayerMode {
+    /// 按住激活键时激活
+    #[default]
+    Momentary,
+    /// 按一下激活，再按一下取消
+    Toggle,
+    /// 切换为基础层，再按一下切换回默认
+    To,
+}
+
+/// 层中未映射的键如何处理
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Fallthrough {
+    /// 原样输出
+    #[default]
+    Pass,
+    /// 不输出（修饰键除外）
+    Block,
+    /// 交给下面一层处理
+    Defer,
+}
+
+/// 一个层
+#[derive(Clone, Debug)]
+pub struct Layer {
+    pub name: String,
+    /// 激活键
+    pub key: OsCode,
+    pub mode: LayerMode,
+    pub fallthrough: Fallthrough,
+    pub keymap: Keymap,
+}
+
+impl Layer {
+    /// `[bindings]` 对应的层：按住 CapsLock 激活，未映射的键原样输出
+    pub fn caps(keymap: Keymap) -> Self {
+        Layer {
+            name: CAPS_LAYER.to_string(),
+            key: OsCode::KEY_CAPSLOCK,
+            mode: LayerMode::Momentary,
+            fallthrough: Fallthrough::Pass,
+            keymap,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct RawLayer {
+    key: Spanned<String>,
+    #[serde(default)]
+    mode: LayerMode,
+    #[serde(default)]
+    fallthrough: Fallthrough,
+    #[serde(default)]
+    bindings: BTreeMap<Spanned<String>, Spanned<RawBinding>>,
+}
+
+pub(super) fn layers_from_raw(
+    raw_layers: BTreeMap<Spanned<String>, RawLayer>,
+    layer_names: &[String],
+    macros: &HashMap<String, Macro>,
+    src: &str,
+    origin: &str,
+) -> Result<Vec<Layer>, ConfigError> {
+    let mut layers: Vec<Layer> = Vec::new();
+    for (name, raw) in raw_layers {
+        let err = |span, msg: String| ConfigError::new(src, origin, Some(span), msg);
+        if name.get_ref() == CAPS_LAYER {
+            return Err(err(
+                name.span(),
+                format!("{CAPS_LAYER} 层由 [bindings] 定义"),
+            ));
+        }
+        let key = raw
+            .key
+            .get_ref()
+            .parse::<OsCode>()
+            .map_err(|e| err(raw.key.span(), e.to_string()))?;
+        if key == OsCode::KEY_CAPSLOCK {
+            return Err(err(
+                raw.key.span(),
+                format!("CapsLock 已用于激活 {CAPS_LAYER} 层"),
+            ));
+        }
+        if let Some(other) = layers.iter().find(|layer| layer.key == key) {
+            return Err(err(
+                raw.key.span(),
+                format!("\"{key}\" 已用于激活 {} 层", other.name),
+            ));
+        }
+        layers.push(Layer {
+            name: name.into_inner(),
+            key,
+            mode: raw.mode,
+            fallthrough: raw.fallthrough,
+            keymap: Keymap::from_raw(raw.bindings, layer_names, macros, src, origin)?,
+        });
+    }
+    Ok(layers)
+}
@@ -0,0 +1,144 @@
+//! leader 键：按下 leader 键（层键为轻触）后，在超时内依次按下 `[leader.bindings]` 中的键，
+//! 例如 `"g s" = "Ctrl+S"`，输出对应的键或组合键。键名以空格分隔；嵌套的表为共同的前缀，
+//! 例如 `[leader.bindings.w]` 中的 `q = "Alt+F4"` 即 `w q`。
+
+use super::sequences::{Sequence, find_conflict};
+use super::{ConfigError, Layer, LayerMode, is_layer_key, parse_output};
+use crate::oscode::OsCode;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use toml::Spanned;
+
+/// 默认超时：按下 leader 键或上一个键后超过该时间没有按键则取消
+const DEFAULT_TIMEOUT: u64 = 1000;
+
+/// `[leader]` 中的设置与所有 leader 序列
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Leader {
+    /// leader 键；层键轻触时启动，其他键按下时启动
+    pub key: OsCode,
+    pub timeout: Duration,
+    /// leader 键之后依次按下的键及其输出，没有序列是另一个的前缀
+    pub sequences: Vec<Sequence>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct RawLeader {
+    key: Spanned<String>,
+    #[serde(default = "default_timeout")]
+    timeout: u64,
+    #[serde(default)]
+    bindings: BTreeMap<Spanned<String>, Spanned<RawLeaderBinding>>,
+}
+
+/// 输出，或者以该键为前缀的一组序列
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawLeaderBinding {
+    Output(String),
+    Prefix(BTreeMap<String, RawLeaderBinding>),
+}
+
+fn default_timeout() -> u64 {
+    DEFAULT_TIMEOUT
+}
+
+pub(super) fn leader_from_raw(
+    raw: RawLeader,
+    layers: &[Layer],
+    src: &str,
+    origin: &str,
+) -> Result<Leader, ConfigError> {
+    let err = |span, msg: String| ConfigError::new(src, origin, Some(span), msg);
+    let key = raw
+        .key
+        .get_ref()
+        .parse::<OsCode>()
+        .map_err(|e| err(raw.key.span(), e.to_string()))?;
+    // 层键需要轻触启动，只有按住激活的层键可以判定轻触
+    let is_other_layer_key = layers
+        .iter()
+        .any(|layer| layer.key == key && layer.mode != LayerMode::Momentary);
+    if key.is_modifier() || is_other_layer_key {
+        return Err(err(
+            raw.key.span(),
+            format!("\"{key}\" 是修饰键或不是按住激活的层键，不能作为 leader 键"),
+        ));
+    }
+
+    let mut sequences = Vec::new();
+    for (trigger, binding) in raw.bindings {
+        let trigger_err = |msg: String| err(trigger.span(), msg);
+        let prefix = parse_keys(trigger.get_ref(), layers).map_err(trigger_err)?;
+        flatten(
+            prefix,
+            binding.into_inner(),
+            layers,
+            &mut sequences,
+            &trigger_err,
+        )?;
+    }
+    Ok(Leader {
+        key,
+        timeout: Duration::from_millis(raw.timeout),
+        sequences,
+    })
+}
+
+/// 将嵌套的表展开为完整的序列；同一条顶层映射中的错误都指向它的键
+fn flatten(
+    prefix: Vec<OsCode>,
+    binding: RawLeaderBinding,
+    layers: &[Layer],
+    sequences: &mut Vec<Sequence>,
+    err: &dyn Fn(String) -> ConfigError,
+) -> Result<(), ConfigError> {
+    match binding {
+        RawLeaderBinding::Prefix(bindings) => {
+            for (trigger, binding) in bindings {
+                let mut keys = prefix.clone();
+                keys.extend(parse_keys(&trigger, layers).map_err(err)?);
+                flatten(keys, binding, layers, sequences, err)?;
+            }
+        }
+        RawLeaderBinding::Output(output) => {
+            // 一个序列是另一个的前缀时，无法确定输入到哪里结束
+            if let Some(other) = find_conflict(sequences, &prefix) {
+                return Err(err(format!(
+                    "leader 序列 \"{}\" 与 \"{}\" 冲突：相同或一个是另一个的前缀",
+                    super::sequences::names(&prefix),
+                    other.names()
+                )));
+            }
+            let output = parse_output(&output).map_err(err)?;
+            sequences.push(Sequence {
+                keys: prefix,
+                output,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// 解析以空格分隔的键名，例如 `"g s"`、`"Space f"`
+fn parse_keys(text: &str, layers: &[Layer]) -> Result<Vec<OsCode>, String> {
+    let keys = text
+        .split_whitespace()
+        .map(|name| name.parse::<OsCode>().map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+    if keys.is_empty() {
+        return Err("leader 序列不能为空".to_string());
+    }
+    // 修饰键照常生效，Esc 与层键取消 leader，都不能用于序列
+    match keys
+        .iter()
+        .find(|&&key| key.is_modifier() || key == OsCode::KEY_ESC || is_layer_key(key, layers))
+    {
+        Some(key) => Err(format!(
+            "\"{key}\" 是修饰键、Esc 或层键，不能用于 leader 序列"
+        )),
+        None => Ok(keys),
+    }
+}
@@ -0,0 +1,159 @@
+//! 宏：`[macros]` 中的一串步骤，映射写成 `M = { macro = "名称" }` 触发。
+//! 步骤可以按下、抬起、轻触键或组合键，按住一段时间，等待，输入文本，以及抬起所有按住的键，
+//! 加载时展开为只有按下、抬起、等待与全部抬起的基本步骤。
+
+use super::{ConfigError, parse_output};
+use crate::oscode::{KeyChord, OsCode};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use toml::Spanned;
+
+/// 宏的基本步骤
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step {
+    Press(OsCode),
+    Release(OsCode),
+    Delay(Duration),
+    /// 抬起宏按住的所有键
+    ReleaseAll,
+}
+
+/// 一个宏
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Macro {
+    pub name: String,
+    pub steps: Vec<Step>,
+}
+
+/// 配置中的一个步骤，例如 `{ tap = "Ctrl+S" }`、`{ hold = "Space", ms = 300 }`、`{ text = "Hi" }`。
+/// 每个步骤只能指定一种动作，`ms` 只用于 `hold`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct RawStep {
+    press: Option<String>,
+    release: Option<String>,
+    tap: Option<String>,
+    hold: Option<String>,
+    ms: Option<u64>,
+    delay: Option<u64>,
+    text: Option<String>,
+    release_all: Option<bool>,
+}
+
+pub(super) fn macros_from_raw(
+    raw_macros: BTreeMap<Spanned<String>, Vec<Spanned<RawStep>>>,
+    src: &str,
+    origin: &str,
+) -> Result<HashMap<String, Macro>, ConfigError> {
+    let mut macros = HashMap::new();
+    for (name, raw_steps) in raw_macros {
+        let err = |span, msg: String| ConfigError::new(src, origin, Some(span), msg);
+        if raw_steps.is_empty() {
+            return Err(err(name.span(), "宏至少需要一个步骤".to_string()));
+        }
+        let mut steps = Vec::new();
+        for raw in raw_steps {
+            expand(raw.get_ref(), &mut steps).map_err(|msg| err(raw.span(), msg))?;
+        }
+        let name = name.into_inner();
+        macros.insert(name.clone(), Macro { name, steps });
+    }
+    Ok(macros)
+}
+
+/// 将一个步骤展开为基本步骤
+fn expand(raw: &RawStep, steps: &mut Vec<Step>) -> Result<(), String> {
+    let actions = [
+        raw.press.is_some(),
+        raw.release.is_some(),
+        raw.tap.is_some(),
+        raw.hold.is_some(),
+        raw.delay.is_some(),
+        raw.text.is_some(),
+        raw.release_all.is_some(),
+    ];
+    if actions.iter().filter(|&&action| action).count() != 1 {
+        return Err(
+            "每个步骤需要指定 press、release、tap、hold、delay、text、release_all 中的一个"
+                .to_string(),
+        );
+    }
+    if raw.ms.is_some() && raw.hold.is_none() {
+        return Err("ms 只能与 hold 一起使用".to_string());
+    }
+    if let Some(press) = &raw.press {
+        steps.extend(parse_output(press)?.keys().map(Step::Press));
+    } else if let Some(release) = &raw.release {
+        steps.extend(parse_output(release)?.keys().rev().map(Step::Release));
+    } else if let Some(tap) = &raw.tap {
+        push_tap(&parse_output(tap)?, None, steps);
+    } else if let Some(hold) = &raw.hold {
+        let ms = raw.ms.ok_or("hold 需要用 ms 指定按住的毫秒数")?;
+        push_tap(&parse_output(hold)?, Some(Duration::from_millis(ms)), steps);
+    } else if let Some(delay) = raw.delay {
+        steps.push(Step::Delay(Duration::from_millis(delay)));
+    } else if let Some(text) = &raw.text {
+        for c in text.chars() {
+            let chord = char_chord(c).ok_or_else(|| format!("无法输入字符 {c:?}"))?;
+            push_tap(&chord, None, steps);
+        }
+    } else if raw.release_all == Some(true) {
+        steps.push(Step::ReleaseAll);
+    } else {
+        return Err("release_all 只能为 true".to_string());
+    }
+    Ok(())
+}
+
+/// 按下组合键，等待 `hold` 后按相反的顺序抬起
+fn push_tap(chord: &KeyChord, hold: Option<Duration>, steps: &mut Vec<Step>) {
+    steps.extend(chord.keys().map(Step::Press));
+    steps.extend(hold.map(Step::Delay));
+    steps.extend(chord.keys().rev().map(Step::Release));
+}
+
+/// 美式键盘布局下输入一个字符所需的组合键
+fn char_chord(c: char) -> Option<KeyChord> {
+    const SHIFTED: [(char, char); 21] = [
+        ('!', '1'),
+        ('@', '2'),
+        ('#', '3'),
+        ('$', '4'),
+        ('%', '5'),
+        ('^', '6'),
+        ('&', '7'),
+        ('*', '8'),
+        ('(', '9'),
+        (')', '0'),
+        ('_', '-'),
+        ('+', '='),
+        ('{', '['),
+        ('}', ']'),
+        ('|', '\\'),
+        (':', ';'),
+        ('"', '\''),
+        ('~', '`'),
+        ('<', ','),
+        ('>', '.'),
+        ('?', '/'),
+    ];
+    let (shift, base) = match SHIFTED.iter().find(|&&(shifted, _)| shifted == c) {
+        Some(&(_, base)) => (true, base),
+        None if c.is_ascii_uppercase() => (true, c.to_ascii_lowercase()),
+        None => (false, c),
+    };
+    let key = match base {
+        ' ' => OsCode::KEY_SPACE,
+        '\n' => OsCode::KEY_ENTER,
+        '\t' => OsCode::KEY_TAB,
+        c if c.is_ascii_graphic() => c.to_string().parse().ok()?,
+        _ => return None,
+    };
+    let modifiers = if shift {
+        vec![OsCode::KEY_LEFTSHIFT]
+    } else {
+        Vec::new()
+    };
+    Some(KeyChord { modifiers, key })
+}
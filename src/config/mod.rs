@@ -0,0 +1,1127 @@
+//! 配置文件加载：从 nuna.toml 读取 CapsLock 层的键位映射，
+//! 未找到配置文件时使用内置的默认映射（即 README 中列出的键位）。
+//! Linux 下还可以通过 `[evdev]` 指定要拦截的键盘设备。
+
+mod combos;
+mod devices;
+mod layers;
+mod leader;
+mod macros;
+mod one_shot;
+mod overrides;
+mod sequences;
+mod tap_hold;
+
+pub use combos::Combo;
+pub use devices::{DeviceAction, DeviceRule, device_action};
+pub use layers::{Fallthrough, Layer, LayerMode};
+pub use leader::Leader;
+pub use macros::{Macro, Step};
+pub use one_shot::{OneShot, OneShotOptions};
+pub use overrides::Overrides;
+pub use sequences::{Rollback, Sequence, Sequences};
+pub use tap_hold::TapHold;
+
+use crate::oscode::{KeyChord, OsCode};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use toml::Spanned;
+
+/// 配置文件名，放在可执行文件所在目录下
+pub const CONFIG_FILE_NAME: &str = "nuna.toml";
+
+/// 内置默认配置
+pub const DEFAULT_CONFIG: &str = include_str!("../../assets/nuna.toml");
+
+/// 完整的配置
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    pub keymap: Keymap,
+    /// 要拦截的 evdev 键盘，可以是设备名或 `/dev/input/event*` 路径；为空时拦截所有键盘
+    #[allow(unused)]
+    pub evdev_devices: Vec<String>,
+    /// 所有键盘共用层与修饰键状态，默认每个键盘各自独立
+    pub shared_layers: bool,
+    /// 具名的映射，供设备规则选用
+    pub profiles: HashMap<String, Keymap>,
+    /// 设备规则，未匹配任何规则的设备使用 `keymap`
+    pub devices: Vec<DeviceRule>,
+    /// 层键的 tap-hold 设置，未设置的层键按下即激活层
+    pub tap_hold: HashMap<OsCode, TapHold>,
+    /// `[bindings]` 以外的具名层
+    pub layers: Vec<Layer>,
+    /// 基础层的按键覆盖
+    pub overrides: Overrides,
+    /// 一次性修饰键与层的设置
+    pub one_shot: OneShotOptions,
+    /// 同时按下几个键触发的组合
+    pub combos: Vec<Combo>,
+    /// leader 键及其之后输入的序列，未设置时为 `None`
+    pub leader: Option<Leader>,
+    /// 按顺序输入几个键触发的序列
+    pub sequences: Sequences,
+}
+
+impl Config {
+    /// 从指定文件加载配置
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let src = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("无法读取配置文件 {}: {e}", path.display()))?;
+        Ok(Self::parse(&src, &path.display().to_string())?)
+    }
+
+    /// 解析配置文本，`origin` 为报错时显示的文件名
+    pub fn parse(src: &str, origin: &str) -> Result<Self, ConfigError> {
+        let raw: RawConfig = toml::from_str(src)
+            .map_err(|e| ConfigError::new(src, origin, e.span(), e.message().to_string()))?;
+        // 一次性层可以引用任意层，包括后面才定义的
+        let layer_names: Vec<String> = raw
+            .layers
+            .keys()
+            .map(|name| name.get_ref().clone())
+            .chain([layers::CAPS_LAYER.to_string()])
+            .collect();
+        let macros = macros::macros_from_raw(raw.macros, src, origin)?;
+        let layers = layers::layers_from_raw(raw.layers, &layer_names, &macros, src, origin)?;
+        let leader = raw
+            .leader
+            .map(|raw| leader::leader_from_raw(raw, &layers, src, origin))
+            .transpose()?;
+        let leader_key = leader.as_ref().map(|leader| leader.key);
+        let mut tap_hold =
+            tap_hold::tap_holds_from_raw(raw.tap_hold, &layers, leader_key, src, origin)?;
+        // 作为 leader 键的层键没有设置时按默认方式判定轻触
+        if let Some(key) = leader_key
+            && is_layer_key(key, &layers)
+        {
+            tap_hold
+                .entry(key)
+                .or_insert_with(tap_hold::leader_tap_hold);
+        }
+        let mut profiles = HashMap::new();
+        for (name, bindings) in raw.profiles {
+            profiles.insert(
+                name,
+                Keymap::from_raw(bindings, &layer_names, &macros, src, origin)?,
+            );
+        }
+        Ok(Config {
+            keymap: Keymap::from_raw(raw.bindings, &layer_names, &macros, src, origin)?,
+            evdev_devices: raw.evdev.devices,
+            shared_layers: raw.options.shared_layers,
+            devices: devices::rules_from_raw(raw.devices, &profiles, src, origin)?,
+            profiles,
+            tap_hold,
+            combos: combos::combos_from_raw(raw.combos, &layers, &layer_names, src, origin)?,
+            sequences: sequences::sequences_from_raw(raw.sequences, &layers, src, origin)?,
+            layers,
+            overrides: overrides::overrides_from_raw(raw.overrides, src, origin)?,
+            one_shot: raw.one_shot.into(),
+            leader,
+        })
+    }
+}
+
+/// 一个键位映射：输出的组合键及其选项
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Binding {
+    pub output: KeyChord,
+    /// 按住触发键时是否随自动重复再次输出，关闭后只在按下时输出一次
+    pub repeat: bool,
+    /// 输出期间暂时抬起的物理修饰键（按种类，不区分左右），其余按住的修饰键与输出组合
+    pub suppress: Vec<OsCode>,
+}
+
+/// CapsLock 层的键位映射表：触发键 -> 映射
+#[derive(Clone, Debug, Default)]
+pub struct Keymap {
+    bindings: HashMap<OsCode, Binding>,
+    /// 带修饰键的触发键，例如 `"Shift+H"`：(触发键, 修饰键种类，已排序) -> 映射
+    overrides: HashMap<(OsCode, Vec<OsCode>), Binding>,
+    /// 触发一次性修饰键或层的键
+    one_shots: HashMap<OsCode, OneShot>,
+    /// 触发宏的键
+    macros: HashMap<OsCode, Macro>,
+}
+
+impl Keymap {
+    /// 查找触发键对应的映射
+    pub fn get(&self, code: OsCode) -> Option<&Binding> {
+        self.bindings.get(&code)
+    }
+
+    /// 查找按住 `modifiers` 时触发键对应的映射：修饰键种类完全一致的映射优先，
+    /// 否则使用不带修饰键的映射
+    pub fn lookup(&self, code: OsCode, modifiers: &[OsCode]) -> Option<&Binding> {
+        if !self.overrides.is_empty() {
+            let kinds = modifier_kinds(modifiers.iter().copied());
+            if let Some(binding) = self.overrides.get(&(code, kinds)) {
+                return Some(binding);
+            }
+        }
+        self.get(code)
+    }
+
+    /// 查找触发键对应的一次性修饰键或层
+    pub fn one_shot(&self, code: OsCode) -> Option<&OneShot> {
+        self.one_shots.get(&code)
+    }
+
+    /// 查找触发键对应的宏
+    pub fn get_macro(&self, code: OsCode) -> Option<&Macro> {
+        self.macros.get(&code)
+    }
+
+    pub fn len(&self) -> usize {
+        self.bindings.len() + self.overrides.len() + self.one_shots.len() + self.macros.len()
+    }
+
+    /// 内置的默认映射
+    pub fn builtin() -> Self {
+        Self::parse(DEFAULT_CONFIG, "<内置配置>").expect("内置配置解析失败")
+    }
+
+    /// 解析配置文本中的映射，`origin` 为报错时显示的文件名
+    pub fn parse(src: &str, origin: &str) -> Result<Self, ConfigError> {
+        Config::parse(src, origin).map(|config| config.keymap)
+    }
+
+    fn from_raw(
+        raw_bindings: BTreeMap<Spanned<String>, Spanned<RawBinding>>,
+        layer_names: &[String],
+        macros: &HashMap<String, Macro>,
+        src: &str,
+        origin: &str,
+    ) -> Result<Self, ConfigError> {
+        let mut keymap = Keymap::default();
+        for (trigger, binding) in raw_bindings {
+            let err =
+                |span: Range<usize>, msg: String| ConfigError::new(src, origin, Some(span), msg);
+            let trigger_chord = trigger
+                .get_ref()
+                .parse::<KeyChord>()
+                .map_err(|e| err(trigger.span(), e.to_string()))?;
+            let code = trigger_chord.key;
+            if code == OsCode::KEY_CAPSLOCK {
+                return Err(err(trigger.span(), "CapsLock 不能作为触发键".to_string()));
+            }
+            // 带修饰键的触发键默认在输出期间抬起这些修饰键
+            let kinds = modifier_kinds(trigger_chord.modifiers.iter().copied());
+            let (output, repeat) = match binding.get_ref() {
+                RawBinding::Output(output) => (output, true),
+                RawBinding::Table { output, repeat } => (output, *repeat),
+                RawBinding::OneShot { one_shot } => {
+                    let one_shot = one_shot::modifier_from_raw(one_shot)
+                        .map_err(|msg| err(binding.span(), msg))?;
+                    keymap.insert_one_shot(code, &kinds, one_shot, &err, &trigger)?;
+                    continue;
+                }
+                RawBinding::OneShotLayer { one_shot_layer } => {
+                    let one_shot = one_shot::layer_from_raw(one_shot_layer, layer_names)
+                        .map_err(|msg| err(binding.span(), msg))?;
+                    keymap.insert_one_shot(code, &kinds, one_shot, &err, &trigger)?;
+                    continue;
+                }
+                RawBinding::Macro { name } => {
+                    let Some(mac) = macros.get(name) else {
+                        return Err(err(binding.span(), format!("未定义的宏 \"{name}\"")));
+                    };
+                    keymap.insert_macro(code, &kinds, mac.clone(), &err, &trigger)?;
+                    continue;
+                }
+            };
+            let chord = parse_output(output).map_err(|msg| err(binding.span(), msg))?;
+            let binding = Binding {
+                output: chord,
+                repeat,
+                suppress: kinds.clone(),
+            };
+            let duplicate = if kinds.is_empty() {
+                keymap.one_shots.contains_key(&code)
+                    || keymap.macros.contains_key(&code)
+                    || keymap.bindings.insert(code, binding).is_some()
+            } else {
+                keymap.overrides.insert((code, kinds), binding).is_some()
+            };
+            if duplicate {
+                return Err(err(
+                    trigger.span(),
+                    format!("触发键 \"{trigger_chord}\" 重复（修饰键不区分左右）"),
+                ));
+            }
+        }
+        Ok(keymap)
+    }
+
+    fn insert_one_shot(
+        &mut self,
+        code: OsCode,
+        kinds: &[OsCode],
+        one_shot: OneShot,
+        err: &dyn Fn(Range<usize>, String) -> ConfigError,
+        trigger: &Spanned<String>,
+    ) -> Result<(), ConfigError> {
+        if !kinds.is_empty() {
+            return Err(err(
+                trigger.span(),
+                "一次性修饰键或层的触发键不能带修饰键".to_string(),
+            ));
+        }
+        if self.bindings.contains_key(&code)
+            || self.macros.contains_key(&code)
+            || self.one_shots.insert(code, one_shot).is_some()
+        {
+            return Err(err(trigger.span(), format!("触发键 \"{code}\" 重复")));
+        }
+        Ok(())
+    }
+
+    fn insert_macro(
+        &mut self,
+        code: OsCode,
+        kinds: &[OsCode],
+        mac: Macro,
+        err: &dyn Fn(Range<usize>, String) -> ConfigError,
+        trigger: &Spanned<String>,
+    ) -> Result<(), ConfigError> {
+        if !kinds.is_empty() {
+            return Err(err(trigger.span(), "宏的触发键不能带修饰键".to_string()));
+        }
+        if self.bindings.contains_key(&code)
+            || self.one_shots.contains_key(&code)
+            || self.macros.insert(code, mac).is_some()
+        {
+            return Err(err(trigger.span(), format!("触发键 \"{code}\" 重复")));
+        }
+        Ok(())
+    }
+}
+
+/// 解析映射的输出，其中每个键都需要有对应的扫描码
+pub(super) fn parse_output(text: &str) -> Result<KeyChord, String> {
+    let chord = text.parse::<KeyChord>().map_err(|e| e.to_string())?;
+    if let Some(key) = chord.keys().find(|key| !key.is_encodable()) {
+        return Err(format!("\"{key}\" 没有对应的扫描码，无法输出"));
+    }
+    Ok(chord)
+}
+
+/// CapsLock 或具名层的激活键
+pub(super) fn is_layer_key(key: OsCode, layers: &[Layer]) -> bool {
+    key == OsCode::KEY_CAPSLOCK || layers.iter().any(|layer| layer.key == key)
+}
+
+/// 修饰键的种类，排序并去重，用作带修饰键的触发键的查找键
+fn modifier_kinds(modifiers: impl Iterator<Item = OsCode>) -> Vec<OsCode> {
+    let mut kinds: Vec<OsCode> = modifiers.map(OsCode::modifier_kind).collect();
+    kinds.sort();
+    kinds.dedup();
+    kinds
+}
+
+/// 加载配置：优先读取可执行文件所在目录下的 nuna.toml，不存在则使用内置默认映射。
+/// 配置文件存在但无效时返回错误。
+pub fn load() -> anyhow::Result<Config> {
+    match config_path() {
+        Some(path) if path.exists() => {
+            log::info!("加载配置文件: {}", path.display());
+            Config::from_file(&path)
+        }
+        _ => {
+            log::info!("未找到配置文件 {CONFIG_FILE_NAME}，使用内置默认映射");
+            Ok(Config {
+                keymap: Keymap::builtin(),
+                ..Config::default()
+            })
+        }
+    }
+}
+
+/// 配置文件的路径：可执行文件所在目录下的 nuna.toml
+fn config_path() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    Some(exe.parent()?.join(CONFIG_FILE_NAME))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawConfig {
+    #[serde(default)]
+    bindings: BTreeMap<Spanned<String>, Spanned<RawBinding>>,
+    #[serde(default)]
+    evdev: RawEvdev,
+    #[serde(default)]
+    options: RawOptions,
+    #[serde(default)]
+    profiles: BTreeMap<String, BTreeMap<Spanned<String>, Spanned<RawBinding>>>,
+    #[serde(default)]
+    devices: Vec<Spanned<devices::RawDevice>>,
+    #[serde(default)]
+    tap_hold: BTreeMap<Spanned<String>, tap_hold::RawTapHold>,
+    #[serde(default)]
+    layers: BTreeMap<Spanned<String>, layers::RawLayer>,
+    #[serde(default)]
+    overrides: Vec<overrides::RawOverride>,
+    #[serde(default)]
+    one_shot: one_shot::RawOneShotOptions,
+    #[serde(default)]
+    combos: Vec<combos::RawCombo>,
+    #[serde(default)]
+    sequences: sequences::RawSequences,
+    leader: Option<leader::RawLeader>,
+    #[serde(default)]
+    macros: BTreeMap<Spanned<String>, Vec<Spanned<macros::RawStep>>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawOptions {
+    #[serde(default)]
+    shared_layers: bool,
+}
+
+/// 映射可以只写输出，也可以写成带选项的表，例如 `S = { output = "Ctrl+S", repeat = false }`，
+/// 或者触发一次性修饰键或层，例如 `O = { one_shot = "Ctrl" }`，或者触发宏，例如 `M = { macro = "sig" }`
+#[derive(Debug)]
+enum RawBinding {
+    Output(String),
+    Table { output: String, repeat: bool },
+    OneShot { one_shot: String },
+    OneShotLayer { one_shot_layer: String },
+    Macro { name: String },
+}
+
+/// 写成表的映射，拒绝未知的字段
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawBindingTable {
+    output: Option<String>,
+    repeat: Option<bool>,
+    one_shot: Option<String>,
+    one_shot_layer: Option<String>,
+    #[serde(rename = "macro")]
+    name: Option<String>,
+}
+
+impl RawBindingTable {
+    /// 检查表中恰好指定了一种动作
+    fn into_binding(self) -> Result<RawBinding, String> {
+        let actions = [
+            self.output.is_some(),
+            self.one_shot.is_some(),
+            self.one_shot_layer.is_some(),
+            self.name.is_some(),
+        ];
+        match actions.iter().filter(|&&action| action).count() {
+            0 => return Err("映射需要指定 output、one_shot、one_shot_layer 或 macro".to_string()),
+            1 => {}
+            _ => {
+                return Err(
+                    "output、one_shot、one_shot_layer 和 macro 只能指定其中一个".to_string()
+                );
+            }
+        }
+        if self.repeat.is_some() && self.output.is_none() {
+            return Err("repeat 只能与 output 一起使用".to_string());
+        }
+        Ok(if let Some(output) = self.output {
+            RawBinding::Table {
+                output,
+                repeat: self.repeat.unwrap_or(true),
+            }
+        } else if let Some(one_shot) = self.one_shot {
+            RawBinding::OneShot { one_shot }
+        } else if let Some(one_shot_layer) = self.one_shot_layer {
+            RawBinding::OneShotLayer { one_shot_layer }
+        } else {
+            RawBinding::Macro {
+                name: self.name.unwrap_or_default(),
+            }
+        })
+    }
+}
+
+/// 不用 `#[serde(untagged)]`：它会吞掉表中字段的具体错误
+impl<'de> Deserialize<'de> for RawBinding {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BindingVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for BindingVisitor {
+            type Value = RawBinding;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("输出字符串或映射表")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, output: &str) -> Result<RawBinding, E> {
+                Ok(RawBinding::Output(output.to_string()))
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                map: A,
+            ) -> Result<RawBinding, A::Error> {
+                let table = RawBindingTable::deserialize(
+                    serde::de::value::MapAccessDeserializer::new(map),
+                )?;
+                table.into_binding().map_err(serde::de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_any(BindingVisitor)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawEvdev {
+    #[serde(default)]
+    devices: Vec<String>,
+}
+
+/// 配置错误，带有出错位置（行号、列号均从 1 开始）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub origin: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl ConfigError {
+    fn new(src: &str, origin: &str, span: Option<Range<usize>>, message: String) -> Self {
+        let (line, column) = span.map_or((1, 1), |span| line_column(src, span.start));
+        ConfigError {
+            origin: origin.to_string(),
+            line,
+            column,
+            message: message.trim_end().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.origin, self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// 将字节偏移转换为行号和列号（列号按字符计数）
+fn line_column(src: &str, offset: usize) -> (usize, usize) {
+    let offset = offset.min(src.len());
+    let before = &src[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::devices::DeviceMatch;
+    use super::*;
+
+    #[test]
+    fn builtin_covers_readme_bindings() {
+        let keymap = Keymap::builtin();
+        assert_eq!(keymap.len(), 17);
+        assert_eq!(
+            keymap.get(OsCode::KEY_A).map(|b| &b.output),
+            Some(&KeyChord::single(OsCode::KEY_HOME))
+        );
+        assert_eq!(
+            keymap.get(OsCode::KEY_B).map(|b| &b.output),
+            Some(&KeyChord {
+                modifiers: vec![OsCode::KEY_LEFTCTRL],
+                key: OsCode::KEY_LEFT,
+            })
+        );
+        assert!(keymap.get(OsCode::KEY_G).is_none());
+    }
+
+    #[test]
+    fn reports_unknown_key_with_position() {
+        let src = "[bindings]\nA = \"Home\"\nU = \"Ctrl+Hme\"\n";
+        let err = Keymap::parse(src, "nuna.toml").unwrap_err();
+        assert_eq!((err.line, err.column), (3, 5));
+        assert!(err.message.contains("Hme"), "{err}");
+        assert!(err.message.contains("是否想输入"), "{err}");
+        assert!(err.to_string().starts_with("nuna.toml:3:5:"));
+    }
+
+    #[test]
+    fn reports_syntax_error_with_position() {
+        let src = "[bindings]\nA = Home\n";
+        let err = Keymap::parse(src, "nuna.toml").unwrap_err();
+        assert_eq!(err.line, 2);
+    }
+
+    #[test]
+    fn accepts_pause_output() {
+        let keymap = Keymap::parse("[bindings]\nP = \"Pause\"\n", "nuna.toml").unwrap();
+        assert_eq!(
+            keymap.get(OsCode::KEY_P).map(|b| &b.output),
+            Some(&KeyChord::single(OsCode::KEY_PAUSE))
+        );
+    }
+
+    #[test]
+    fn parses_modified_triggers() {
+        let src = "[bindings]\nH = \"Left\"\n\"Ctrl+Shift+H\" = \"Home\"\n";
+        let keymap = Keymap::parse(src, "nuna.toml").unwrap();
+        let modifiers = [OsCode::KEY_RIGHTSHIFT, OsCode::KEY_LEFTCTRL];
+        let home = keymap.lookup(OsCode::KEY_H, &modifiers).unwrap();
+        assert_eq!(home.output, KeyChord::single(OsCode::KEY_HOME));
+        assert_eq!(home.suppress, [OsCode::KEY_LEFTCTRL, OsCode::KEY_LEFTSHIFT]);
+        let left = keymap
+            .lookup(OsCode::KEY_H, &[OsCode::KEY_LEFTSHIFT])
+            .unwrap();
+        assert_eq!(left.output, KeyChord::single(OsCode::KEY_LEFT));
+        assert!(left.suppress.is_empty());
+
+        let src = "[bindings]\n\"LShift+H\" = \"Home\"\n\"Shift+H\" = \"End\"\n";
+        let err = Keymap::parse(src, "nuna.toml").unwrap_err();
+        assert_eq!((err.line, err.column), (3, 1));
+        assert!(err.message.contains("重复"), "{err}");
+    }
+
+    #[test]
+    fn parses_binding_options() {
+        let src = "[bindings]\nS = { output = \"Ctrl+S\", repeat = false }\nZ = { output = \"Ctrl+Z\" }\n";
+        let keymap = Keymap::parse(src, "nuna.toml").unwrap();
+        let save = keymap.get(OsCode::KEY_S).unwrap();
+        assert_eq!(save.output.to_string(), "Ctrl+S");
+        assert!(!save.repeat);
+        assert!(keymap.get(OsCode::KEY_Z).unwrap().repeat);
+        assert!(!Keymap::builtin().get(OsCode::KEY_S).unwrap().repeat);
+
+        let err =
+            Keymap::parse("[bindings]\nS = { ouput = \"Ctrl+S\" }\n", "nuna.toml").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 7));
+    }
+
+    #[test]
+    fn rejects_unknown_or_conflicting_binding_fields() {
+        let err = Keymap::parse(
+            "[bindings]\nS = { output = \"Ctrl+S\", repaet = false }\n",
+            "nuna.toml",
+        )
+        .unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(err.message.contains("repaet"), "{err}");
+
+        let err = Keymap::parse(
+            "[bindings]\nX = { output = \"X\", macro = \"sig\" }\n\n[macros]\nsig = [{ text = \"x\" }]\n",
+            "nuna.toml",
+        )
+        .unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(err.message.contains("只能指定其中一个"), "{err}");
+
+        let err = Keymap::parse(
+            "[bindings]\nO = { one_shot = \"Ctrl\", repeat = false }\n",
+            "nuna.toml",
+        )
+        .unwrap_err();
+        assert!(err.message.contains("repeat"), "{err}");
+        let err = Keymap::parse("[bindings]\nO = {}\n", "nuna.toml").unwrap_err();
+        assert_eq!(err.line, 2);
+    }
+
+    #[test]
+    fn parses_shared_layers() {
+        let config = Config::parse("[options]\nshared_layers = true\n", "nuna.toml").unwrap();
+        assert!(config.shared_layers);
+        assert!(!Config::parse("", "nuna.toml").unwrap().shared_layers);
+    }
+
+    #[test]
+    fn parses_evdev_devices() {
+        let src = "[evdev]\ndevices = [\"AT Translated Set 2 keyboard\", \"/dev/input/event3\"]\n\n[bindings]\nA = \"Home\"\n";
+        let config = Config::parse(src, "nuna.toml").unwrap();
+        assert_eq!(
+            config.evdev_devices,
+            ["AT Translated Set 2 keyboard", "/dev/input/event3"]
+        );
+        assert_eq!(config.keymap.len(), 1);
+        assert!(
+            Config::parse("", "nuna.toml")
+                .unwrap()
+                .evdev_devices
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn parses_device_rules_and_profiles() {
+        let src = r#"
+[bindings]
+H = "Left"
+
+[profiles.qmk]
+H = "Home"
+
+[[devices]]
+hardware_id = "VID_1050"
+bypass = true
+
+[[devices]]
+index = 3
+profile = "qmk"
+"#;
+        let config = Config::parse(src, "nuna.toml").unwrap();
+        assert_eq!(
+            config.devices,
+            [
+                DeviceRule {
+                    matcher: DeviceMatch::HardwareId("VID_1050".to_string()),
+                    action: DeviceAction::Bypass,
+                },
+                DeviceRule {
+                    matcher: DeviceMatch::Index(3),
+                    action: DeviceAction::Profile("qmk".to_string()),
+                },
+            ]
+        );
+        assert_eq!(
+            config.profiles["qmk"].get(OsCode::KEY_H).unwrap().output,
+            KeyChord::single(OsCode::KEY_HOME)
+        );
+        assert!(
+            config.devices[0]
+                .matcher
+                .matches(5, Some("HID\\vid_1050&PID_0407"))
+        );
+        assert!(!config.devices[0].matcher.matches(5, None));
+        assert!(config.devices[1].matcher.matches(3, None));
+    }
+
+    #[test]
+    fn rejects_invalid_device_rules() {
+        let unknown_profile = "[[devices]]\nindex = 1\nprofile = \"qmk\"\n";
+        let err = Config::parse(unknown_profile, "nuna.toml").unwrap_err();
+        assert_eq!((err.line, err.column), (3, 11));
+        assert!(err.message.contains("qmk"), "{err}");
+
+        let no_matcher = "[[devices]]\nbypass = true\n";
+        assert!(Config::parse(no_matcher, "nuna.toml").is_err());
+        let both_actions = "[profiles.a]\n[[devices]]\nindex = 1\nbypass = true\nprofile = \"a\"\n";
+        assert!(Config::parse(both_actions, "nuna.toml").is_err());
+
+        let bad_binding = "[profiles.qmk]\nH = \"Hoem\"\n";
+        let err = Config::parse(bad_binding, "nuna.toml").unwrap_err();
+        assert_eq!(err.line, 2);
+    }
+
+    #[test]
+    fn parses_tap_hold() {
+        let src = "[tap_hold.CapsLock]\ntap = \"Esc\"\npermissive_hold = true\n";
+        let config = Config::parse(src, "nuna.toml").unwrap();
+        assert_eq!(
+            config.tap_hold[&OsCode::KEY_CAPSLOCK],
+            TapHold {
+                tap: Some(KeyChord::single(OsCode::KEY_ESC)),
+                tapping_term: std::time::Duration::from_millis(200),
+                hold_on_other_key_press: false,
+                permissive_hold: true,
+                retro_tap: false,
+            }
+        );
+
+        let err = Config::parse("[tap_hold.Tab]\ntap = \"Esc\"\n", "nuna.toml").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 11));
+        let err = Config::parse("[tap_hold.CapsLock]\ntap = \"Ecs\"\n", "nuna.toml").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 7));
+    }
+
+    #[test]
+    fn parses_layers() {
+        let src = r#"
+[layers.symbols]
+key = "RightAlt"
+fallthrough = "block"
+
+[layers.symbols.bindings]
+Q = "Shift+1"
+
+[layers.numbers]
+key = "Tab"
+mode = "toggle"
+"#;
+        let config = Config::parse(src, "nuna.toml").unwrap();
+        let [numbers, symbols] = &config.layers[..] else {
+            panic!("{:?}", config.layers);
+        };
+        assert_eq!(
+            (
+                symbols.name.as_str(),
+                symbols.key,
+                symbols.mode,
+                symbols.fallthrough
+            ),
+            (
+                "symbols",
+                OsCode::KEY_RIGHTALT,
+                LayerMode::Momentary,
+                Fallthrough::Block
+            )
+        );
+        assert_eq!(
+            symbols
+                .keymap
+                .get(OsCode::KEY_Q)
+                .unwrap()
+                .output
+                .to_string(),
+            "Shift+1"
+        );
+        assert_eq!(
+            (numbers.key, numbers.mode, numbers.fallthrough),
+            (OsCode::KEY_TAB, LayerMode::Toggle, Fallthrough::Pass)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_layers() {
+        let duplicate = "[layers.a]\nkey = \"Tab\"\n[layers.b]\nkey = \"Tab\"\n";
+        let err = Config::parse(duplicate, "nuna.toml").unwrap_err();
+        assert_eq!((err.line, err.column), (4, 7));
+        for src in [
+            "[layers.caps]\nkey = \"Tab\"\n",
+            "[layers.a]\nkey = \"CapsLock\"\n",
+            "[layers.a]\nkey = \"Tab\"\nmode = \"hold\"\n",
+            "[layers.a]\nkey = \"Tab\"\nmode = \"toggle\"\n[tap_hold.Tab]\ntap = \"Tab\"\n",
+        ] {
+            assert!(Config::parse(src, "nuna.toml").is_err(), "{src}");
+        }
+        let momentary = "[layers.a]\nkey = \"Tab\"\n[tap_hold.Tab]\ntap = \"Tab\"\n";
+        assert!(Config::parse(momentary, "nuna.toml").is_ok());
+    }
+
+    #[test]
+    fn parses_overrides() {
+        let src = r#"
+[[overrides]]
+trigger = "Shift+Backspace"
+output = "Delete"
+
+[[overrides]]
+trigger = "Shift+Esc"
+output = "Grave"
+suppress = []
+
+[[overrides]]
+trigger = "Ctrl+Shift+Q"
+"#;
+        let overrides = Config::parse(src, "nuna.toml").unwrap().overrides;
+        assert_eq!(overrides.len(), 3);
+        let delete = overrides
+            .lookup(OsCode::KEY_BACKSPACE, &[OsCode::KEY_RIGHTSHIFT])
+            .unwrap()
+            .unwrap();
+        assert_eq!(delete.output, KeyChord::single(OsCode::KEY_DELETE));
+        assert_eq!(delete.suppress, [OsCode::KEY_LEFTSHIFT]);
+        let grave = overrides
+            .lookup(OsCode::KEY_ESC, &[OsCode::KEY_LEFTSHIFT])
+            .unwrap()
+            .unwrap();
+        assert!(grave.suppress.is_empty());
+        let modifiers = [OsCode::KEY_LEFTCTRL, OsCode::KEY_LEFTSHIFT];
+        assert_eq!(overrides.lookup(OsCode::KEY_Q, &modifiers), Some(None));
+        assert_eq!(
+            overrides.lookup(OsCode::KEY_Q, &[OsCode::KEY_LEFTCTRL]),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_overrides() {
+        for src in [
+            "[[overrides]]\ntrigger = \"Backspace\"\noutput = \"Delete\"\n",
+            "[[overrides]]\ntrigger = \"Ctrl+Shift\"\n",
+            "[[overrides]]\ntrigger = \"Shift+A\"\noutput = \"B\"\nsuppress = [\"A\"]\n",
+            "[[overrides]]\ntrigger = \"Shift+A\"\nsuppress = [\"Shift\"]\n",
+            "[[overrides]]\ntrigger = \"Shift+A\"\n[[overrides]]\ntrigger = \"RShift+A\"\n",
+        ] {
+            assert!(Config::parse(src, "nuna.toml").is_err(), "{src}");
+        }
+    }
+
+    #[test]
+    fn parses_one_shot_bindings() {
+        let src = r#"
+[bindings]
+O = { one_shot = "Ctrl" }
+Y = { one_shot_layer = "symbols" }
+
+[layers.symbols]
+key = "RightAlt"
+
+[one_shot]
+timeout = 500
+"#;
+        let config = Config::parse(src, "nuna.toml").unwrap();
+        assert_eq!(
+            config.keymap.one_shot(OsCode::KEY_O),
+            Some(&OneShot::Modifier(OsCode::KEY_LEFTCTRL))
+        );
+        assert_eq!(
+            config.keymap.one_shot(OsCode::KEY_Y),
+            Some(&OneShot::Layer("symbols".to_string()))
+        );
+        assert!(config.keymap.get(OsCode::KEY_O).is_none());
+        assert_eq!(
+            config.one_shot.timeout,
+            std::time::Duration::from_millis(500)
+        );
+        assert_eq!(
+            Config::default().one_shot.timeout,
+            std::time::Duration::from_millis(1000)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_one_shot_bindings() {
+        let err = Config::parse("[bindings]\nO = { one_shot = \"A\" }\n", "nuna.toml").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 5));
+        for src in [
+            "[bindings]\nY = { one_shot_layer = \"nope\" }\n",
+            "[bindings]\n\"Shift+O\" = { one_shot = \"Ctrl\" }\n",
+            "[bindings]\nO = \"Home\"\no = { one_shot = \"Ctrl\" }\n",
+        ] {
+            assert!(Config::parse(src, "nuna.toml").is_err(), "{src}");
+        }
+    }
+
+    #[test]
+    fn parses_combos() {
+        let src = r#"
+[[combos]]
+keys = ["K", "J"]
+output = "Esc"
+
+[[combos]]
+keys = ["S", "D"]
+output = "Ctrl+S"
+layer = "caps"
+term = 80
+"#;
+        let config = Config::parse(src, "nuna.toml").unwrap();
+        let [esc, save] = &config.combos[..] else {
+            panic!("{:?}", config.combos);
+        };
+        assert_eq!(esc.keys, [OsCode::KEY_J, OsCode::KEY_K]);
+        assert_eq!(esc.output, KeyChord::single(OsCode::KEY_ESC));
+        assert_eq!(esc.layer, None);
+        assert_eq!(esc.term, std::time::Duration::from_millis(50));
+        assert_eq!(save.layer.as_deref(), Some("caps"));
+        assert_eq!(save.term, std::time::Duration::from_millis(80));
+    }
+
+    #[test]
+    fn rejects_invalid_combos() {
+        for src in [
+            "[[combos]]\nkeys = [\"J\"]\noutput = \"Esc\"\n",
+            "[[combos]]\nkeys = [\"J\", \"j\"]\noutput = \"Esc\"\n",
+            "[[combos]]\nkeys = [\"J\", \"Shift\"]\noutput = \"Esc\"\n",
+            "[[combos]]\nkeys = [\"J\", \"CapsLock\"]\noutput = \"Esc\"\n",
+            "[[combos]]\nkeys = [\"J\", \"K\"]\noutput = \"Esc\"\nlayer = \"nope\"\n",
+            "[[combos]]\nkeys = [\"J\", \"K\"]\noutput = \"Esc\"\n[[combos]]\nkeys = [\"K\", \"J\"]\noutput = \"Tab\"\n",
+        ] {
+            assert!(Config::parse(src, "nuna.toml").is_err(), "{src}");
+        }
+    }
+
+    #[test]
+    fn parses_sequences() {
+        let src = r#"
+[sequences]
+timeout = 300
+rollback = "hold"
+disabled_apps = ["KeePass.exe"]
+
+[sequences.bindings]
+jk = "Esc"
+"Space Space" = "Ctrl+Space"
+"#;
+        let config = Config::parse(src, "nuna.toml").unwrap();
+        let sequences = &config.sequences;
+        assert_eq!(sequences.timeout, std::time::Duration::from_millis(300));
+        assert_eq!(sequences.rollback, Rollback::Hold);
+        assert!(sequences.is_disabled_in("keepass.exe"));
+        let [space, esc] = &sequences.sequences[..] else {
+            panic!("{:?}", sequences.sequences);
+        };
+        assert_eq!(space.keys, [OsCode::KEY_SPACE, OsCode::KEY_SPACE]);
+        assert_eq!(esc.keys, [OsCode::KEY_J, OsCode::KEY_K]);
+        assert_eq!(esc.output, KeyChord::single(OsCode::KEY_ESC));
+
+        let config = Config::parse("", "nuna.toml").unwrap();
+        assert_eq!(config.sequences.rollback, Rollback::Backspace);
+        assert!(config.sequences.sequences.is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_sequences() {
+        for src in [
+            "[sequences.bindings]\nj = \"Esc\"\n",
+            "[sequences.bindings]\n\"Shift J\" = \"Esc\"\n",
+            "[sequences.bindings]\n\"CapsLock J\" = \"Esc\"\n",
+            "[sequences.bindings]\njk = \"Nope\"\n",
+            "[sequences.bindings]\njk = \"Esc\"\njkl = \"Tab\"\n",
+            "[sequences]\nrollback = \"undo\"\n",
+        ] {
+            assert!(Config::parse(src, "nuna.toml").is_err(), "{src}");
+        }
+    }
+
+    #[test]
+    fn parses_leader() {
+        let src = r#"
+[leader]
+key = "CapsLock"
+timeout = 800
+
+[leader.bindings]
+"g s" = "Ctrl+S"
+"Space f" = "Ctrl+F"
+
+[leader.bindings.w]
+q = "Alt+F4"
+"v s" = "Win+Up"
+"#;
+        let config = Config::parse(src, "nuna.toml").unwrap();
+        let leader = config.leader.unwrap();
+        assert_eq!(leader.key, OsCode::KEY_CAPSLOCK);
+        assert_eq!(leader.timeout, std::time::Duration::from_millis(800));
+        let keys: Vec<&[OsCode]> = leader.sequences.iter().map(|s| &s.keys[..]).collect();
+        assert_eq!(
+            keys,
+            [
+                &[OsCode::KEY_SPACE, OsCode::KEY_F][..],
+                &[OsCode::KEY_G, OsCode::KEY_S],
+                &[OsCode::KEY_W, OsCode::KEY_Q],
+                &[OsCode::KEY_W, OsCode::KEY_V, OsCode::KEY_S],
+            ]
+        );
+        // 层键作为 leader 键时按默认方式判定轻触
+        assert_eq!(config.tap_hold[&OsCode::KEY_CAPSLOCK].tap, None);
+        assert!(Config::parse("", "nuna.toml").unwrap().leader.is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_leader() {
+        for src in [
+            "[leader]\nkey = \"Shift\"\n",
+            "[layers.nav]\nkey = \"Tab\"\nmode = \"toggle\"\n[leader]\nkey = \"Tab\"\n",
+            "[leader]\nkey = \"CapsLock\"\n[leader.bindings]\ng = \"Home\"\n\"g s\" = \"End\"\n",
+            "[leader]\nkey = \"CapsLock\"\n[leader.bindings]\n\"g s\" = \"End\"\n[leader.bindings.g]\ns = \"Home\"\n",
+            "[leader]\nkey = \"CapsLock\"\n[leader.bindings]\n\"g Esc\" = \"End\"\n",
+            "[leader]\nkey = \"CapsLock\"\n[leader.bindings]\n\"g Ctrl\" = \"End\"\n",
+            "[leader]\nkey = \"CapsLock\"\n[leader.bindings]\ngs = \"End\"\n",
+            "[leader]\nkey = \"CapsLock\"\n[tap_hold.CapsLock]\ntap = \"Esc\"\n",
+            "[tap_hold.CapsLock]\ntapping_term = 150\n",
+        ] {
+            assert!(Config::parse(src, "nuna.toml").is_err(), "{src}");
+        }
+    }
+
+    #[test]
+    fn parses_macros() {
+        use OsCode::{KEY_1, KEY_A, KEY_LEFTCTRL, KEY_LEFTSHIFT, KEY_SPACE};
+        let src = r#"
+[bindings]
+M = { macro = "sig" }
+
+[macros]
+sig = [
+    { tap = "Ctrl+A" },
+    { hold = "Space", ms = 300 },
+    { delay = 50 },
+    { text = "A!" },
+    { press = "Shift" },
+    { release = "Shift" },
+    { release_all = true },
+]
+"#;
+        let keymap = Keymap::parse(src, "nuna.toml").unwrap();
+        let sig = keymap.get_macro(OsCode::KEY_M).unwrap();
+        let ms = std::time::Duration::from_millis;
+        assert_eq!(sig.name, "sig");
+        assert_eq!(
+            sig.steps,
+            [
+                Step::Press(KEY_LEFTCTRL),
+                Step::Press(KEY_A),
+                Step::Release(KEY_A),
+                Step::Release(KEY_LEFTCTRL),
+                Step::Press(KEY_SPACE),
+                Step::Delay(ms(300)),
+                Step::Release(KEY_SPACE),
+                Step::Delay(ms(50)),
+                Step::Press(KEY_LEFTSHIFT),
+                Step::Press(KEY_A),
+                Step::Release(KEY_A),
+                Step::Release(KEY_LEFTSHIFT),
+                Step::Press(KEY_LEFTSHIFT),
+                Step::Press(KEY_1),
+                Step::Release(KEY_1),
+                Step::Release(KEY_LEFTSHIFT),
+                Step::Press(KEY_LEFTSHIFT),
+                Step::Release(KEY_LEFTSHIFT),
+                Step::ReleaseAll,
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_macros() {
+        for src in [
+            "[bindings]\nM = { macro = \"nope\" }\n",
+            "[bindings]\n\"Shift+M\" = { macro = \"m\" }\n[macros]\nm = [{ tap = \"A\" }]\n",
+            "[macros]\nm = []\n",
+            "[macros]\nm = [{ tap = \"Nope\" }]\n",
+            "[macros]\nm = [{ text = \"€\" }]\n",
+            "[macros]\nm = [{ release_all = false }]\n",
+            "[macros]\nm = [{ wait = 10 }]\n",
+            "[macros]\nm = [{ hold = \"A\" }]\n",
+        ] {
+            assert!(Config::parse(src, "nuna.toml").is_err(), "{src}");
+        }
+    }
+
+    #[test]
+    fn rejects_extra_keys_in_macro_steps() {
+        let err =
+            Config::parse("[macros]\nm = [{ tap = \"A\", ms = 300 }]\n", "nuna.toml").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(err.message.contains("ms"), "{err}");
+
+        let err = Config::parse("[macros]\nm = [{ text = \"x\", delay = 5 }]\n", "nuna.toml")
+            .unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(err.message.contains("中的一个"), "{err}");
+
+        let err = Config::parse(
+            "[macros]\nm = [{ tap = \"A\", tapp = \"B\" }]\n",
+            "nuna.toml",
+        )
+        .unwrap_err();
+        assert!(err.message.contains("tapp"), "{err}");
+    }
+
+    #[test]
+    fn rejects_unknown_sections() {
+        let err = Keymap::parse("[bindigns]\nA = \"Home\"\n", "nuna.toml").unwrap_err();
+        assert_eq!(err.line, 1);
+    }
+}
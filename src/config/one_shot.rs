@@ -0,0 +1,75 @@
+//! 一次性修饰键与层：映射写成 `O = { one_shot = "Ctrl" }` 或 `Y = { one_shot_layer = "symbols" }`，
+//! 之后按下的一个键带上该修饰键或在该层中查找；`[one_shot]` 中设置超时。
+
+use crate::oscode::OsCode;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// 默认超时，触发后超过该时间没有按其他键即失效
+const DEFAULT_TIMEOUT: u64 = 1000;
+
+/// 一次性作用的对象
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OneShot {
+    Modifier(OsCode),
+    /// 层名，配置加载时已校验存在
+    Layer(String),
+}
+
+/// `[one_shot]` 中的设置
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OneShotOptions {
+    pub timeout: Duration,
+}
+
+impl Default for OneShotOptions {
+    fn default() -> Self {
+        OneShotOptions {
+            timeout: Duration::from_millis(DEFAULT_TIMEOUT),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct RawOneShotOptions {
+    #[serde(default = "default_timeout")]
+    timeout: u64,
+}
+
+impl Default for RawOneShotOptions {
+    fn default() -> Self {
+        RawOneShotOptions {
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+fn default_timeout() -> u64 {
+    DEFAULT_TIMEOUT
+}
+
+/// `{ one_shot = "Ctrl" }` 中的修饰键
+pub(super) fn modifier_from_raw(name: &str) -> Result<OneShot, String> {
+    let code = name.parse::<OsCode>().map_err(|e| e.to_string())?;
+    if !code.is_modifier() {
+        return Err(format!("\"{code}\" 不是修饰键"));
+    }
+    Ok(OneShot::Modifier(code))
+}
+
+/// `{ one_shot_layer = "symbols" }` 中的层名，`layer_names` 为所有层名（包括 caps）
+pub(super) fn layer_from_raw(name: &str, layer_names: &[String]) -> Result<OneShot, String> {
+    if !layer_names.iter().any(|layer| layer == name) {
+        return Err(format!("未定义的层 \"{name}\""));
+    }
+    Ok(OneShot::Layer(name.to_string()))
+}
+
+impl From<RawOneShotOptions> for OneShotOptions {
+    fn from(raw: RawOneShotOptions) -> Self {
+        OneShotOptions {
+            timeout: Duration::from_millis(raw.timeout),
+        }
+    }
+}
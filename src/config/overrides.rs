@@ -0,0 +1,106 @@
+//! 基础层的按键覆盖：`[[overrides]]` 中的修饰键 + 键在没有被任何层映射时改为输出其他键或不输出，
+//! 例如 Shift+Backspace 输出 Delete、屏蔽 Ctrl+Shift+Q。
+
+use super::{Binding, ConfigError, modifier_kinds, parse_output};
+use crate::oscode::{KeyChord, OsCode};
+use serde::Deserialize;
+use std::collections::HashMap;
+use toml::Spanned;
+
+/// 所有按键覆盖：(键, 修饰键种类，已排序) -> 替换的输出，`None` 为不输出
+#[derive(Clone, Debug, Default)]
+pub struct Overrides {
+    overrides: HashMap<(OsCode, Vec<OsCode>), Option<Binding>>,
+}
+
+impl Overrides {
+    /// 查找按住 `modifiers` 时按下 `code` 的覆盖，修饰键种类需完全一致。
+    /// 返回 `Some(None)` 表示该组合被屏蔽
+    pub fn lookup(&self, code: OsCode, modifiers: &[OsCode]) -> Option<Option<&Binding>> {
+        if self.is_empty() {
+            return None;
+        }
+        let kinds = modifier_kinds(modifiers.iter().copied());
+        self.overrides.get(&(code, kinds)).map(Option::as_ref)
+    }
+
+    pub fn len(&self) -> usize {
+        self.overrides.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.overrides.is_empty()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct RawOverride {
+    trigger: Spanned<String>,
+    output: Option<Spanned<String>>,
+    suppress: Option<Spanned<Vec<String>>>,
+}
+
+pub(super) fn overrides_from_raw(
+    raw_overrides: Vec<RawOverride>,
+    src: &str,
+    origin: &str,
+) -> Result<Overrides, ConfigError> {
+    let mut overrides = HashMap::new();
+    for raw in raw_overrides {
+        let err = |span, msg: String| ConfigError::new(src, origin, Some(span), msg);
+        let trigger = raw
+            .trigger
+            .get_ref()
+            .parse::<KeyChord>()
+            .map_err(|e| err(raw.trigger.span(), e.to_string()))?;
+        if trigger.modifiers.is_empty() || trigger.key.is_modifier() {
+            return Err(err(
+                raw.trigger.span(),
+                "触发键需要是修饰键加一个非修饰键，例如 \"Shift+Backspace\"".to_string(),
+            ));
+        }
+        let kinds = modifier_kinds(trigger.modifiers.iter().copied());
+        let output = match &raw.output {
+            Some(output) => {
+                let chord =
+                    parse_output(output.get_ref()).map_err(|msg| err(output.span(), msg))?;
+                // 默认抬起触发用的修饰键
+                let suppress = match &raw.suppress {
+                    Some(suppress) => {
+                        let codes = suppress
+                            .get_ref()
+                            .iter()
+                            .map(|name| name.parse::<OsCode>())
+                            .collect::<Result<Vec<_>, _>>()
+                            .map_err(|e| err(suppress.span(), e.to_string()))?;
+                        if let Some(code) = codes.iter().find(|code| !code.is_modifier()) {
+                            return Err(err(suppress.span(), format!("\"{code}\" 不是修饰键")));
+                        }
+                        modifier_kinds(codes.into_iter())
+                    }
+                    None => kinds.clone(),
+                };
+                Some(Binding {
+                    output: chord,
+                    repeat: true,
+                    suppress,
+                })
+            }
+            None if raw.suppress.is_some() => {
+                return Err(err(
+                    raw.trigger.span(),
+                    "没有 output 的覆盖不输出任何键，不能设置 suppress".to_string(),
+                ));
+            }
+            None => None,
+        };
+        if overrides.insert((trigger.key, kinds), output).is_some() {
+            return Err(err(
+                raw.trigger.span(),
+                format!("触发键 \"{trigger}\" 重复（修饰键不区分左右）"),
+            ));
+        }
+    }
+    Ok(Overrides { overrides })
+}
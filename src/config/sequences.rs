@@ -0,0 +1,170 @@
+//! 按顺序输入的序列：`[sequences.bindings]` 中的几个键在超时内依次按下时输出另一个键，
+//! 例如像 vim 一样输入 `jk` 输出 Esc。`[sequences]` 中设置超时、已输入字符的处理方式，
+//! 以及不触发序列的程序（例如密码框所在的程序或游戏）。
+
+use super::{ConfigError, Layer, is_layer_key, parse_output};
+use crate::oscode::{KeyChord, OsCode};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use toml::Spanned;
+
+/// 默认超时：相邻两个键的间隔超过该时间则重新开始匹配
+const DEFAULT_TIMEOUT: u64 = 500;
+
+/// 序列匹配前已输入的字符如何处理
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rollback {
+    /// 照常输出，匹配后用退格删除
+    #[default]
+    Backspace,
+    /// 可能组成序列的键先不输出，匹配失败或超时后再按原来的顺序输出
+    Hold,
+}
+
+/// 一个序列
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sequence {
+    /// 依次按下的键
+    pub keys: Vec<OsCode>,
+    /// 匹配时输出一次的键或组合键
+    pub output: KeyChord,
+}
+
+/// `[sequences]` 中的设置与所有序列
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sequences {
+    pub sequences: Vec<Sequence>,
+    pub timeout: Duration,
+    pub rollback: Rollback,
+    /// 不触发序列的程序的可执行文件名（不区分大小写），例如 `KeePass.exe`
+    pub disabled_apps: Vec<String>,
+}
+
+impl Default for Sequences {
+    fn default() -> Self {
+        Sequences {
+            sequences: Vec::new(),
+            timeout: Duration::from_millis(DEFAULT_TIMEOUT),
+            rollback: Rollback::default(),
+            disabled_apps: Vec::new(),
+        }
+    }
+}
+
+impl Sequence {
+    /// 以空格分隔的键名，用于报错与日志
+    pub fn names(&self) -> String {
+        names(&self.keys)
+    }
+}
+
+/// 以空格分隔的键名
+pub(super) fn names(keys: &[OsCode]) -> String {
+    let names: Vec<String> = keys.iter().map(OsCode::to_string).collect();
+    names.join(" ")
+}
+
+impl Sequences {
+    /// 前台程序是否关闭了序列
+    pub fn is_disabled_in(&self, app: &str) -> bool {
+        self.disabled_apps
+            .iter()
+            .any(|disabled| disabled.eq_ignore_ascii_case(app))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct RawSequences {
+    #[serde(default = "default_timeout")]
+    timeout: u64,
+    #[serde(default)]
+    rollback: Rollback,
+    #[serde(default)]
+    disabled_apps: Vec<String>,
+    #[serde(default)]
+    bindings: BTreeMap<Spanned<String>, Spanned<String>>,
+}
+
+impl Default for RawSequences {
+    fn default() -> Self {
+        RawSequences {
+            timeout: DEFAULT_TIMEOUT,
+            rollback: Rollback::default(),
+            disabled_apps: Vec::new(),
+            bindings: BTreeMap::new(),
+        }
+    }
+}
+
+fn default_timeout() -> u64 {
+    DEFAULT_TIMEOUT
+}
+
+/// 解析序列的键：含空白时按空白分隔的键名解析（例如 `"Space Space"`），否则每个字符是一个键
+fn parse_keys(text: &str) -> Result<Vec<OsCode>, String> {
+    let names: Vec<String> = if text.trim().contains(char::is_whitespace) {
+        text.split_whitespace().map(str::to_string).collect()
+    } else {
+        text.trim().chars().map(String::from).collect()
+    };
+    names
+        .iter()
+        .map(|name| name.parse::<OsCode>().map_err(|e| e.to_string()))
+        .collect()
+}
+
+/// 与 `keys` 相同或互为前缀的序列
+pub(super) fn find_conflict<'a>(
+    sequences: &'a [Sequence],
+    keys: &[OsCode],
+) -> Option<&'a Sequence> {
+    sequences
+        .iter()
+        .find(|other| other.keys.starts_with(keys) || keys.starts_with(&other.keys))
+}
+
+pub(super) fn sequences_from_raw(
+    raw: RawSequences,
+    layers: &[Layer],
+    src: &str,
+    origin: &str,
+) -> Result<Sequences, ConfigError> {
+    let mut sequences: Vec<Sequence> = Vec::with_capacity(raw.bindings.len());
+    for (trigger, output) in raw.bindings {
+        let err = |span, msg: String| ConfigError::new(src, origin, Some(span), msg);
+        let keys = parse_keys(trigger.get_ref()).map_err(|msg| err(trigger.span(), msg))?;
+        if keys.len() < 2 {
+            return Err(err(trigger.span(), "序列至少需要两个键".to_string()));
+        }
+        if let Some(key) = keys
+            .iter()
+            .find(|&&key| key.is_modifier() || is_layer_key(key, layers))
+        {
+            return Err(err(
+                trigger.span(),
+                format!("\"{key}\" 是修饰键或层键，不能用于序列"),
+            ));
+        }
+        // 一个序列是另一个的前缀时，无法确定输入前缀后是否应该等待
+        if let Some(other) = find_conflict(&sequences, &keys) {
+            return Err(err(
+                trigger.span(),
+                format!("与序列 \"{}\" 冲突：一个是另一个的前缀", other.names()),
+            ));
+        }
+        let chord = parse_output(output.get_ref()).map_err(|msg| err(output.span(), msg))?;
+        sequences.push(Sequence {
+            keys,
+            output: chord,
+        });
+    }
+    Ok(Sequences {
+        sequences,
+        timeout: Duration::from_millis(raw.timeout),
+        rollback: raw.rollback,
+        disabled_apps: raw.disabled_apps,
+    })
+}
@@ -0,0 +1,106 @@
+//! 层键的 tap-hold 设置：`[tap_hold.<键名>]` 中配置轻触时的输出以及判定为按住的条件。
+//! 只有按住激活（momentary）的层键可以设置。作为 leader 键的层键轻触时启动 leader，不写 `tap`。
+
+use super::{ConfigError, Layer, LayerMode, parse_output};
+use crate::oscode::{KeyChord, OsCode};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use toml::Spanned;
+
+/// 默认的判定时间，与 QMK 一致
+const DEFAULT_TAPPING_TERM: u64 = 200;
+
+/// 层键轻触与按住的判定方式
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TapHold {
+    /// 轻触时输出的键或组合键，`None` 为启动 leader
+    pub tap: Option<KeyChord>,
+    /// 层键按下超过该时间即判定为按住
+    pub tapping_term: Duration,
+    /// 判定期间按下其他键，立即判定为按住
+    pub hold_on_other_key_press: bool,
+    /// 判定期间另一个键完整地按下并抬起（层键仍按住），判定为按住
+    pub permissive_hold: bool,
+    /// 按住超时后没有按过其他键就抬起，仍然输出轻触
+    pub retro_tap: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct RawTapHold {
+    tap: Option<Spanned<String>>,
+    #[serde(default = "default_tapping_term")]
+    tapping_term: u64,
+    #[serde(default)]
+    hold_on_other_key_press: bool,
+    #[serde(default)]
+    permissive_hold: bool,
+    #[serde(default)]
+    retro_tap: bool,
+}
+
+fn default_tapping_term() -> u64 {
+    DEFAULT_TAPPING_TERM
+}
+
+/// 作为 leader 键、又没有 `[tap_hold.<键名>]` 设置的层键使用的默认判定方式。
+/// 快速地按层键与另一个键（例如 Caps+H）时判定为按住，以免启动 leader 吞掉该键
+pub(super) fn leader_tap_hold() -> TapHold {
+    TapHold {
+        tap: None,
+        tapping_term: Duration::from_millis(DEFAULT_TAPPING_TERM),
+        hold_on_other_key_press: false,
+        permissive_hold: true,
+        retro_tap: false,
+    }
+}
+
+pub(super) fn tap_holds_from_raw(
+    raw_tap_holds: BTreeMap<Spanned<String>, RawTapHold>,
+    layers: &[Layer],
+    leader: Option<OsCode>,
+    src: &str,
+    origin: &str,
+) -> Result<HashMap<OsCode, TapHold>, ConfigError> {
+    let mut tap_holds = HashMap::new();
+    for (key, raw) in raw_tap_holds {
+        let err = |span, msg: String| ConfigError::new(src, origin, Some(span), msg);
+        let code = key
+            .get_ref()
+            .parse::<OsCode>()
+            .map_err(|e| err(key.span(), e.to_string()))?;
+        let is_momentary_key = code == OsCode::KEY_CAPSLOCK
+            || layers
+                .iter()
+                .any(|layer| layer.key == code && layer.mode == LayerMode::Momentary);
+        if !is_momentary_key {
+            return Err(err(
+                key.span(),
+                format!("\"{code}\" 不是按住激活的层键，不能设置 tap-hold"),
+            ));
+        }
+        let tap = match raw.tap {
+            Some(tap) if leader == Some(code) => {
+                return Err(err(
+                    tap.span(),
+                    format!("\"{code}\" 是 leader 键，轻触用于启动 leader，不能设置 tap"),
+                ));
+            }
+            Some(tap) => Some(parse_output(tap.get_ref()).map_err(|msg| err(tap.span(), msg))?),
+            None if leader == Some(code) => None,
+            None => return Err(err(key.span(), "缺少轻触时的输出 tap".to_string())),
+        };
+        tap_holds.insert(
+            code,
+            TapHold {
+                tap,
+                tapping_term: Duration::from_millis(raw.tapping_term),
+                hold_on_other_key_press: raw.hold_on_other_key_press,
+                permissive_hold: raw.permissive_hold,
+                retro_tap: raw.retro_tap,
+            },
+        );
+    }
+    Ok(tap_holds)
+}
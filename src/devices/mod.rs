@@ -0,0 +1,136 @@
+//! `nuna devices` 命令：列出所有键盘和鼠标，或识别下一次按键来自哪个键盘，
+//! 以便把设备编号或硬件 ID 填入配置文件。
+
+use crate::backend::{self, DeviceInfo, InputBackend};
+use anyhow::{Result, bail};
+use std::fmt::Write;
+use std::time::Duration;
+
+/// 识别模式等待按键的时间
+const IDENTIFY_TIMEOUT: Duration = Duration::from_secs(30);
+
+const USAGE: &str = "用法: nuna devices [--identify]";
+
+/// 执行 `nuna devices` 命令，`args` 为 `devices` 之后的参数
+pub fn run(args: &[String]) -> Result<()> {
+    // 托盘版本没有控制台窗口，输出需要借用启动它的命令行
+    #[cfg(all(windows, feature = "tray"))]
+    attach_console();
+
+    match args {
+        [] => list(),
+        [flag] if flag == "--identify" || flag == "-i" => identify(),
+        [flag] if flag == "--help" || flag == "-h" => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        _ => bail!("{USAGE}"),
+    }
+}
+
+fn list() -> Result<()> {
+    let devices = list_devices()?;
+    if devices.is_empty() {
+        println!("没有找到输入设备");
+    } else {
+        print!("{}", format_table(&devices));
+    }
+    Ok(())
+}
+
+#[cfg(any(all(windows, feature = "interception"), target_os = "linux"))]
+fn identify() -> Result<()> {
+    let mut backend = open_backend()?;
+    println!(
+        "请在要识别的键盘上按任意键（{} 秒内）...",
+        IDENTIFY_TIMEOUT.as_secs()
+    );
+    let Some(info) = backend::identify(&mut backend, IDENTIFY_TIMEOUT) else {
+        bail!("{} 秒内没有收到按键", IDENTIFY_TIMEOUT.as_secs());
+    };
+    print!("{}", format_table(std::slice::from_ref(&info)));
+    println!("可以将编号或硬件 ID 填入配置文件");
+    Ok(())
+}
+
+/// 按编号、类型、硬件 ID 三列输出设备列表
+fn format_table(devices: &[DeviceInfo]) -> String {
+    let mut table = String::from("编号  类型  硬件 ID\n");
+    for info in devices {
+        // 类型固定为两个汉字，各行自然对齐
+        let _ = writeln!(table, "{:>4}  {}  {}", info.id, info.kind, info.hardware_id);
+    }
+    table
+}
+
+#[cfg(all(windows, feature = "interception"))]
+const DRIVER_MISSING: &str = "interception 驱动未安装，请先安装驱动并重启电脑";
+
+#[cfg(all(windows, feature = "interception"))]
+fn list_devices() -> Result<Vec<DeviceInfo>> {
+    use anyhow::Context;
+    backend::list_devices().context(DRIVER_MISSING)
+}
+
+#[cfg(all(windows, feature = "interception"))]
+fn open_backend() -> Result<impl InputBackend> {
+    use anyhow::Context;
+    backend::InterceptionBackend::new().context(DRIVER_MISSING)
+}
+
+#[cfg(target_os = "linux")]
+fn list_devices() -> Result<Vec<DeviceInfo>> {
+    Ok(backend::list_devices())
+}
+
+/// 独占所有键盘，按键经虚拟键盘原样发出
+#[cfg(target_os = "linux")]
+fn open_backend() -> Result<impl InputBackend> {
+    backend::EvdevBackend::open(&[], &[])
+}
+
+#[cfg(not(any(all(windows, feature = "interception"), target_os = "linux")))]
+fn list_devices() -> Result<Vec<DeviceInfo>> {
+    bail!("当前平台没有可用的键盘后端")
+}
+
+#[cfg(not(any(all(windows, feature = "interception"), target_os = "linux")))]
+fn identify() -> Result<()> {
+    bail!("当前平台没有可用的键盘后端")
+}
+
+/// 附加到父进程（命令行）的控制台，使 `println!` 可见
+#[cfg(all(windows, feature = "tray"))]
+fn attach_console() {
+    use windows::Win32::System::Console::{ATTACH_PARENT_PROCESS, AttachConsole};
+    // 从资源管理器启动时没有父控制台，失败也无妨
+    let _ = unsafe { AttachConsole(ATTACH_PARENT_PROCESS) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::DeviceKind;
+
+    #[test]
+    fn formats_devices_as_table() {
+        let devices = [
+            DeviceInfo {
+                id: 1,
+                kind: DeviceKind::Keyboard,
+                hardware_id: "HID\\VID_046D&PID_C31C&REV_6400&MI_00".to_string(),
+            },
+            DeviceInfo {
+                id: 11,
+                kind: DeviceKind::Mouse,
+                hardware_id: "HID\\VID_046D&PID_C077&REV_7200".to_string(),
+            },
+        ];
+        assert_eq!(
+            format_table(&devices),
+            "编号  类型  硬件 ID\n\
+             \x20  1  键盘  HID\\VID_046D&PID_C31C&REV_6400&MI_00\n\
+             \x20 11  鼠标  HID\\VID_046D&PID_C077&REV_7200\n"
+        );
+    }
+}
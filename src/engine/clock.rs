@@ -0,0 +1,40 @@
+//! 引擎的时间来源。tap-hold 等按时间判定的逻辑都通过 `Clock` 取当前时间，
+//! 测试中可以换成手动拨动的时钟。
+
+use std::time::Instant;
+
+pub trait Clock: Send {
+    fn now(&self) -> Instant;
+}
+
+/// 系统时钟
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// 手动拨动的时钟，克隆出的副本共享同一时间
+#[cfg(test)]
+#[derive(Clone)]
+pub struct FakeClock(std::sync::Arc<std::sync::Mutex<Instant>>);
+
+#[cfg(test)]
+impl FakeClock {
+    pub fn new() -> Self {
+        FakeClock(std::sync::Arc::new(std::sync::Mutex::new(Instant::now())))
+    }
+
+    pub fn advance(&self, duration: std::time::Duration) {
+        *self.0.lock().unwrap() += duration;
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now(&self) -> Instant {
+        *self.0.lock().unwrap()
+    }
+}
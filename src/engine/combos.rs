@@ -0,0 +1,83 @@
+//! 组合的判定：按下组合中的键后先暂存，其余的键在判定时间内按下即输出组合，
+//! 否则按原来的顺序依次处理暂存的事件。不属于任何组合的键不会被暂存。
+
+use super::layers::LayerStack;
+use super::{DeviceId, KeyEvent};
+use crate::config::{Combo, Layer};
+use crate::oscode::OsCode;
+use std::time::Instant;
+
+/// 组合在当前层状态下是否生效
+pub fn is_active(combo: &Combo, layers: &[Layer], stack: &LayerStack) -> bool {
+    match &combo.layer {
+        None => stack.is_empty(),
+        Some(name) => stack.iter().any(|layer| layers[layer].name == *name),
+    }
+}
+
+/// 正在判定的组合
+#[derive(Debug)]
+pub struct PendingCombo {
+    /// 暂存的按下事件，按按下顺序排列
+    events: Vec<KeyEvent>,
+    since: Instant,
+}
+
+impl PendingCombo {
+    pub fn new(event: KeyEvent, now: Instant) -> Self {
+        PendingCombo {
+            events: vec![event],
+            since: now,
+        }
+    }
+
+    /// 产生暂存事件的设备
+    pub fn device(&self) -> DeviceId {
+        self.events[0].device
+    }
+
+    pub fn contains(&self, code: OsCode) -> bool {
+        self.events.iter().any(|event| event.code == code)
+    }
+
+    pub fn push(&mut self, event: KeyEvent) {
+        self.events.push(event);
+    }
+
+    /// 包含所有暂存的键的组合
+    fn candidates<'a>(&self, combos: &[&'a Combo]) -> impl Iterator<Item = &'a Combo> {
+        combos
+            .iter()
+            .copied()
+            .filter(|combo| self.events.iter().all(|e| combo.keys.contains(&e.code)))
+    }
+
+    /// 再按下 `code` 后是否仍可能组成某个组合
+    pub fn accepts(&self, code: OsCode, combos: &[&Combo]) -> bool {
+        self.candidates(combos)
+            .any(|combo| combo.keys.contains(&code))
+    }
+
+    /// 恰好由暂存的键组成的组合
+    pub fn complete<'a>(&self, combos: &[&'a Combo]) -> Option<&'a Combo> {
+        self.candidates(combos)
+            .find(|combo| combo.keys.len() == self.events.len())
+    }
+
+    /// 是否还可能组成键更多的组合，是则需要继续等待
+    pub fn is_prefix(&self, combos: &[&Combo]) -> bool {
+        self.candidates(combos)
+            .any(|combo| combo.keys.len() > self.events.len())
+    }
+
+    /// 是否已超过所有可能组成的组合的判定时间
+    pub fn is_expired(&self, now: Instant, combos: &[&Combo]) -> bool {
+        let term = self.candidates(combos).map(|combo| combo.term).max();
+        term.is_none_or(|term| now.duration_since(self.since) >= term)
+    }
+
+    /// 暂存的事件
+    pub fn into_events(self) -> Vec<KeyEvent> {
+        self.events
+    }
+}
@@ -0,0 +1,122 @@
+//! 层栈：记录当前激活的层及其优先级。后激活的层在上面，优先查找映射；
+//! `to` 切换的基础层在所有激活的层之下。
+
+use super::PhysicalKey;
+use crate::config::{Fallthrough, Keymap, Layer, Overrides};
+
+/// 层的编号，即引擎中层列表的下标
+pub type LayerId = usize;
+
+/// `[bindings]` 对应的 caps 层的编号
+pub const CAPS: LayerId = 0;
+
+/// 层是如何激活的
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Activation {
+    /// 按住激活键，`used` 为按住期间是否按过其他键
+    Held {
+        key: PhysicalKey,
+        used: bool,
+    },
+    Toggled,
+}
+
+#[derive(Debug, Default)]
+pub struct LayerStack {
+    /// 激活的层，按激活顺序排列
+    active: Vec<(LayerId, Activation)>,
+    /// `to` 切换的基础层，`None` 为默认（没有映射）
+    base: Option<LayerId>,
+}
+
+impl LayerStack {
+    /// 是否没有任何层生效
+    pub fn is_empty(&self) -> bool {
+        self.active.is_empty() && self.base.is_none()
+    }
+
+    /// 按住激活键激活层
+    pub fn hold(&mut self, layer: LayerId, key: PhysicalKey) {
+        self.active
+            .push((layer, Activation::Held { key, used: false }));
+    }
+
+    /// 抬起激活键，返回按住期间是否按过其他键；该键没有激活层时返回 `None`
+    pub fn release(&mut self, key: PhysicalKey) -> Option<bool> {
+        let index = self.active.iter().position(
+            |&(_, activation)| matches!(activation, Activation::Held { key: k, .. } if k == key),
+        )?;
+        match self.active.remove(index) {
+            (_, Activation::Held { used, .. }) => Some(used),
+            (_, Activation::Toggled) => None,
+        }
+    }
+
+    /// 切换层的激活状态
+    pub fn toggle(&mut self, layer: LayerId) {
+        let toggled = (layer, Activation::Toggled);
+        match self.active.iter().position(|&entry| entry == toggled) {
+            Some(index) => {
+                self.active.remove(index);
+            }
+            None => self.active.push(toggled),
+        }
+    }
+
+    /// 切换基础层，已经是基础层时切换回默认
+    pub fn switch_base(&mut self, layer: LayerId) {
+        self.base = if self.base == Some(layer) {
+            None
+        } else {
+            Some(layer)
+        };
+    }
+
+    /// 记录按住激活键期间按过其他键
+    pub fn mark_used(&mut self) {
+        for (_, activation) in &mut self.active {
+            if let Activation::Held { used, .. } = activation {
+                *used = true;
+            }
+        }
+    }
+
+    /// 从上到下依次为生效的层，最后是基础层
+    pub fn iter(&self) -> impl Iterator<Item = LayerId> + '_ {
+        self.active
+            .iter()
+            .rev()
+            .map(|&(layer, _)| layer)
+            .chain(self.base)
+    }
+}
+
+/// 某个设备使用的层：caps 层的映射可能被设备规则换成其他映射
+pub struct LayerView<'a> {
+    pub layers: &'a [Layer],
+    pub caps: &'a Keymap,
+    /// 没有被任何层映射的键使用的按键覆盖
+    pub overrides: &'a Overrides,
+}
+
+impl LayerView<'_> {
+    pub fn keymap(&self, layer: LayerId) -> &Keymap {
+        if layer == CAPS {
+            self.caps
+        } else {
+            &self.layers[layer].keymap
+        }
+    }
+
+    pub fn fallthrough(&self, layer: LayerId) -> Fallthrough {
+        self.layers[layer].fallthrough
+    }
+
+    /// 层名对应的编号，配置加载时已校验层存在
+    pub fn layer_id(&self, name: &str) -> LayerId {
+        self.layers
+            .iter()
+            .position(|layer| layer.name == name)
+            .expect("层已定义")
+    }
+}
@@ -0,0 +1,42 @@
+//! leader 键之后的输入：依次按下的键沿序列的前缀树向下走，走到叶子即输出；
+//! 走不通、超时或按 Esc 则取消，并在日志中提示。
+
+use super::DeviceId;
+use crate::oscode::OsCode;
+use std::time::{Duration, Instant};
+
+/// 已启动的 leader
+#[derive(Debug)]
+pub struct Leading {
+    /// 启动 leader 的设备
+    pub device: DeviceId,
+    /// leader 键之后已按下的键
+    pub keys: Vec<OsCode>,
+    /// 启动或最后一个键按下的时间
+    last: Instant,
+}
+
+impl Leading {
+    pub fn new(device: DeviceId, now: Instant) -> Self {
+        Leading {
+            device,
+            keys: Vec::new(),
+            last: now,
+        }
+    }
+
+    pub fn push(&mut self, code: OsCode, now: Instant) {
+        self.keys.push(code);
+        self.last = now;
+    }
+
+    /// 已按下的键名，用于日志
+    pub fn names(&self) -> String {
+        let names: Vec<String> = self.keys.iter().map(OsCode::to_string).collect();
+        names.join(" ")
+    }
+
+    pub fn is_expired(&self, now: Instant, timeout: Duration) -> bool {
+        now.duration_since(self.last) >= timeout
+    }
+}
@@ -0,0 +1,96 @@
+//! 宏的执行：宏有自己的时间线，每次处理事件或定时检查时执行已到时间的步骤，
+//! 遇到等待就停下，不阻塞拦截循环。结束或中止时抬起宏按住的所有键。
+//! 宏按下与抬起的修饰键记入修饰键账本，以便与物理按住的修饰键互不干扰。
+
+use super::modifiers::ModifierLedger;
+use super::{DeviceId, OutputEvent, PhysicalKey};
+use crate::config::{Macro, Step};
+use crate::oscode::OsCode;
+use std::time::Instant;
+
+/// 正在执行的宏
+#[derive(Debug)]
+pub struct RunningMacro {
+    /// 触发宏的键，再次按下时中止
+    pub trigger: PhysicalKey,
+    steps: Vec<Step>,
+    /// 下一个要执行的步骤
+    next: usize,
+    /// 等待结束的时间
+    wake: Instant,
+    /// 宏按住的键，按按下顺序排列
+    held: Vec<OsCode>,
+}
+
+impl RunningMacro {
+    pub fn new(trigger: PhysicalKey, mac: &Macro, now: Instant) -> Self {
+        RunningMacro {
+            trigger,
+            steps: mac.steps.clone(),
+            next: 0,
+            wake: now,
+            held: Vec::new(),
+        }
+    }
+
+    /// 输出发送到的设备
+    pub fn device(&self) -> DeviceId {
+        self.trigger.0
+    }
+
+    /// 宏按住的修饰键
+    pub fn modifiers(&self) -> impl Iterator<Item = OsCode> + '_ {
+        self.held.iter().copied().filter(|key| key.is_modifier())
+    }
+
+    /// 执行到 `now` 为止已到时间的步骤，返回宏是否已结束。
+    /// 执行步骤前抬起宏以外按下的修饰键，由调用方在之后恢复
+    pub fn advance(
+        &mut self,
+        now: Instant,
+        modifiers: &mut ModifierLedger,
+        output: &mut Vec<OutputEvent>,
+    ) -> bool {
+        if now < self.wake {
+            return false;
+        }
+        let held: Vec<OsCode> = self.modifiers().collect();
+        modifiers.sync(&held, output);
+        while now >= self.wake {
+            let Some(&step) = self.steps.get(self.next) else {
+                self.release_all(modifiers, output);
+                return true;
+            };
+            self.next += 1;
+            match step {
+                Step::Press(code) => {
+                    if !self.held.contains(&code) {
+                        self.held.push(code);
+                    }
+                    modifiers.set_sent(code, true);
+                    output.push(OutputEvent::press(code));
+                }
+                // 只抬起宏自己按住的键，不影响物理按住的键
+                Step::Release(code) => {
+                    if let Some(index) = self.held.iter().position(|&held| held == code) {
+                        self.held.remove(index);
+                        modifiers.set_sent(code, false);
+                        output.push(OutputEvent::release(code));
+                    }
+                }
+                // 从上一次等待结束的时间算起，不受定时检查间隔的影响
+                Step::Delay(delay) => self.wake += delay,
+                Step::ReleaseAll => self.release_all(modifiers, output),
+            }
+        }
+        false
+    }
+
+    /// 按相反的顺序抬起宏按住的所有键
+    pub fn release_all(&mut self, modifiers: &mut ModifierLedger, output: &mut Vec<OutputEvent>) {
+        for code in self.held.drain(..).rev() {
+            modifiers.set_sent(code, false);
+            output.push(OutputEvent::release(code));
+        }
+    }
+}
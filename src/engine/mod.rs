@@ -0,0 +1,3209 @@
+//! 平台无关的键位映射引擎：输入物理按键事件，输出应发送给系统的按键事件。
+//! 层与修饰键的状态都保存在引擎内部，拦截线程只负责收发事件。
+
+mod clock;
+mod combos;
+mod layers;
+mod leader;
+mod macros;
+mod modifiers;
+mod one_shot;
+mod sequences;
+mod tap_hold;
+
+use clock::Clock;
+#[cfg(test)]
+pub use clock::FakeClock;
+pub use clock::SystemClock;
+use combos::PendingCombo;
+
+use crate::config::{
+    Binding, Combo, Config, DeviceAction, DeviceRule, Fallthrough, Keymap, Layer, LayerMode,
+    Leader, Macro, OneShot, OneShotOptions, Overrides, Rollback, Sequences, TapHold, device_action,
+};
+use crate::oscode::{KeyChord, OsCode};
+use layers::{CAPS, LayerStack, LayerView};
+use leader::Leading;
+use macros::RunningMacro;
+use modifiers::ModifierLedger;
+use one_shot::{OneShots, Target};
+use sequences::{Match, Trie, Typed};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tap_hold::{Decision, PendingTap};
+
+/// 设备编号，与 Interception 的 `Device` 一致
+pub type DeviceId = i32;
+
+/// 物理按键事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub code: OsCode,
+    pub down: bool,
+    pub device: DeviceId,
+    /// 事件产生的时间，按时间判定的逻辑以此为准，而不是处理事件的时间
+    pub timestamp: Instant,
+}
+
+/// 引擎输出的按键事件，由拦截线程发送回产生输入事件的设备
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputEvent {
+    pub code: OsCode,
+    pub down: bool,
+}
+
+impl OutputEvent {
+    pub fn press(code: OsCode) -> Self {
+        OutputEvent { code, down: true }
+    }
+
+    pub fn release(code: OsCode) -> Self {
+        OutputEvent { code, down: false }
+    }
+}
+
+/// 查询系统中某个键当前是否处于按下状态
+pub type KeyStateQuery = Box<dyn Fn(OsCode) -> bool + Send>;
+
+/// 查询前台程序的可执行文件名
+pub type ForegroundAppQuery = Box<dyn Fn() -> Option<String> + Send>;
+
+/// 物理键按下时产生的输出
+#[derive(Debug, Clone)]
+enum Pressed {
+    /// 原样输出的键
+    Key(OsCode),
+    /// 层中的映射
+    Chord(Binding),
+    /// 层的激活键，本身不输出
+    Layer,
+    /// 被层拦下的未映射键，不输出
+    Blocked,
+    /// 触发一次性修饰键或层，本身不输出
+    OneShot(OneShot),
+    /// 触发宏，本身不输出
+    Macro(Macro),
+}
+
+/// 物理键：产生事件的设备与键码
+type PhysicalKey = (DeviceId, OsCode);
+
+/// 开启共享层时所有设备共用的状态编号
+const SHARED: DeviceId = 0;
+
+/// 一个键盘的层与修饰键状态
+#[derive(Debug, Default)]
+struct DeviceState {
+    /// 激活的层
+    layers: LayerStack,
+    /// 尚未判定轻触还是按住的层键，同一时间最多一个
+    pending: Option<PendingTap>,
+    /// 正在判定的组合
+    combo: Option<PendingCombo>,
+    /// 每个按下的物理键所产生的输出。物理键抬起时按此释放，与此时的层状态无关。
+    /// 共享层时不同设备上的同一个键分别记录
+    pressed: HashMap<PhysicalKey, Pressed>,
+    /// 正在按住的映射触发键，按按下顺序排列；最后一个决定系统中按下哪些修饰键
+    chords: Vec<PhysicalKey>,
+    /// 修饰键的物理状态与系统中的状态
+    modifiers: ModifierLedger,
+    /// 已触发、等待下一个键的一次性修饰键与层
+    one_shots: OneShots,
+    /// 正在输入的序列
+    typed: Typed,
+    /// 已启动、等待后续按键的 leader
+    leading: Option<Leading>,
+    /// 正在执行的宏
+    running: Option<RunningMacro>,
+}
+
+/// CapsLock 层映射引擎
+pub struct Engine {
+    /// 所有层，第一个是 `[bindings]` 对应的 caps 层，其映射为默认映射，未匹配任何设备规则的设备使用
+    layers: Vec<Layer>,
+    /// 具名的映射，供设备规则选用
+    profiles: HashMap<String, Keymap>,
+    /// 设备规则，第一条匹配的生效
+    rules: Vec<DeviceRule>,
+    /// 每个设备匹配到的规则处理方式，`None` 为使用默认映射；设备首次出现时确定
+    routes: HashMap<DeviceId, Option<DeviceAction>>,
+    /// 按设备保存的状态：在一个键盘上按住 CapsLock 不影响其他键盘
+    devices: HashMap<DeviceId, DeviceState>,
+    /// 所有设备共用一份状态，允许在一个键盘上按住 CapsLock、在另一个键盘上按触发键
+    shared_layers: bool,
+    /// 系统按键状态查询，用于修正卡住的修饰键；未设置时不做修正
+    key_state: Option<KeyStateQuery>,
+    /// 层键的 tap-hold 设置
+    tap_hold: HashMap<OsCode, TapHold>,
+    /// 基础层的按键覆盖
+    overrides: Overrides,
+    /// 一次性修饰键与层的超时
+    one_shot_timeout: Duration,
+    /// 同时按下几个键触发的组合
+    combos: Vec<Combo>,
+    /// 按顺序输入的序列的设置
+    sequences: Sequences,
+    /// 所有序列组成的前缀树
+    trie: Trie,
+    /// leader 键及其序列
+    leader: Option<Leader>,
+    /// 所有 leader 序列组成的前缀树
+    leader_trie: Trie,
+    /// 前台程序查询，用于按程序关闭序列；未设置时不关闭
+    foreground_app: Option<ForegroundAppQuery>,
+    /// 时间来源，用于 tap-hold 判定
+    clock: Box<dyn Clock>,
+}
+
+impl Engine {
+    pub fn new(keymap: Keymap) -> Self {
+        Engine {
+            layers: vec![Layer::caps(keymap)],
+            profiles: HashMap::new(),
+            rules: Vec::new(),
+            routes: HashMap::new(),
+            devices: HashMap::new(),
+            shared_layers: false,
+            key_state: None,
+            tap_hold: HashMap::new(),
+            overrides: Overrides::default(),
+            one_shot_timeout: OneShotOptions::default().timeout,
+            combos: Vec::new(),
+            sequences: Sequences::default(),
+            trie: Trie::default(),
+            leader: None,
+            leader_trie: Trie::default(),
+            foreground_app: None,
+            clock: Box::new(SystemClock),
+        }
+    }
+
+    /// 按配置创建引擎，包括所有层、设备规则、tap-hold、组合、序列与 leader
+    pub fn from_config(config: Config, clock: impl Clock + 'static) -> Self {
+        Engine::new(config.keymap)
+            .with_shared_layers(config.shared_layers)
+            .with_devices(config.devices, config.profiles)
+            .with_layers(config.layers)
+            .with_tap_hold(config.tap_hold)
+            .with_overrides(config.overrides)
+            .with_one_shot(config.one_shot)
+            .with_combos(config.combos)
+            .with_sequences(config.sequences)
+            .with_leader(config.leader)
+            .with_clock(clock)
+    }
+
+    /// 设置系统按键状态查询
+    #[allow(unused)]
+    pub fn with_key_state(mut self, query: impl Fn(OsCode) -> bool + Send + 'static) -> Self {
+        self.key_state = Some(Box::new(query));
+        self
+    }
+
+    /// 设置是否所有设备共用层与修饰键状态
+    pub fn with_shared_layers(mut self, shared: bool) -> Self {
+        self.shared_layers = shared;
+        self
+    }
+
+    /// 在 caps 层之外增加具名的层
+    pub fn with_layers(mut self, layers: Vec<Layer>) -> Self {
+        self.layers.extend(layers);
+        self
+    }
+
+    /// 设置层键的 tap-hold
+    pub fn with_tap_hold(mut self, tap_hold: HashMap<OsCode, TapHold>) -> Self {
+        self.tap_hold = tap_hold;
+        self
+    }
+
+    /// 设置基础层的按键覆盖
+    pub fn with_overrides(mut self, overrides: Overrides) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
+    /// 设置一次性修饰键与层
+    pub fn with_one_shot(mut self, options: OneShotOptions) -> Self {
+        self.one_shot_timeout = options.timeout;
+        self
+    }
+
+    /// 设置组合
+    pub fn with_combos(mut self, combos: Vec<Combo>) -> Self {
+        self.combos = combos;
+        self
+    }
+
+    /// 设置按顺序输入的序列
+    pub fn with_sequences(mut self, sequences: Sequences) -> Self {
+        self.trie = Trie::new(&sequences.sequences);
+        self.sequences = sequences;
+        self
+    }
+
+    /// 设置 leader 键
+    pub fn with_leader(mut self, leader: Option<Leader>) -> Self {
+        self.leader_trie = leader
+            .as_ref()
+            .map(|leader| Trie::new(&leader.sequences))
+            .unwrap_or_default();
+        self.leader = leader;
+        self
+    }
+
+    /// 设置前台程序查询
+    #[allow(unused)]
+    pub fn with_foreground_app(
+        mut self,
+        query: impl Fn() -> Option<String> + Send + 'static,
+    ) -> Self {
+        self.foreground_app = Some(Box::new(query));
+        self
+    }
+
+    /// 设置时间来源
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// 设置设备规则及其使用的具名映射
+    pub fn with_devices(
+        mut self,
+        rules: Vec<DeviceRule>,
+        profiles: HashMap<String, Keymap>,
+    ) -> Self {
+        self.rules = rules;
+        self.profiles = profiles;
+        self
+    }
+
+    /// 设备是否已确定使用的映射
+    pub fn is_attached(&self, device: DeviceId) -> bool {
+        self.routes.contains_key(&device)
+    }
+
+    /// 设备是否绕过映射，其输入由后端原样发回
+    pub fn is_bypassed(&self, device: DeviceId) -> bool {
+        matches!(self.routes.get(&device), Some(Some(DeviceAction::Bypass)))
+    }
+
+    /// 按设备规则确定设备使用的映射，`hardware_id` 未知时只能按编号匹配。
+    /// 未调用时在设备的第一个事件处按编号确定。
+    pub fn attach(&mut self, device: DeviceId, hardware_id: Option<&str>) {
+        let action = device_action(&self.rules, device, hardware_id).cloned();
+        let name = hardware_id.unwrap_or("未知硬件 ID");
+        match &action {
+            Some(DeviceAction::Bypass) => log::info!("设备 {device} ({name}) 绕过映射"),
+            Some(DeviceAction::Profile(profile)) => {
+                log::info!("设备 {device} ({name}) 使用映射 {profile}")
+            }
+            None => log::info!("设备 {device} ({name}) 使用默认映射"),
+        }
+        self.routes.insert(device, action);
+    }
+
+    /// 处理一个物理按键事件，返回需要发送回该设备的按键事件
+    pub fn process(&mut self, event: KeyEvent) -> Vec<OutputEvent> {
+        if !self.is_attached(event.device) {
+            self.attach(event.device, None);
+        }
+        // 绕过的设备不影响任何层与修饰键状态
+        if let Some(DeviceAction::Bypass) = self.routes[&event.device] {
+            return vec![OutputEvent {
+                code: event.code,
+                down: event.down,
+            }];
+        }
+
+        let id = self.state_id(event.device);
+        // 后端给出的时间可能因系统时间调整而晚于当前时间
+        let now = event.timestamp.min(self.clock.now());
+        let mut output = Vec::new();
+        let state = self.devices.entry(id).or_default();
+        state.one_shots.expire(now, self.one_shot_timeout);
+        self.expire(id, now, &mut output);
+        self.handle(id, event, now, &mut output);
+        output
+    }
+
+    /// 处理与输入无关的定时事件（tap-hold 与组合判定超时），返回需要发送的事件及目标设备
+    pub fn tick(&mut self) -> Vec<(DeviceId, Vec<OutputEvent>)> {
+        let now = self.clock.now();
+        let ids: Vec<DeviceId> = self.devices.keys().copied().collect();
+        let mut sent = Vec::new();
+        for id in ids {
+            // 输出发送到产生暂存事件的设备
+            let state = &self.devices[&id];
+            let device = state.pending.as_ref().map(|p| p.key.0);
+            let device = device.or(state.combo.as_ref().map(PendingCombo::device));
+            let device = device.or(state.leading.as_ref().map(|leading| leading.device));
+            let device = device.or(state.running.as_ref().map(RunningMacro::device));
+            let Some(device) = device.or(state.typed.device()) else {
+                continue;
+            };
+            let mut output = Vec::new();
+            self.expire(id, now, &mut output);
+            if !output.is_empty() {
+                sent.push((device, output));
+            }
+        }
+        sent
+    }
+
+    /// 中止所有正在执行的宏并抬起宏按住的键，退出拦截前调用，避免键卡在按下状态
+    pub fn stop_macros(&mut self) -> Vec<(DeviceId, Vec<OutputEvent>)> {
+        let mut sent = Vec::new();
+        for state in self.devices.values_mut() {
+            let Some(mut running) = state.running.take() else {
+                continue;
+            };
+            let mut output = Vec::new();
+            running.release_all(&mut state.modifiers, &mut output);
+            state.sync_modifiers(&mut output);
+            if !output.is_empty() {
+                sent.push((running.device(), output));
+            }
+        }
+        sent
+    }
+
+    /// 层与修饰键状态的编号：共享层时所有设备共用一份
+    fn state_id(&self, device: DeviceId) -> DeviceId {
+        if self.shared_layers { SHARED } else { device }
+    }
+
+    /// 判定时间已过的层键判定为按住，再处理暂存的事件；组合判定超时则结束判定；
+    /// 序列输入超时则输出暂不输出的事件；leader 超时则取消；执行宏已到时间的步骤
+    fn expire(&mut self, id: DeviceId, now: Instant, output: &mut Vec<OutputEvent>) {
+        let state = self.devices.entry(id).or_default();
+        if let Some(pending) = state
+            .pending
+            .take_if(|pending| pending.is_expired(now, &self.tap_hold[&pending.key.1]))
+        {
+            state.layers.hold(pending.layer, pending.key);
+            for event in pending.into_buffer() {
+                self.handle(id, event, now, output);
+            }
+        }
+        let state = &self.devices[&id];
+        if let Some(combo) = &state.combo
+            && combo.is_expired(now, &self.active_combos(state))
+        {
+            self.finish_combo(id, now, output);
+        }
+        if self.devices[&id]
+            .typed
+            .is_expired(now, self.sequences.timeout)
+        {
+            self.flush_sequence(id, now, output);
+        }
+        let state = self.devices.get_mut(&id).expect("设备状态");
+        if let Some(leader) = &self.leader
+            && let Some(leading) = state
+                .leading
+                .take_if(|leading| leading.is_expired(now, leader.timeout))
+        {
+            log::warn!("leader 超时，已输入: {}", leading.names());
+        }
+        // 宏执行步骤后恢复物理按住的修饰键
+        if let Some(running) = &mut state.running {
+            if running.advance(now, &mut state.modifiers, output) {
+                state.running = None;
+            }
+            state.sync_modifiers(output);
+        }
+    }
+
+    /// 当前层状态下生效的组合
+    fn active_combos(&self, state: &DeviceState) -> Vec<&Combo> {
+        self.combos
+            .iter()
+            .filter(|combo| combos::is_active(combo, &self.layers, &state.layers))
+            .collect()
+    }
+
+    /// 结束组合判定：暂存的键恰好组成组合时输出组合，否则按原来的顺序处理暂存的事件
+    fn finish_combo(&mut self, id: DeviceId, now: Instant, output: &mut Vec<OutputEvent>) {
+        let Some(pending) = self
+            .devices
+            .get_mut(&id)
+            .and_then(|state| state.combo.take())
+        else {
+            return;
+        };
+        let state = &self.devices[&id];
+        let chord = pending
+            .complete(&self.active_combos(state))
+            .map(|combo| combo.output.clone());
+        let state = self.devices.get_mut(&id).expect("设备状态");
+        match chord {
+            Some(chord) => {
+                // 组合中的键之后的自动重复与抬起都不输出
+                for event in pending.into_events() {
+                    state
+                        .pressed
+                        .insert((event.device, event.code), Pressed::Blocked);
+                }
+                state.layers.mark_used();
+                state.tap(&chord, output);
+            }
+            None => {
+                for event in pending.into_events() {
+                    self.deliver(id, event, now, output);
+                }
+            }
+        }
+    }
+
+    /// 处理一个事件：有待定的层键时先交给 tap-hold 判定
+    fn handle(
+        &mut self,
+        id: DeviceId,
+        event: KeyEvent,
+        now: Instant,
+        output: &mut Vec<OutputEvent>,
+    ) {
+        let state = self.devices.entry(id).or_default();
+        let Some(pending) = &mut state.pending else {
+            return self.dispatch(id, event, now, output);
+        };
+        let key = (event.device, event.code);
+        let decision = if key == pending.key {
+            // 待定期间的自动重复忽略，抬起即为轻触
+            if event.down {
+                return;
+            }
+            None
+        } else {
+            Some(pending.other_key(event, &self.tap_hold[&pending.key.1]))
+        };
+        match decision {
+            Some(Decision::Wait) => {}
+            Some(Decision::Now(event)) => self.dispatch(id, event, now, output),
+            Some(Decision::Hold) => {
+                let pending = state.pending.take().expect("待定的层键");
+                state.layers.hold(pending.layer, pending.key);
+                for event in pending.into_buffer() {
+                    self.handle(id, event, now, output);
+                }
+            }
+            None => {
+                let pending = state.pending.take().expect("待定的层键");
+                state.pressed.remove(&key);
+                state.tap_layer_key(&self.tap_hold[&key.1], key.0, now, output);
+                for event in pending.into_buffer() {
+                    self.handle(id, event, now, output);
+                }
+            }
+        }
+    }
+
+    /// 处理一个事件：组合中的键按下时先暂存，等待组合判定
+    fn dispatch(
+        &mut self,
+        id: DeviceId,
+        event: KeyEvent,
+        now: Instant,
+        output: &mut Vec<OutputEvent>,
+    ) {
+        let key = (event.device, event.code);
+        let state = self.devices.entry(id).or_default();
+        if state.leading.is_some() {
+            return self.lead(id, event, now, output);
+        }
+        // 不是层键的 leader 键按下即启动 leader
+        let is_layer_key = self.layers.iter().any(|layer| layer.key == event.code);
+        if let Some(leader) = &self.leader
+            && leader.key == event.code
+            && !is_layer_key
+            && event.down
+            && !state.pressed.contains_key(&key)
+        {
+            self.flush_sequence(id, now, output);
+            let state = self.devices.get_mut(&id).expect("设备状态");
+            state.pressed.insert(key, Pressed::Blocked);
+            state.start_leader(event.device, now);
+            return;
+        }
+        if self.combos.is_empty() {
+            return self.deliver(id, event, now, output);
+        }
+        self.devices.entry(id).or_default();
+        let state = &self.devices[&id];
+        let active = self.active_combos(state);
+        if let Some(pending) = &state.combo {
+            let continues = event.down
+                && event.device == pending.device()
+                && !pending.contains(event.code)
+                && pending.accepts(event.code, &active);
+            if continues {
+                let state = self.devices.get_mut(&id).expect("设备状态");
+                state.combo.as_mut().expect("正在判定的组合").push(event);
+                // 还可能组成键更多的组合时继续等待，否则立即结束判定
+                let state = &self.devices[&id];
+                let waiting = state
+                    .combo
+                    .as_ref()
+                    .is_some_and(|pending| pending.is_prefix(&self.active_combos(state)));
+                if !waiting {
+                    self.finish_combo(id, now, output);
+                }
+                return;
+            }
+            self.finish_combo(id, now, output);
+            return self.dispatch(id, event, now, output);
+        }
+        let starts = event.down
+            && !state.pressed.contains_key(&(event.device, event.code))
+            && active.iter().any(|combo| combo.keys.contains(&event.code));
+        if starts {
+            self.devices.get_mut(&id).expect("设备状态").combo =
+                Some(PendingCombo::new(event, now));
+        } else {
+            self.deliver(id, event, now, output);
+        }
+    }
+
+    /// leader 启动后的按键：沿前缀树匹配，匹配时输出；修饰键与抬起照常处理
+    fn lead(&mut self, id: DeviceId, event: KeyEvent, now: Instant, output: &mut Vec<OutputEvent>) {
+        let key = (event.device, event.code);
+        let is_layer_key = self.layers.iter().any(|layer| layer.key == event.code);
+        let state = self.devices.get_mut(&id).expect("设备状态");
+        if !event.down || event.code.is_modifier() || state.pressed.contains_key(&key) {
+            return self.deliver(id, event, now, output);
+        }
+        let mut leading = state.leading.take().expect("已启动的 leader");
+        // Esc 与层键取消 leader，层键照常激活层
+        if is_layer_key {
+            log::info!("leader 已取消");
+            return self.deliver(id, event, now, output);
+        }
+        state.pressed.insert(key, Pressed::Blocked);
+        if event.code == OsCode::KEY_ESC {
+            log::info!("leader 已取消");
+            return;
+        }
+        leading.push(event.code, now);
+        match self.leader_trie.find(&leading.keys) {
+            Match::Prefix => state.leading = Some(leading),
+            Match::Complete(chord) => {
+                log::info!("leader {} -> {chord}", leading.names());
+                state.tap(chord, output);
+            }
+            Match::Mismatch => log::warn!("未定义的 leader 序列: {}", leading.names()),
+        }
+    }
+
+    /// 按当前层状态处理一个事件
+    fn deliver(
+        &mut self,
+        id: DeviceId,
+        event: KeyEvent,
+        now: Instant,
+        output: &mut Vec<OutputEvent>,
+    ) {
+        let key = (event.device, event.code);
+
+        // 层的激活键本身不发送给系统
+        if let Some(layer) = self.layers.iter().position(|layer| layer.key == event.code) {
+            // 层状态改变前先结束序列输入
+            self.flush_sequence(id, now, output);
+            let state = self.devices.entry(id).or_default();
+            if !event.down {
+                state.pressed.remove(&key);
+                let unused = state.layers.release(key) == Some(false);
+                if let Some(config) = self.tap_hold.get(&event.code)
+                    && config.retro_tap
+                    && unused
+                {
+                    state.tap_layer_key(config, key.0, now, output);
+                }
+            } else if state.pressed.insert(key, Pressed::Layer).is_none() {
+                // 自动重复不会再次切换层
+                match self.layers[layer].mode {
+                    LayerMode::Momentary if self.tap_hold.contains_key(&event.code) => {
+                        state.pending = Some(PendingTap::new(key, layer, now));
+                    }
+                    LayerMode::Momentary => state.layers.hold(layer, key),
+                    LayerMode::Toggle => state.layers.toggle(layer),
+                    LayerMode::To => state.layers.switch_base(layer),
+                }
+            }
+            return;
+        }
+        self.type_sequence(id, event, now, output);
+    }
+
+    /// 前台程序是否关闭了序列
+    fn sequences_disabled(&self) -> bool {
+        if self.sequences.disabled_apps.is_empty() {
+            return false;
+        }
+        let app = self.foreground_app.as_ref().and_then(|query| query());
+        app.is_some_and(|app| self.sequences.is_disabled_in(&app))
+    }
+
+    /// 按顺序输入的序列：沿前缀树匹配已输入的键，匹配后删除或丢弃已输入的键并输出序列。
+    /// 只在没有层生效、没有按住修饰键时匹配
+    fn type_sequence(
+        &mut self,
+        id: DeviceId,
+        event: KeyEvent,
+        now: Instant,
+        output: &mut Vec<OutputEvent>,
+    ) {
+        if self.trie.is_empty() {
+            return self.deliver_key(id, event, now, output);
+        }
+        let key = (event.device, event.code);
+        let state = self.devices.entry(id).or_default();
+        // 暂不输出的键，其抬起与自动重复同样暂存
+        if state.typed.holds_press(&event) {
+            return state.typed.hold(event);
+        }
+        if !event.down {
+            return self.deliver_key(id, event, now, output);
+        }
+        // 自动重复已经多输入了字符，已输入的键不再组成序列
+        if state.pressed.contains_key(&key) {
+            self.flush_sequence(id, now, output);
+            return self.deliver_key(id, event, now, output);
+        }
+        if state.typed.is_expired(now, self.sequences.timeout) {
+            self.flush_sequence(id, now, output);
+        }
+
+        let state = &self.devices[&id];
+        let mut keys = state.typed.keys.clone();
+        keys.push(event.code);
+        let enabled = state.layers.is_empty()
+            && state.modifiers.physical().is_empty()
+            && state.one_shots.is_empty();
+        let found = self.trie.find(&keys);
+        // 开始新的序列时才查询前台程序
+        let starts = state.typed.keys.is_empty() && found != Match::Mismatch;
+        let enabled = enabled && !(starts && self.sequences_disabled());
+        match found {
+            _ if !enabled => {
+                self.flush_sequence(id, now, output);
+                self.deliver_key(id, event, now, output);
+            }
+            Match::Complete(chord) => {
+                let chord = chord.clone();
+                let state = self.devices.get_mut(&id).expect("设备状态");
+                let typed = std::mem::take(&mut state.typed);
+                match self.sequences.rollback {
+                    // 已输入的键各删除一个字符
+                    Rollback::Backspace => {
+                        for _ in &typed.keys {
+                            state.tap(&KeyChord::single(OsCode::KEY_BACKSPACE), output);
+                        }
+                    }
+                    // 丢弃暂存的事件，仍按住的键之后的抬起不输出
+                    Rollback::Hold => {
+                        for event in typed.still_pressed() {
+                            state
+                                .pressed
+                                .insert((event.device, event.code), Pressed::Blocked);
+                        }
+                    }
+                }
+                state.pressed.insert(key, Pressed::Blocked);
+                state.tap(&chord, output);
+            }
+            Match::Prefix => {
+                let state = self.devices.get_mut(&id).expect("设备状态");
+                state.typed.push(event.code, now);
+                match self.sequences.rollback {
+                    Rollback::Backspace => self.deliver_key(id, event, now, output),
+                    Rollback::Hold => state.typed.hold(event),
+                }
+            }
+            // 走不通时从根重新匹配这个键
+            Match::Mismatch if !state.typed.keys.is_empty() => {
+                self.flush_sequence(id, now, output);
+                self.type_sequence(id, event, now, output);
+            }
+            Match::Mismatch => self.deliver_key(id, event, now, output),
+        }
+    }
+
+    /// 结束序列输入，按原来的顺序输出暂不输出的事件
+    fn flush_sequence(&mut self, id: DeviceId, now: Instant, output: &mut Vec<OutputEvent>) {
+        let Some(state) = self.devices.get_mut(&id) else {
+            return;
+        };
+        for event in std::mem::take(&mut state.typed).into_held() {
+            self.deliver_key(id, event, now, output);
+        }
+    }
+
+    /// 按当前层状态输出一个非层键的事件
+    fn deliver_key(
+        &mut self,
+        id: DeviceId,
+        event: KeyEvent,
+        now: Instant,
+        output: &mut Vec<OutputEvent>,
+    ) {
+        let key = (event.device, event.code);
+        let state = self.devices.entry(id).or_default();
+        if state.layers.is_empty()
+            && let Some(is_down) = &self.key_state
+        {
+            state.modifiers.resync(is_down, output);
+        }
+        if event.down {
+            // 配置加载时已校验映射存在
+            let caps = match self.routes.get(&event.device) {
+                Some(Some(DeviceAction::Profile(name))) => &self.profiles[name],
+                _ => &self.layers[CAPS].keymap,
+            };
+            let view = LayerView {
+                layers: &self.layers,
+                caps,
+                overrides: &self.overrides,
+            };
+            state.press(&view, key, now, output);
+        } else {
+            state.release(key, output);
+        }
+    }
+}
+
+impl DeviceState {
+    fn press(
+        &mut self,
+        view: &LayerView,
+        key: PhysicalKey,
+        now: Instant,
+        output: &mut Vec<OutputEvent>,
+    ) {
+        // 没有抬起又再次按下即为自动重复，沿用第一次按下时的输出
+        let (pressed, repeat) = match self.pressed.get(&key) {
+            Some(pressed) => (pressed.clone(), true),
+            None => {
+                self.layers.mark_used();
+                let pressed = if key.1 == OsCode::KEY_ESC && !self.one_shots.is_empty() {
+                    // Esc 只用于取消，本身不输出
+                    self.one_shots.cancel();
+                    Pressed::Blocked
+                } else {
+                    let pressed = self.resolve(view, key.1);
+                    self.apply_one_shots(pressed)
+                };
+                if let Pressed::Chord(_) = pressed {
+                    self.chords.push(key);
+                }
+                self.pressed.insert(key, pressed.clone());
+                (pressed, false)
+            }
+        };
+        match pressed {
+            Pressed::Key(key) if key.is_modifier() => {
+                self.modifiers.set_physical(key, true);
+                self.sync_modifiers(output);
+            }
+            Pressed::Key(key) => output.push(OutputEvent::press(key)),
+            // 关闭了重复的映射只在第一次按下时输出
+            Pressed::Chord(binding) if repeat && !binding.repeat => {}
+            // 修饰键已经按下，自动重复时只会重发非修饰键
+            Pressed::Chord(binding) => {
+                self.sync_modifiers(output);
+                if !binding.output.key.is_modifier() {
+                    output.push(OutputEvent::press(binding.output.key));
+                }
+            }
+            Pressed::Macro(_) if repeat => {}
+            Pressed::Macro(mac) => self.run_macro(key, &mac, now, output),
+            Pressed::OneShot(_) if repeat => {}
+            Pressed::OneShot(one_shot) => {
+                let target = match one_shot {
+                    OneShot::Modifier(code) => Target::Modifier(code),
+                    OneShot::Layer(name) => Target::Layer(view.layer_id(&name)),
+                };
+                self.one_shots.trigger(target, now);
+            }
+            Pressed::Layer | Pressed::Blocked => {}
+        }
+    }
+
+    fn release(&mut self, key: PhysicalKey, output: &mut Vec<OutputEvent>) {
+        let code = key.1;
+        match self.pressed.remove(&key) {
+            Some(Pressed::Key(key)) if key.is_modifier() => {
+                self.modifiers.set_physical(key, false);
+                self.sync_modifiers(output);
+            }
+            Some(Pressed::Key(key)) => output.push(OutputEvent::release(key)),
+            Some(Pressed::Chord(binding)) => {
+                if !binding.output.key.is_modifier() {
+                    output.push(OutputEvent::release(binding.output.key));
+                }
+                self.chords.retain(|&trigger| trigger != key);
+                self.sync_modifiers(output);
+            }
+            // 宏在自己的时间线上继续执行，不随触发键抬起而中止
+            Some(Pressed::Layer | Pressed::Blocked | Pressed::OneShot(_) | Pressed::Macro(_)) => {}
+            // 没有记录的键（例如启动前就已按下）原样释放
+            None => {
+                self.modifiers.set_physical(code, false);
+                self.modifiers.set_sent(code, false);
+                output.push(OutputEvent::release(code));
+            }
+        }
+    }
+
+    /// 物理键按下时应输出的键：从上到下查找激活的层，未映射时按层的设置原样输出、
+    /// 拦下或交给下一层；没有层生效时原样输出。带修饰键的映射按此时物理按住的修饰键查找，
+    /// 原样输出的键再按同样的修饰键查找按键覆盖。已触发的一次性层在最上面，一次性修饰键视为按住
+    fn resolve(&self, view: &LayerView, code: OsCode) -> Pressed {
+        let mut modifiers = self.modifiers.physical();
+        modifiers.extend(self.one_shots.modifiers());
+        for layer in self.one_shots.layers().chain(self.layers.iter()) {
+            let keymap = view.keymap(layer);
+            if let Some(one_shot) = keymap.one_shot(code) {
+                return Pressed::OneShot(one_shot.clone());
+            }
+            if let Some(mac) = keymap.get_macro(code) {
+                return Pressed::Macro(mac.clone());
+            }
+            if let Some(binding) = keymap.lookup(code, &modifiers) {
+                return Pressed::Chord(binding.clone());
+            }
+            match view.fallthrough(layer) {
+                Fallthrough::Pass => break,
+                // 修饰键不拦下，以便与下面的层或其他键组合
+                Fallthrough::Block if !code.is_modifier() => return Pressed::Blocked,
+                Fallthrough::Block => break,
+                Fallthrough::Defer => {}
+            }
+        }
+        match view.overrides.lookup(code, &modifiers) {
+            Some(Some(binding)) => Pressed::Chord(binding.clone()),
+            Some(None) => Pressed::Blocked,
+            None => Pressed::Key(code),
+        }
+    }
+
+    /// 已触发一次性修饰键或层后按下的键：输出带上一次性修饰键，之后未锁定的失效。
+    /// 修饰键与触发其他一次性修饰键或层的键不算
+    fn apply_one_shots(&mut self, pressed: Pressed) -> Pressed {
+        let skip = match &pressed {
+            Pressed::Key(code) => code.is_modifier(),
+            Pressed::OneShot(_) => true,
+            _ => false,
+        };
+        if self.one_shots.is_empty() || skip {
+            return pressed;
+        }
+        let modifiers: Vec<OsCode> = self.one_shots.modifiers().collect();
+        let pressed = match pressed {
+            Pressed::Key(code) if !modifiers.is_empty() => Pressed::Chord(Binding {
+                output: KeyChord {
+                    modifiers,
+                    key: code,
+                },
+                repeat: true,
+                suppress: Vec::new(),
+            }),
+            Pressed::Chord(mut binding) => {
+                for code in modifiers {
+                    let kind = code.modifier_kind();
+                    let present = binding
+                        .output
+                        .modifiers
+                        .iter()
+                        .any(|m| m.modifier_kind() == kind);
+                    if !present && !binding.suppress.contains(&kind) {
+                        binding.output.modifiers.push(code);
+                    }
+                }
+                Pressed::Chord(binding)
+            }
+            pressed => pressed,
+        };
+        self.one_shots.consume();
+        pressed
+    }
+
+    /// 按下触发宏的键：宏正在执行时中止它，否则开始执行。
+    /// 宏的步骤在没有按住其他修饰键的状态下执行，等待或结束时恢复物理按住的修饰键
+    fn run_macro(
+        &mut self,
+        key: PhysicalKey,
+        mac: &Macro,
+        now: Instant,
+        output: &mut Vec<OutputEvent>,
+    ) {
+        if let Some(mut running) = self.running.take() {
+            log::info!("宏已中止");
+            running.release_all(&mut self.modifiers, output);
+            self.sync_modifiers(output);
+            if running.trigger == key {
+                return;
+            }
+        }
+        log::info!("执行宏 {}", mac.name);
+        let mut running = RunningMacro::new(key, mac, now);
+        if !running.advance(now, &mut self.modifiers, output) {
+            self.running = Some(running);
+        }
+        self.sync_modifiers(output);
+    }
+
+    /// 层键判定为轻触：输出轻触的键，或者启动 leader
+    fn tap_layer_key(
+        &mut self,
+        config: &TapHold,
+        device: DeviceId,
+        now: Instant,
+        output: &mut Vec<OutputEvent>,
+    ) {
+        match &config.tap {
+            Some(chord) => self.tap(chord, output),
+            None => self.start_leader(device, now),
+        }
+    }
+
+    fn start_leader(&mut self, device: DeviceId, now: Instant) {
+        log::info!("leader 已启动");
+        self.leading = Some(Leading::new(device, now));
+    }
+
+    /// 轻触层键时输出组合键：按下并抬起，之后恢复修饰键。
+    /// 按住的修饰键保持按下，与按住映射时一致
+    fn tap(&mut self, chord: &KeyChord, output: &mut Vec<OutputEvent>) {
+        let mut desired = self.desired_modifiers();
+        add_modifiers(&mut desired, chord.keys());
+        self.modifiers.sync(&desired, output);
+        if !chord.key.is_modifier() {
+            output.push(OutputEvent::press(chord.key));
+            output.push(OutputEvent::release(chord.key));
+        }
+        self.sync_modifiers(output);
+    }
+
+    /// 让系统中按下的修饰键与当前状态一致
+    fn sync_modifiers(&mut self, output: &mut Vec<OutputEvent>) {
+        let desired = self.desired_modifiers();
+        self.modifiers.sync(&desired, output);
+    }
+
+    /// 按住映射时，系统中的修饰键为物理按住的修饰键（除去最后按下的映射要抬起的）
+    /// 加上该映射所需的修饰键；否则与物理状态一致。正在执行的宏按住的修饰键保持按下
+    fn desired_modifiers(&self) -> Vec<OsCode> {
+        let mut desired = self.modifiers.physical();
+        if let Some(Pressed::Chord(binding)) =
+            self.chords.last().and_then(|key| self.pressed.get(key))
+        {
+            desired.retain(|key| !binding.suppress.contains(&key.modifier_kind()));
+            add_modifiers(&mut desired, binding.output.keys());
+        }
+        for key in self.running.iter().flat_map(RunningMacro::modifiers) {
+            if !desired.contains(&key) {
+                desired.push(key);
+            }
+        }
+        desired
+    }
+}
+
+/// 加入 `keys` 中的修饰键，已经有同种类的修饰键（例如右 Ctrl）时不再加入另一侧的
+fn add_modifiers(desired: &mut Vec<OsCode>, keys: impl Iterator<Item = OsCode>) {
+    for key in keys.filter(|key| key.is_modifier()) {
+        if !desired
+            .iter()
+            .any(|held| held.modifier_kind() == key.modifier_kind())
+        {
+            desired.push(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::oscode::KeyChord;
+    use OsCode::*;
+    use OutputEvent as Out;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    const DEVICE: DeviceId = 1;
+
+    fn event(engine: &Engine, code: OsCode, down: bool) -> KeyEvent {
+        event_on(engine, DEVICE, code, down)
+    }
+
+    /// 引擎时钟当前时刻产生的按键事件
+    fn event_on(engine: &Engine, device: DeviceId, code: OsCode, down: bool) -> KeyEvent {
+        KeyEvent {
+            code,
+            down,
+            device,
+            timestamp: engine.clock.now(),
+        }
+    }
+
+    /// 按配置创建引擎，时间由返回的时钟控制
+    fn engine_from(src: &str) -> (Engine, FakeClock) {
+        let config = Config::parse(src, "nuna.toml").unwrap();
+        let clock = FakeClock::new();
+        (Engine::from_config(config, clock.clone()), clock)
+    }
+
+    fn press(engine: &mut Engine, code: OsCode) -> Vec<OutputEvent> {
+        engine.process(event(engine, code, true))
+    }
+
+    fn release(engine: &mut Engine, code: OsCode) -> Vec<OutputEvent> {
+        engine.process(event(engine, code, false))
+    }
+
+    /// 依次处理一串事件，返回所有输出
+    fn run(engine: &mut Engine, events: &[(OsCode, bool)]) -> Vec<OutputEvent> {
+        events
+            .iter()
+            .flat_map(|&(code, down)| engine.process(event(engine, code, down)))
+            .collect()
+    }
+
+    #[test]
+    fn readme_bindings() {
+        let cases = [
+            (KEY_A, vec![KEY_HOME]),
+            (KEY_E, vec![KEY_END]),
+            (KEY_H, vec![KEY_LEFT]),
+            (KEY_J, vec![KEY_UP]),
+            (KEY_K, vec![KEY_DOWN]),
+            (KEY_L, vec![KEY_RIGHT]),
+            (KEY_F, vec![KEY_LEFTCTRL, KEY_RIGHT]),
+            (KEY_B, vec![KEY_LEFTCTRL, KEY_LEFT]),
+            (KEY_SPACE, vec![KEY_BACKSPACE]),
+            (KEY_D, vec![KEY_DELETE]),
+            (KEY_Q, vec![KEY_LEFTCTRL, KEY_A]),
+            (KEY_S, vec![KEY_LEFTCTRL, KEY_S]),
+            (KEY_W, vec![KEY_LEFTCTRL, KEY_W]),
+            (KEY_Z, vec![KEY_LEFTCTRL, KEY_Z]),
+            (KEY_X, vec![KEY_LEFTCTRL, KEY_X]),
+            (KEY_C, vec![KEY_LEFTCTRL, KEY_C]),
+            (KEY_V, vec![KEY_LEFTCTRL, KEY_V]),
+        ];
+        for (trigger, keys) in cases {
+            let mut engine = Engine::new(Keymap::builtin());
+            let expected: Vec<_> = keys
+                .iter()
+                .copied()
+                .map(Out::press)
+                .chain(keys.iter().rev().copied().map(Out::release))
+                .collect();
+            let output = run(
+                &mut engine,
+                &[
+                    (KEY_CAPSLOCK, true),
+                    (trigger, true),
+                    (trigger, false),
+                    (KEY_CAPSLOCK, false),
+                ],
+            );
+            assert_eq!(output, expected, "Caps+{trigger}");
+        }
+    }
+
+    #[test]
+    fn capslock_is_never_sent() {
+        let mut engine = Engine::new(Keymap::builtin());
+        assert!(press(&mut engine, KEY_CAPSLOCK).is_empty());
+        assert!(release(&mut engine, KEY_CAPSLOCK).is_empty());
+    }
+
+    #[test]
+    fn keys_pass_through_without_layer() {
+        let mut engine = Engine::new(Keymap::builtin());
+        assert_eq!(press(&mut engine, KEY_H), [Out::press(KEY_H)]);
+        assert_eq!(release(&mut engine, KEY_H), [Out::release(KEY_H)]);
+    }
+
+    #[test]
+    fn unmapped_keys_pass_through_in_layer() {
+        let mut engine = Engine::new(Keymap::builtin());
+        press(&mut engine, KEY_CAPSLOCK);
+        assert_eq!(press(&mut engine, KEY_G), [Out::press(KEY_G)]);
+        assert_eq!(release(&mut engine, KEY_G), [Out::release(KEY_G)]);
+    }
+
+    #[test]
+    fn chord_presses_in_order_and_releases_in_reverse() {
+        let mut engine = Engine::new(Keymap::builtin());
+        press(&mut engine, KEY_CAPSLOCK);
+        assert_eq!(
+            press(&mut engine, KEY_B),
+            [Out::press(KEY_LEFTCTRL), Out::press(KEY_LEFT)]
+        );
+        assert_eq!(
+            release(&mut engine, KEY_B),
+            [Out::release(KEY_LEFT), Out::release(KEY_LEFTCTRL)]
+        );
+    }
+
+    #[test]
+    fn repeat_does_not_repress_held_modifiers() {
+        let mut engine = Engine::new(Keymap::builtin());
+        press(&mut engine, KEY_CAPSLOCK);
+        let output = run(&mut engine, &[(KEY_H, true), (KEY_H, true), (KEY_H, true)]);
+        assert_eq!(output, [Out::press(KEY_LEFT); 3]);
+        let output = run(&mut engine, &[(KEY_Z, true), (KEY_Z, true)]);
+        assert_eq!(
+            output,
+            [
+                Out::press(KEY_LEFTCTRL),
+                Out::press(KEY_Z),
+                Out::press(KEY_Z),
+            ]
+        );
+    }
+
+    #[test]
+    fn repeat_with_held_shift_resends_only_the_key() {
+        let mut engine = Engine::new(Keymap::builtin());
+        press(&mut engine, KEY_LEFTSHIFT);
+        press(&mut engine, KEY_CAPSLOCK);
+        let output = run(&mut engine, &[(KEY_F, true), (KEY_F, true), (KEY_F, true)]);
+        assert_eq!(
+            output,
+            [
+                Out::press(KEY_LEFTCTRL),
+                Out::press(KEY_RIGHT),
+                Out::press(KEY_RIGHT),
+                Out::press(KEY_RIGHT),
+            ]
+        );
+    }
+
+    #[test]
+    fn binding_can_suppress_repeat() {
+        let mut engine = Engine::new(Keymap::builtin());
+        press(&mut engine, KEY_CAPSLOCK);
+        let output = run(
+            &mut engine,
+            &[(KEY_S, true), (KEY_S, true), (KEY_S, true), (KEY_S, false)],
+        );
+        assert_eq!(
+            output,
+            [
+                Out::press(KEY_LEFTCTRL),
+                Out::press(KEY_S),
+                Out::release(KEY_S),
+                Out::release(KEY_LEFTCTRL),
+            ]
+        );
+        // 再次按下时照常输出
+        assert_eq!(
+            press(&mut engine, KEY_S),
+            [Out::press(KEY_LEFTCTRL), Out::press(KEY_S)]
+        );
+    }
+
+    #[test]
+    fn physical_ctrl_survives_ctrl_chord() {
+        let mut engine = Engine::new(Keymap::builtin());
+        assert_eq!(press(&mut engine, KEY_LEFTCTRL), [Out::press(KEY_LEFTCTRL)]);
+        assert!(engine.devices[&DEVICE].modifiers.is_physical(KEY_LEFTCTRL));
+        press(&mut engine, KEY_CAPSLOCK);
+        // Ctrl 已按下，只需按下 C
+        assert_eq!(press(&mut engine, KEY_C), [Out::press(KEY_C)]);
+        assert_eq!(release(&mut engine, KEY_C), [Out::release(KEY_C)]);
+        release(&mut engine, KEY_CAPSLOCK);
+        assert!(
+            !engine.devices[&DEVICE]
+                .modifiers
+                .is_nuna_pressed(KEY_LEFTCTRL)
+        );
+        assert_eq!(
+            release(&mut engine, KEY_LEFTCTRL),
+            [Out::release(KEY_LEFTCTRL)]
+        );
+    }
+
+    /// 带修饰键触发的映射：按住 Shift 或 Alt 时 Caps+H 输出 Home
+    fn modified_keymap() -> Keymap {
+        let src = "[bindings]\nH = \"Left\"\nF = \"Ctrl+Right\"\n\"Shift+H\" = \"Home\"\n\"Alt+H\" = \"Home\"\n";
+        Keymap::parse(src, "nuna.toml").unwrap()
+    }
+
+    #[test]
+    fn held_modifiers_combine_with_layer_outputs() {
+        let mut engine = Engine::new(Keymap::builtin());
+        let output = run(
+            &mut engine,
+            &[
+                (KEY_CAPSLOCK, true),
+                (KEY_LEFTSHIFT, true),
+                (KEY_H, true),
+                (KEY_H, false),
+            ],
+        );
+        assert_eq!(
+            output,
+            [
+                Out::press(KEY_LEFTSHIFT),
+                Out::press(KEY_LEFT),
+                Out::release(KEY_LEFT),
+            ]
+        );
+        // Caps+Shift+F 选中一个词
+        assert_eq!(
+            press(&mut engine, KEY_F),
+            [Out::press(KEY_LEFTCTRL), Out::press(KEY_RIGHT)]
+        );
+        assert_eq!(
+            release(&mut engine, KEY_F),
+            [Out::release(KEY_RIGHT), Out::release(KEY_LEFTCTRL)]
+        );
+        assert!(engine.devices[&DEVICE].modifiers.is_physical(KEY_LEFTSHIFT));
+    }
+
+    #[test]
+    fn held_right_modifier_satisfies_chord() {
+        let mut engine = Engine::new(Keymap::builtin());
+        run(&mut engine, &[(KEY_RIGHTCTRL, true), (KEY_CAPSLOCK, true)]);
+        // 右 Ctrl 已按下，不再按下左 Ctrl
+        assert_eq!(press(&mut engine, KEY_F), [Out::press(KEY_RIGHT)]);
+        assert_eq!(release(&mut engine, KEY_F), [Out::release(KEY_RIGHT)]);
+    }
+
+    #[test]
+    fn modified_binding_takes_priority() {
+        let mut engine = Engine::new(modified_keymap());
+        run(&mut engine, &[(KEY_RIGHTSHIFT, true), (KEY_CAPSLOCK, true)]);
+        // 不区分左右：右 Shift 同样触发 "Shift+H"
+        assert_eq!(
+            press(&mut engine, KEY_H),
+            [Out::release(KEY_RIGHTSHIFT), Out::press(KEY_HOME)]
+        );
+        assert_eq!(
+            release(&mut engine, KEY_H),
+            [Out::release(KEY_HOME), Out::press(KEY_RIGHTSHIFT)]
+        );
+        // 修饰键不完全一致时使用不带修饰键的映射
+        press(&mut engine, KEY_LEFTCTRL);
+        assert_eq!(press(&mut engine, KEY_H), [Out::press(KEY_LEFT)]);
+    }
+
+    #[test]
+    fn held_modifiers_are_lifted_and_restored() {
+        let mut engine = Engine::new(modified_keymap());
+        press(&mut engine, KEY_LEFTSHIFT);
+        press(&mut engine, KEY_CAPSLOCK);
+        assert_eq!(
+            press(&mut engine, KEY_H),
+            [Out::release(KEY_LEFTSHIFT), Out::press(KEY_HOME)]
+        );
+        assert!(engine.devices[&DEVICE].modifiers.is_lifted(KEY_LEFTSHIFT));
+        // 暂时抬起期间的重复不恢复 Shift
+        assert_eq!(press(&mut engine, KEY_H), [Out::press(KEY_HOME)]);
+        assert_eq!(
+            release(&mut engine, KEY_H),
+            [Out::release(KEY_HOME), Out::press(KEY_LEFTSHIFT)]
+        );
+        assert!(!engine.devices[&DEVICE].modifiers.is_lifted(KEY_LEFTSHIFT));
+    }
+
+    #[test]
+    fn modifier_released_while_lifted_is_not_restored() {
+        let mut engine = Engine::new(modified_keymap());
+        run(
+            &mut engine,
+            &[(KEY_LEFTALT, true), (KEY_CAPSLOCK, true), (KEY_H, true)],
+        );
+        assert!(release(&mut engine, KEY_LEFTALT).is_empty());
+        assert_eq!(release(&mut engine, KEY_H), [Out::release(KEY_HOME)]);
+    }
+
+    #[test]
+    fn physical_modifier_pressed_during_chord_stays_down() {
+        let mut engine = Engine::new(Keymap::builtin());
+        run(&mut engine, &[(KEY_CAPSLOCK, true), (KEY_B, true)]);
+        assert!(
+            engine.devices[&DEVICE]
+                .modifiers
+                .is_nuna_pressed(KEY_LEFTCTRL)
+        );
+        assert!(press(&mut engine, KEY_LEFTCTRL).is_empty());
+        assert_eq!(release(&mut engine, KEY_B), [Out::release(KEY_LEFT)]);
+        assert_eq!(
+            release(&mut engine, KEY_LEFTCTRL),
+            [Out::release(KEY_LEFTCTRL)]
+        );
+    }
+
+    #[test]
+    fn overlapping_chords_share_modifiers() {
+        let mut engine = Engine::new(Keymap::builtin());
+        let output = run(
+            &mut engine,
+            &[(KEY_CAPSLOCK, true), (KEY_B, true), (KEY_F, true)],
+        );
+        assert_eq!(
+            output,
+            [
+                Out::press(KEY_LEFTCTRL),
+                Out::press(KEY_LEFT),
+                Out::press(KEY_RIGHT),
+            ]
+        );
+        assert_eq!(release(&mut engine, KEY_B), [Out::release(KEY_LEFT)]);
+        // 后按下的 H 不需要 Ctrl，期间暂时抬起
+        assert_eq!(
+            press(&mut engine, KEY_H),
+            [Out::release(KEY_LEFTCTRL), Out::press(KEY_LEFT)]
+        );
+        assert_eq!(
+            release(&mut engine, KEY_H),
+            [Out::release(KEY_LEFT), Out::press(KEY_LEFTCTRL)]
+        );
+        assert_eq!(
+            release(&mut engine, KEY_F),
+            [Out::release(KEY_RIGHT), Out::release(KEY_LEFTCTRL)]
+        );
+    }
+
+    /// CapsLock 与触发键按下、抬起的所有交错顺序下，输出的键都被完整释放，
+    /// 且只有在 CapsLock 按住期间按下的键才会映射
+    #[test]
+    fn releases_outputs_for_every_caps_interleaving() {
+        let caps_down = (KEY_CAPSLOCK, true);
+        let caps_up = (KEY_CAPSLOCK, false);
+        for trigger in [KEY_B, KEY_H, KEY_G] {
+            let down = (trigger, true);
+            let up = (trigger, false);
+            let orders = [
+                [caps_down, down, up, caps_up],
+                [caps_down, down, caps_up, up],
+                [caps_down, caps_up, down, up],
+                [down, caps_down, up, caps_up],
+                [down, caps_down, caps_up, up],
+                [down, up, caps_down, caps_up],
+            ];
+            for order in orders {
+                let mut engine = Engine::new(Keymap::builtin());
+                let output = run(&mut engine, &order);
+
+                let in_layer = order[..order.iter().position(|&e| e == down).unwrap()]
+                    .last()
+                    .is_some_and(|&e| e == caps_down);
+                let chord = match Keymap::builtin().get(trigger) {
+                    Some(binding) if in_layer => binding.output.clone(),
+                    _ => KeyChord::single(trigger),
+                };
+                let expected: Vec<_> = chord
+                    .keys()
+                    .map(Out::press)
+                    .chain(chord.keys().rev().map(Out::release))
+                    .collect();
+                assert_eq!(output, expected, "{order:?}");
+                assert!(engine.devices[&DEVICE].pressed.is_empty(), "{order:?}");
+                assert!(engine.devices[&DEVICE].chords.is_empty(), "{order:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn repeat_after_layer_release_keeps_layer_output() {
+        let mut engine = Engine::new(Keymap::builtin());
+        run(
+            &mut engine,
+            &[(KEY_CAPSLOCK, true), (KEY_H, true), (KEY_CAPSLOCK, false)],
+        );
+        assert_eq!(press(&mut engine, KEY_H), [Out::press(KEY_LEFT)]);
+        assert_eq!(release(&mut engine, KEY_H), [Out::release(KEY_LEFT)]);
+    }
+
+    #[test]
+    fn overlapping_triggers_release_their_own_outputs() {
+        let mut engine = Engine::new(Keymap::builtin());
+        let output = run(
+            &mut engine,
+            &[
+                (KEY_CAPSLOCK, true),
+                (KEY_H, true),
+                (KEY_CAPSLOCK, false),
+                (KEY_J, true),
+                (KEY_H, false),
+                (KEY_J, false),
+            ],
+        );
+        assert_eq!(
+            output,
+            [
+                Out::press(KEY_LEFT),
+                Out::press(KEY_J),
+                Out::release(KEY_LEFT),
+                Out::release(KEY_J),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_release_passes_through() {
+        // 启动前就已按下的键，抬起时原样发送
+        let mut engine = Engine::new(Keymap::builtin());
+        press(&mut engine, KEY_CAPSLOCK);
+        assert_eq!(release(&mut engine, KEY_H), [Out::release(KEY_H)]);
+    }
+
+    const LAPTOP: DeviceId = 1;
+    const EXTERNAL: DeviceId = 2;
+
+    /// 依次处理多个设备上的事件，返回所有输出
+    fn run_on(engine: &mut Engine, events: &[(DeviceId, OsCode, bool)]) -> Vec<OutputEvent> {
+        events
+            .iter()
+            .flat_map(|&(device, code, down)| engine.process(event_on(engine, device, code, down)))
+            .collect()
+    }
+
+    #[test]
+    fn layer_is_per_device() {
+        let mut engine = Engine::new(Keymap::builtin());
+        let output = run_on(
+            &mut engine,
+            &[
+                (LAPTOP, KEY_CAPSLOCK, true),
+                (EXTERNAL, KEY_H, true),
+                (EXTERNAL, KEY_H, false),
+                (LAPTOP, KEY_H, true),
+                (LAPTOP, KEY_H, false),
+            ],
+        );
+        assert_eq!(
+            output,
+            [
+                Out::press(KEY_H),
+                Out::release(KEY_H),
+                Out::press(KEY_LEFT),
+                Out::release(KEY_LEFT),
+            ]
+        );
+    }
+
+    #[test]
+    fn releasing_caps_on_one_device_keeps_the_other_layer() {
+        let mut engine = Engine::new(Keymap::builtin());
+        let output = run_on(
+            &mut engine,
+            &[
+                (LAPTOP, KEY_CAPSLOCK, true),
+                (EXTERNAL, KEY_CAPSLOCK, true),
+                (LAPTOP, KEY_CAPSLOCK, false),
+                (EXTERNAL, KEY_H, true),
+            ],
+        );
+        assert_eq!(output, [Out::press(KEY_LEFT)]);
+    }
+
+    #[test]
+    fn modifiers_are_per_device() {
+        let mut engine = Engine::new(Keymap::builtin());
+        // 笔记本上按住的 Shift 不会被外接键盘的映射抬起
+        let output = run_on(
+            &mut engine,
+            &[
+                (LAPTOP, KEY_LEFTSHIFT, true),
+                (EXTERNAL, KEY_CAPSLOCK, true),
+                (EXTERNAL, KEY_H, true),
+            ],
+        );
+        assert_eq!(output, [Out::press(KEY_LEFTSHIFT), Out::press(KEY_LEFT)]);
+    }
+
+    #[test]
+    fn shared_layers_span_devices() {
+        let mut engine = Engine::new(Keymap::builtin()).with_shared_layers(true);
+        let output = run_on(
+            &mut engine,
+            &[
+                (LAPTOP, KEY_CAPSLOCK, true),
+                (EXTERNAL, KEY_B, true),
+                (LAPTOP, KEY_CAPSLOCK, false),
+                (EXTERNAL, KEY_B, false),
+            ],
+        );
+        assert_eq!(
+            output,
+            [
+                Out::press(KEY_LEFTCTRL),
+                Out::press(KEY_LEFT),
+                Out::release(KEY_LEFT),
+                Out::release(KEY_LEFTCTRL),
+            ]
+        );
+    }
+
+    #[test]
+    fn shared_layers_track_same_key_per_device() {
+        let mut engine = Engine::new(Keymap::builtin()).with_shared_layers(true);
+        let output = run_on(
+            &mut engine,
+            &[
+                (LAPTOP, KEY_CAPSLOCK, true),
+                (EXTERNAL, KEY_H, true),
+                (LAPTOP, KEY_CAPSLOCK, false),
+                (LAPTOP, KEY_H, true),
+                (EXTERNAL, KEY_H, false),
+                (LAPTOP, KEY_H, false),
+            ],
+        );
+        assert_eq!(
+            output,
+            [
+                Out::press(KEY_LEFT),
+                Out::press(KEY_H),
+                Out::release(KEY_LEFT),
+                Out::release(KEY_H),
+            ]
+        );
+    }
+
+    /// 外接键盘绕过映射、编号 3 的键盘使用 qmk 映射
+    fn engine_with_devices() -> Engine {
+        engine_from(
+            r#"
+[bindings]
+H = "Left"
+
+[profiles.qmk]
+H = "Home"
+
+[[devices]]
+hardware_id = "vid_1050"
+bypass = true
+
+[[devices]]
+index = 3
+profile = "qmk"
+"#,
+        )
+        .0
+    }
+
+    #[test]
+    fn bypassed_device_passes_through_unchanged() {
+        let mut engine = engine_with_devices();
+        engine.attach(EXTERNAL, Some("HID\\VID_1050&PID_0407"));
+        let script = [
+            (EXTERNAL, KEY_CAPSLOCK, true),
+            (EXTERNAL, KEY_H, true),
+            (EXTERNAL, KEY_H, false),
+            (EXTERNAL, KEY_CAPSLOCK, false),
+        ];
+        let expected: Vec<_> = script
+            .iter()
+            .map(|&(_, code, down)| Out { code, down })
+            .collect();
+        assert_eq!(run_on(&mut engine, &script), expected);
+    }
+
+    #[test]
+    fn bypassed_device_does_not_affect_shared_layer() {
+        let mut engine = engine_with_devices().with_shared_layers(true);
+        engine.attach(EXTERNAL, Some("HID\\VID_1050&PID_0407"));
+        let output = run_on(
+            &mut engine,
+            &[(EXTERNAL, KEY_CAPSLOCK, true), (LAPTOP, KEY_H, true)],
+        );
+        assert_eq!(output, [Out::press(KEY_CAPSLOCK), Out::press(KEY_H)]);
+    }
+
+    #[test]
+    fn devices_use_matching_profile_or_default() {
+        let mut engine = engine_with_devices();
+        engine.attach(LAPTOP, Some("ACPI\\PNP0303"));
+        let output = run_on(
+            &mut engine,
+            &[
+                (3, KEY_CAPSLOCK, true),
+                (3, KEY_H, true),
+                (LAPTOP, KEY_CAPSLOCK, true),
+                (LAPTOP, KEY_H, true),
+            ],
+        );
+        assert_eq!(output, [Out::press(KEY_HOME), Out::press(KEY_LEFT)]);
+        assert!(engine.is_attached(3));
+    }
+
+    const TAPPING_TERM: Duration = Duration::from_millis(200);
+
+    #[test]
+    fn caps_tap_sends_escape() {
+        let (mut engine, _) = engine_from(
+            r#"
+[bindings]
+H = "Left"
+
+[tap_hold.CapsLock]
+tap = "Esc"
+"#,
+        );
+        let output = run(&mut engine, &[(KEY_CAPSLOCK, true), (KEY_CAPSLOCK, false)]);
+        assert_eq!(output, [Out::press(KEY_ESC), Out::release(KEY_ESC)]);
+    }
+
+    #[test]
+    fn caps_tap_keeps_held_modifiers() {
+        let (mut engine, _) = engine_from(
+            r#"
+[bindings]
+H = "Left"
+
+[tap_hold.CapsLock]
+tap = "Esc"
+"#,
+        );
+        assert_eq!(
+            press(&mut engine, KEY_LEFTSHIFT),
+            [Out::press(KEY_LEFTSHIFT)]
+        );
+        // Shift+CapsLock 轻触输出 Shift+Esc
+        let output = run(&mut engine, &[(KEY_CAPSLOCK, true), (KEY_CAPSLOCK, false)]);
+        assert_eq!(output, [Out::press(KEY_ESC), Out::release(KEY_ESC)]);
+        assert_eq!(
+            release(&mut engine, KEY_LEFTSHIFT),
+            [Out::release(KEY_LEFTSHIFT)]
+        );
+    }
+
+    #[test]
+    fn caps_held_past_tapping_term_activates_layer() {
+        let (mut engine, clock) = engine_from(
+            r#"
+[bindings]
+H = "Left"
+
+[tap_hold.CapsLock]
+tap = "Esc"
+"#,
+        );
+        press(&mut engine, KEY_CAPSLOCK);
+        clock.advance(TAPPING_TERM);
+        assert_eq!(press(&mut engine, KEY_H), [Out::press(KEY_LEFT)]);
+        assert!(release(&mut engine, KEY_CAPSLOCK).is_empty());
+        assert_eq!(release(&mut engine, KEY_H), [Out::release(KEY_LEFT)]);
+    }
+
+    #[test]
+    fn keys_during_tapping_term_wait_for_the_decision() {
+        let (mut engine, clock) = engine_from(
+            r#"
+[bindings]
+H = "Left"
+
+[tap_hold.CapsLock]
+tap = "Esc"
+"#,
+        );
+        press(&mut engine, KEY_CAPSLOCK);
+        assert!(press(&mut engine, KEY_H).is_empty());
+        clock.advance(TAPPING_TERM / 2);
+        assert!(engine.tick().is_empty());
+        clock.advance(TAPPING_TERM / 2);
+        assert_eq!(engine.tick(), [(DEVICE, vec![Out::press(KEY_LEFT)])]);
+        assert!(engine.tick().is_empty());
+    }
+
+    #[test]
+    fn caps_released_within_tapping_term_is_a_tap_before_buffered_keys() {
+        let (mut engine, _) = engine_from(
+            r#"
+[bindings]
+H = "Left"
+
+[tap_hold.CapsLock]
+tap = "Esc"
+"#,
+        );
+        let output = run(
+            &mut engine,
+            &[
+                (KEY_CAPSLOCK, true),
+                (KEY_H, true),
+                (KEY_H, false),
+                (KEY_CAPSLOCK, false),
+            ],
+        );
+        assert_eq!(
+            output,
+            [
+                Out::press(KEY_ESC),
+                Out::release(KEY_ESC),
+                Out::press(KEY_H),
+                Out::release(KEY_H),
+            ]
+        );
+    }
+
+    #[test]
+    fn hold_on_other_key_press_decides_immediately() {
+        let (mut engine, _) = engine_from(
+            r#"
+[bindings]
+H = "Left"
+
+[tap_hold.CapsLock]
+tap = "Esc"
+hold_on_other_key_press = true
+"#,
+        );
+        press(&mut engine, KEY_CAPSLOCK);
+        assert_eq!(press(&mut engine, KEY_H), [Out::press(KEY_LEFT)]);
+        assert!(release(&mut engine, KEY_CAPSLOCK).is_empty());
+    }
+
+    #[test]
+    fn permissive_hold_decides_on_nested_tap() {
+        let (mut engine, _) = engine_from(
+            r#"
+[bindings]
+H = "Left"
+
+[tap_hold.CapsLock]
+tap = "Esc"
+permissive_hold = true
+"#,
+        );
+        press(&mut engine, KEY_CAPSLOCK);
+        assert!(press(&mut engine, KEY_H).is_empty());
+        assert_eq!(
+            release(&mut engine, KEY_H),
+            [Out::press(KEY_LEFT), Out::release(KEY_LEFT)]
+        );
+        assert!(release(&mut engine, KEY_CAPSLOCK).is_empty());
+
+        // 层键先抬起仍是轻触
+        let output = run(
+            &mut engine,
+            &[
+                (KEY_CAPSLOCK, true),
+                (KEY_H, true),
+                (KEY_CAPSLOCK, false),
+                (KEY_H, false),
+            ],
+        );
+        assert_eq!(
+            output,
+            [
+                Out::press(KEY_ESC),
+                Out::release(KEY_ESC),
+                Out::press(KEY_H),
+                Out::release(KEY_H),
+            ]
+        );
+    }
+
+    #[test]
+    fn retro_tap_sends_tap_after_unused_hold() {
+        let (mut engine, clock) = engine_from(
+            r#"
+[bindings]
+H = "Left"
+
+[tap_hold.CapsLock]
+tap = "Esc"
+retro_tap = true
+"#,
+        );
+        press(&mut engine, KEY_CAPSLOCK);
+        clock.advance(TAPPING_TERM * 2);
+        assert_eq!(
+            release(&mut engine, KEY_CAPSLOCK),
+            [Out::press(KEY_ESC), Out::release(KEY_ESC)]
+        );
+
+        // 按住期间用过层则不输出
+        press(&mut engine, KEY_CAPSLOCK);
+        clock.advance(TAPPING_TERM * 2);
+        run(&mut engine, &[(KEY_H, true), (KEY_H, false)]);
+        assert!(release(&mut engine, KEY_CAPSLOCK).is_empty());
+
+        // 没有 retro_tap 时超时后抬起不输出
+        let (mut engine, clock) = engine_from(
+            r#"
+[bindings]
+H = "Left"
+
+[tap_hold.CapsLock]
+tap = "Esc"
+"#,
+        );
+        press(&mut engine, KEY_CAPSLOCK);
+        clock.advance(TAPPING_TERM * 2);
+        assert!(release(&mut engine, KEY_CAPSLOCK).is_empty());
+    }
+
+    #[test]
+    fn key_held_before_caps_releases_immediately() {
+        let (mut engine, _) = engine_from(
+            r#"
+[bindings]
+H = "Left"
+
+[tap_hold.CapsLock]
+tap = "Esc"
+"#,
+        );
+        assert_eq!(press(&mut engine, KEY_H), [Out::press(KEY_H)]);
+        press(&mut engine, KEY_CAPSLOCK);
+        assert_eq!(release(&mut engine, KEY_H), [Out::release(KEY_H)]);
+    }
+
+    #[test]
+    fn tapping_term_is_measured_by_event_time() {
+        let (mut engine, clock) = engine_from(
+            r#"
+[bindings]
+H = "Left"
+
+[tap_hold.CapsLock]
+tap = "Esc"
+"#,
+        );
+        let start = clock.now();
+        let caps = |down, timestamp| KeyEvent {
+            code: KEY_CAPSLOCK,
+            down,
+            device: DEVICE,
+            timestamp,
+        };
+        // 事件处理得晚：按事件时间 CapsLock 在判定时间内抬起，仍是轻触
+        clock.advance(TAPPING_TERM * 2);
+        assert!(engine.process(caps(true, start)).is_empty());
+        assert_eq!(
+            engine.process(caps(false, start + TAPPING_TERM / 2)),
+            [Out::press(KEY_ESC), Out::release(KEY_ESC)]
+        );
+        // 晚于当前时间的事件按当前时间处理
+        assert!(engine.process(caps(true, clock.now())).is_empty());
+        clock.advance(TAPPING_TERM / 2);
+        let late = clock.now() + TAPPING_TERM * 10;
+        assert_eq!(
+            engine.process(caps(false, late)),
+            [Out::press(KEY_ESC), Out::release(KEY_ESC)]
+        );
+    }
+
+    /// caps 层之外：按住 RightAlt 激活、拦下未映射键的 symbols 层，
+    /// Tab 切换、未映射键交给下一层的 numbers 层，F12 切换基础层的 colemak 层
+    fn layered_engine(extra: &str) -> Engine {
+        let src = format!(
+            r#"
+[bindings]
+H = "Left"
+J = "Up"
+
+[layers.symbols]
+key = "RightAlt"
+fallthrough = "block"
+
+[layers.symbols.bindings]
+Q = "Shift+1"
+
+[layers.numbers]
+key = "Tab"
+mode = "toggle"
+fallthrough = "defer"
+
+[layers.numbers.bindings]
+J = "4"
+U = "7"
+
+[layers.colemak]
+key = "F12"
+mode = "to"
+
+[layers.colemak.bindings]
+K = "E"
+{extra}
+"#
+        );
+        engine_from(&src).0
+    }
+
+    /// 轻触一个键的输出
+    fn tap(engine: &mut Engine, code: OsCode) -> Vec<OutputEvent> {
+        run(engine, &[(code, true), (code, false)])
+    }
+
+    #[test]
+    fn any_key_can_hold_a_layer() {
+        let mut engine = layered_engine("");
+        press(&mut engine, KEY_RIGHTALT);
+        assert_eq!(
+            tap(&mut engine, KEY_Q),
+            [
+                Out::press(KEY_LEFTSHIFT),
+                Out::press(KEY_1),
+                Out::release(KEY_1),
+                Out::release(KEY_LEFTSHIFT),
+            ]
+        );
+        assert!(release(&mut engine, KEY_RIGHTALT).is_empty());
+        assert_eq!(
+            tap(&mut engine, KEY_Q),
+            [Out::press(KEY_Q), Out::release(KEY_Q)]
+        );
+    }
+
+    #[test]
+    fn block_drops_unmapped_keys_except_modifiers() {
+        let mut engine = layered_engine("");
+        press(&mut engine, KEY_RIGHTALT);
+        assert!(tap(&mut engine, KEY_W).is_empty());
+        assert_eq!(
+            press(&mut engine, KEY_LEFTSHIFT),
+            [Out::press(KEY_LEFTSHIFT)]
+        );
+        // 层抬起后才抬起的键也不会输出
+        press(&mut engine, KEY_W);
+        release(&mut engine, KEY_RIGHTALT);
+        assert!(release(&mut engine, KEY_W).is_empty());
+    }
+
+    #[test]
+    fn toggle_layer_stays_until_toggled_again() {
+        let mut engine = layered_engine("");
+        // 自动重复不会再次切换
+        assert!(
+            run(
+                &mut engine,
+                &[(KEY_TAB, true), (KEY_TAB, true), (KEY_TAB, false)]
+            )
+            .is_empty()
+        );
+        assert_eq!(
+            tap(&mut engine, KEY_J),
+            [Out::press(KEY_4), Out::release(KEY_4)]
+        );
+        // 下面没有层，未映射的键原样输出
+        assert_eq!(
+            tap(&mut engine, KEY_K),
+            [Out::press(KEY_K), Out::release(KEY_K)]
+        );
+        tap(&mut engine, KEY_TAB);
+        assert_eq!(
+            tap(&mut engine, KEY_J),
+            [Out::press(KEY_J), Out::release(KEY_J)]
+        );
+    }
+
+    #[test]
+    fn later_layers_take_priority() {
+        let mut engine = layered_engine("");
+        tap(&mut engine, KEY_TAB);
+        press(&mut engine, KEY_CAPSLOCK);
+        // caps 层在上面，未映射的键原样输出，不会交给下面的 numbers 层
+        assert_eq!(press(&mut engine, KEY_J), [Out::press(KEY_UP)]);
+        assert_eq!(press(&mut engine, KEY_U), [Out::press(KEY_U)]);
+        run(&mut engine, &[(KEY_J, false), (KEY_U, false)]);
+        release(&mut engine, KEY_CAPSLOCK);
+        assert_eq!(press(&mut engine, KEY_J), [Out::press(KEY_4)]);
+    }
+
+    #[test]
+    fn defer_falls_through_to_the_layer_below() {
+        let mut engine = layered_engine("");
+        press(&mut engine, KEY_CAPSLOCK);
+        tap(&mut engine, KEY_TAB);
+        assert_eq!(press(&mut engine, KEY_J), [Out::press(KEY_4)]);
+        assert_eq!(press(&mut engine, KEY_H), [Out::press(KEY_LEFT)]);
+        assert_eq!(press(&mut engine, KEY_G), [Out::press(KEY_G)]);
+    }
+
+    #[test]
+    fn to_switches_the_base_layer() {
+        let mut engine = layered_engine("");
+        tap(&mut engine, KEY_F12);
+        assert_eq!(
+            tap(&mut engine, KEY_K),
+            [Out::press(KEY_E), Out::release(KEY_E)]
+        );
+        // 基础层在激活的层之下
+        press(&mut engine, KEY_CAPSLOCK);
+        assert_eq!(
+            tap(&mut engine, KEY_H),
+            [Out::press(KEY_LEFT), Out::release(KEY_LEFT)]
+        );
+        assert_eq!(
+            tap(&mut engine, KEY_K),
+            [Out::press(KEY_K), Out::release(KEY_K)]
+        );
+        release(&mut engine, KEY_CAPSLOCK);
+        assert_eq!(
+            tap(&mut engine, KEY_K),
+            [Out::press(KEY_E), Out::release(KEY_E)]
+        );
+        tap(&mut engine, KEY_F12);
+        assert_eq!(
+            tap(&mut engine, KEY_K),
+            [Out::press(KEY_K), Out::release(KEY_K)]
+        );
+    }
+
+    #[test]
+    fn tap_hold_works_on_any_momentary_layer_key() {
+        let mut engine = layered_engine("[tap_hold.RightAlt]\ntap = \"RightAlt\"");
+        assert_eq!(
+            tap(&mut engine, KEY_RIGHTALT),
+            [Out::press(KEY_RIGHTALT), Out::release(KEY_RIGHTALT)]
+        );
+        let output = run(
+            &mut engine,
+            &[
+                (KEY_RIGHTALT, true),
+                (KEY_Q, true),
+                (KEY_Q, false),
+                (KEY_RIGHTALT, false),
+            ],
+        );
+        assert_eq!(
+            output,
+            [
+                Out::press(KEY_RIGHTALT),
+                Out::release(KEY_RIGHTALT),
+                Out::press(KEY_Q),
+                Out::release(KEY_Q),
+            ]
+        );
+    }
+
+    #[test]
+    fn override_replaces_output_and_lifts_modifiers() {
+        let (mut engine, _) = engine_from(
+            r#"
+[[overrides]]
+trigger = "Shift+Backspace"
+output = "Delete"
+"#,
+        );
+        press(&mut engine, KEY_LEFTSHIFT);
+        assert_eq!(
+            press(&mut engine, KEY_BACKSPACE),
+            [Out::release(KEY_LEFTSHIFT), Out::press(KEY_DELETE)]
+        );
+        assert_eq!(
+            release(&mut engine, KEY_BACKSPACE),
+            [Out::release(KEY_DELETE), Out::press(KEY_LEFTSHIFT)]
+        );
+        // 没有按住 Shift 时不生效
+        release(&mut engine, KEY_LEFTSHIFT);
+        assert_eq!(
+            tap(&mut engine, KEY_BACKSPACE),
+            [Out::press(KEY_BACKSPACE), Out::release(KEY_BACKSPACE)]
+        );
+    }
+
+    #[test]
+    fn override_survives_modifier_released_before_key() {
+        let (mut engine, _) = engine_from(
+            r#"
+[[overrides]]
+trigger = "Shift+Backspace"
+output = "Delete"
+"#,
+        );
+        run(
+            &mut engine,
+            &[(KEY_RIGHTSHIFT, true), (KEY_BACKSPACE, true)],
+        );
+        // Shift 已暂时抬起，物理抬起时不再发送
+        assert!(release(&mut engine, KEY_RIGHTSHIFT).is_empty());
+        // 自动重复沿用按下时的输出，抬起时释放的也是 Delete
+        assert_eq!(press(&mut engine, KEY_BACKSPACE), [Out::press(KEY_DELETE)]);
+        assert_eq!(
+            release(&mut engine, KEY_BACKSPACE),
+            [Out::release(KEY_DELETE)]
+        );
+        assert!(!engine.devices[&DEVICE].modifiers.is_lifted(KEY_RIGHTSHIFT));
+    }
+
+    #[test]
+    fn override_without_output_blocks_the_key() {
+        let (mut engine, _) = engine_from(
+            r#"
+[[overrides]]
+trigger = "Ctrl+Shift+Q"
+"#,
+        );
+        let output = run(
+            &mut engine,
+            &[
+                (KEY_LEFTCTRL, true),
+                (KEY_LEFTSHIFT, true),
+                (KEY_Q, true),
+                (KEY_LEFTSHIFT, false),
+                (KEY_Q, false),
+            ],
+        );
+        assert_eq!(
+            output,
+            [
+                Out::press(KEY_LEFTCTRL),
+                Out::press(KEY_LEFTSHIFT),
+                Out::release(KEY_LEFTSHIFT),
+            ]
+        );
+        // 只按住 Ctrl 时照常输出
+        assert_eq!(
+            tap(&mut engine, KEY_Q),
+            [Out::press(KEY_Q), Out::release(KEY_Q)]
+        );
+    }
+
+    #[test]
+    fn layer_bindings_take_priority_over_overrides() {
+        let (mut engine, _) = engine_from(
+            r#"
+[bindings]
+H = "Left"
+
+[[overrides]]
+trigger = "Shift+H"
+output = "End"
+"#,
+        );
+        run(&mut engine, &[(KEY_LEFTSHIFT, true), (KEY_CAPSLOCK, true)]);
+        assert_eq!(press(&mut engine, KEY_H), [Out::press(KEY_LEFT)]);
+        release(&mut engine, KEY_H);
+        release(&mut engine, KEY_CAPSLOCK);
+        assert_eq!(
+            press(&mut engine, KEY_H),
+            [Out::release(KEY_LEFTSHIFT), Out::press(KEY_END)]
+        );
+    }
+
+    const ONE_SHOT_TIMEOUT: Duration = Duration::from_millis(1000);
+
+    /// 在 CapsLock 层中轻触一次性修饰键或层的触发键
+    fn trigger_one_shot(engine: &mut Engine, code: OsCode) -> Vec<OutputEvent> {
+        run(
+            engine,
+            &[
+                (KEY_CAPSLOCK, true),
+                (code, true),
+                (code, false),
+                (KEY_CAPSLOCK, false),
+            ],
+        )
+    }
+
+    #[test]
+    fn one_shot_modifier_applies_to_the_next_key_only() {
+        let (mut engine, _) = engine_from(
+            r#"
+[bindings]
+O = { one_shot = "Ctrl" }
+"#,
+        );
+        // 触发键的自动重复不会锁定
+        let output = run(
+            &mut engine,
+            &[
+                (KEY_CAPSLOCK, true),
+                (KEY_O, true),
+                (KEY_O, true),
+                (KEY_O, false),
+                (KEY_CAPSLOCK, false),
+            ],
+        );
+        assert!(output.is_empty());
+        assert_eq!(
+            tap(&mut engine, KEY_S),
+            [
+                Out::press(KEY_LEFTCTRL),
+                Out::press(KEY_S),
+                Out::release(KEY_S),
+                Out::release(KEY_LEFTCTRL),
+            ]
+        );
+        assert_eq!(
+            tap(&mut engine, KEY_S),
+            [Out::press(KEY_S), Out::release(KEY_S)]
+        );
+    }
+
+    #[test]
+    fn one_shot_modifiers_stack_and_combine_with_layer_outputs() {
+        let (mut engine, _) = engine_from(
+            r#"
+[bindings]
+H = "Left"
+O = { one_shot = "Ctrl" }
+P = { one_shot = "Shift" }
+"#,
+        );
+        let output = run(
+            &mut engine,
+            &[
+                (KEY_CAPSLOCK, true),
+                (KEY_O, true),
+                (KEY_O, false),
+                (KEY_P, true),
+                (KEY_P, false),
+                (KEY_H, true),
+                (KEY_H, false),
+            ],
+        );
+        assert_eq!(
+            output,
+            [
+                Out::press(KEY_LEFTCTRL),
+                Out::press(KEY_LEFTSHIFT),
+                Out::press(KEY_LEFT),
+                Out::release(KEY_LEFT),
+                Out::release(KEY_LEFTCTRL),
+                Out::release(KEY_LEFTSHIFT),
+            ]
+        );
+    }
+
+    #[test]
+    fn physical_modifiers_do_not_use_up_one_shot() {
+        let (mut engine, _) = engine_from(
+            r#"
+[bindings]
+O = { one_shot = "Ctrl" }
+"#,
+        );
+        trigger_one_shot(&mut engine, KEY_O);
+        assert_eq!(
+            press(&mut engine, KEY_LEFTSHIFT),
+            [Out::press(KEY_LEFTSHIFT)]
+        );
+        assert_eq!(
+            press(&mut engine, KEY_S),
+            [Out::press(KEY_LEFTCTRL), Out::press(KEY_S)]
+        );
+        assert_eq!(
+            release(&mut engine, KEY_S),
+            [Out::release(KEY_S), Out::release(KEY_LEFTCTRL)]
+        );
+    }
+
+    #[test]
+    fn one_shot_expires_after_timeout() {
+        let (mut engine, clock) = engine_from(
+            r#"
+[bindings]
+O = { one_shot = "Ctrl" }
+
+[one_shot]
+timeout = 1000
+"#,
+        );
+        trigger_one_shot(&mut engine, KEY_O);
+        clock.advance(ONE_SHOT_TIMEOUT);
+        assert_eq!(
+            tap(&mut engine, KEY_S),
+            [Out::press(KEY_S), Out::release(KEY_S)]
+        );
+    }
+
+    #[test]
+    fn escape_cancels_one_shot() {
+        let (mut engine, _) = engine_from(
+            r#"
+[bindings]
+O = { one_shot = "Ctrl" }
+"#,
+        );
+        trigger_one_shot(&mut engine, KEY_O);
+        assert!(tap(&mut engine, KEY_ESC).is_empty());
+        assert_eq!(
+            tap(&mut engine, KEY_S),
+            [Out::press(KEY_S), Out::release(KEY_S)]
+        );
+        // 没有一次性修饰键时 Esc 照常输出
+        assert_eq!(
+            tap(&mut engine, KEY_ESC),
+            [Out::press(KEY_ESC), Out::release(KEY_ESC)]
+        );
+    }
+
+    #[test]
+    fn double_tap_locks_one_shot() {
+        let (mut engine, clock) = engine_from(
+            r#"
+[bindings]
+O = { one_shot = "Ctrl" }
+
+[one_shot]
+timeout = 1000
+"#,
+        );
+        trigger_one_shot(&mut engine, KEY_O);
+        trigger_one_shot(&mut engine, KEY_O);
+        let ctrl_tap = |code| {
+            [
+                Out::press(KEY_LEFTCTRL),
+                Out::press(code),
+                Out::release(code),
+                Out::release(KEY_LEFTCTRL),
+            ]
+        };
+        assert_eq!(tap(&mut engine, KEY_S), ctrl_tap(KEY_S));
+        // 锁定后不会超时
+        clock.advance(ONE_SHOT_TIMEOUT * 2);
+        assert_eq!(tap(&mut engine, KEY_A), ctrl_tap(KEY_A));
+        // 再触发一次解除锁定
+        trigger_one_shot(&mut engine, KEY_O);
+        assert_eq!(
+            tap(&mut engine, KEY_S),
+            [Out::press(KEY_S), Out::release(KEY_S)]
+        );
+
+        // Esc 同样解除锁定
+        trigger_one_shot(&mut engine, KEY_O);
+        trigger_one_shot(&mut engine, KEY_O);
+        assert!(tap(&mut engine, KEY_ESC).is_empty());
+        assert_eq!(
+            tap(&mut engine, KEY_S),
+            [Out::press(KEY_S), Out::release(KEY_S)]
+        );
+    }
+
+    #[test]
+    fn one_shot_layer_applies_to_one_key() {
+        let (mut engine, _) = engine_from(
+            r#"
+[bindings]
+Y = { one_shot_layer = "symbols" }
+
+[layers.symbols]
+key = "RightAlt"
+
+[layers.symbols.bindings]
+Q = "Shift+1"
+"#,
+        );
+        trigger_one_shot(&mut engine, KEY_Y);
+        assert_eq!(
+            tap(&mut engine, KEY_Q),
+            [
+                Out::press(KEY_LEFTSHIFT),
+                Out::press(KEY_1),
+                Out::release(KEY_1),
+                Out::release(KEY_LEFTSHIFT),
+            ]
+        );
+        assert_eq!(
+            tap(&mut engine, KEY_Q),
+            [Out::press(KEY_Q), Out::release(KEY_Q)]
+        );
+    }
+
+    const COMBO_TERM: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn combo_outputs_when_keys_are_pressed_together() {
+        let (mut engine, _) = engine_from(
+            r#"
+[[combos]]
+keys = ["J", "K"]
+output = "Esc"
+"#,
+        );
+        assert!(press(&mut engine, KEY_J).is_empty());
+        assert_eq!(
+            press(&mut engine, KEY_K),
+            [Out::press(KEY_ESC), Out::release(KEY_ESC)]
+        );
+        // 之后的自动重复与抬起都不输出
+        let output = run(
+            &mut engine,
+            &[(KEY_K, true), (KEY_J, false), (KEY_K, false)],
+        );
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn keys_outside_combos_are_not_delayed() {
+        let (mut engine, _) = engine_from(
+            r#"
+[[combos]]
+keys = ["J", "K"]
+output = "Esc"
+"#,
+        );
+        assert_eq!(press(&mut engine, KEY_A), [Out::press(KEY_A)]);
+        assert_eq!(release(&mut engine, KEY_A), [Out::release(KEY_A)]);
+    }
+
+    #[test]
+    fn combo_term_is_measured_by_event_time() {
+        let (mut engine, clock) = engine_from(
+            r#"
+[[combos]]
+keys = ["J", "K"]
+output = "Esc"
+"#,
+        );
+        let start = clock.now();
+        let key = |code, timestamp| KeyEvent {
+            code,
+            down: true,
+            device: DEVICE,
+            timestamp,
+        };
+        // 两个键按事件时间相隔超过判定时间，不组成组合
+        clock.advance(COMBO_TERM * 2);
+        assert!(engine.process(key(KEY_J, start)).is_empty());
+        assert_eq!(
+            engine.process(key(KEY_K, start + COMBO_TERM)),
+            [Out::press(KEY_J)]
+        );
+    }
+
+    #[test]
+    fn lone_combo_key_is_sent_when_term_expires() {
+        let (mut engine, clock) = engine_from(
+            r#"
+[[combos]]
+keys = ["J", "K"]
+output = "Esc"
+"#,
+        );
+        assert!(press(&mut engine, KEY_J).is_empty());
+        clock.advance(COMBO_TERM - Duration::from_millis(1));
+        assert!(engine.tick().is_empty());
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(engine.tick(), [(DEVICE, vec![Out::press(KEY_J)])]);
+        // J 已经输出，再按下的 K 单独开始判定，抬起时原样输出
+        assert!(press(&mut engine, KEY_K).is_empty());
+        assert_eq!(
+            release(&mut engine, KEY_K),
+            [Out::press(KEY_K), Out::release(KEY_K)]
+        );
+    }
+
+    #[test]
+    fn interrupted_combo_is_flushed_in_order() {
+        let src = r#"
+[[combos]]
+keys = ["J", "K"]
+output = "Esc"
+
+[[combos]]
+keys = ["S", "D"]
+output = "Ctrl+S"
+
+[[combos]]
+keys = ["S", "D", "F"]
+output = "Ctrl+Shift+S"
+"#;
+        let (mut engine, _) = engine_from(src);
+        let output = run(&mut engine, &[(KEY_J, true), (KEY_A, true)]);
+        assert_eq!(output, [Out::press(KEY_J), Out::press(KEY_A)]);
+
+        // 组合中的键抬起同样结束判定
+        let (mut engine, _) = engine_from(src);
+        let output = run(&mut engine, &[(KEY_J, true), (KEY_J, false)]);
+        assert_eq!(output, [Out::press(KEY_J), Out::release(KEY_J)]);
+
+        // 属于另一个组合的键：先处理暂存的键，再开始新的判定
+        let (mut engine, _) = engine_from(src);
+        assert_eq!(press(&mut engine, KEY_J), []);
+        assert_eq!(press(&mut engine, KEY_S), [Out::press(KEY_J)]);
+        assert_eq!(
+            press(&mut engine, KEY_D),
+            [],
+            "S+D 还可能组成 S+D+F，继续等待"
+        );
+    }
+
+    #[test]
+    fn longer_combo_waits_for_more_keys() {
+        let (mut engine, clock) = engine_from(
+            r#"
+[[combos]]
+keys = ["S", "D"]
+output = "Ctrl+S"
+
+[[combos]]
+keys = ["S", "D", "F"]
+output = "Ctrl+Shift+S"
+"#,
+        );
+        let output = run(&mut engine, &[(KEY_S, true), (KEY_D, true), (KEY_F, true)]);
+        assert_eq!(
+            output,
+            [
+                Out::press(KEY_LEFTCTRL),
+                Out::press(KEY_LEFTSHIFT),
+                Out::press(KEY_S),
+                Out::release(KEY_S),
+                Out::release(KEY_LEFTCTRL),
+                Out::release(KEY_LEFTSHIFT),
+            ]
+        );
+        run(
+            &mut engine,
+            &[(KEY_S, false), (KEY_D, false), (KEY_F, false)],
+        );
+
+        // 判定时间内没有按下 F，输出 S+D 的组合
+        run(&mut engine, &[(KEY_S, true), (KEY_D, true)]);
+        clock.advance(COMBO_TERM);
+        assert_eq!(
+            engine.tick(),
+            [(
+                DEVICE,
+                vec![
+                    Out::press(KEY_LEFTCTRL),
+                    Out::press(KEY_S),
+                    Out::release(KEY_S),
+                    Out::release(KEY_LEFTCTRL),
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn combos_are_per_layer() {
+        let (mut engine, _) = engine_from(
+            r#"
+[bindings]
+J = "Up"
+
+[[combos]]
+keys = ["J", "K"]
+output = "Esc"
+
+[[combos]]
+keys = ["S", "D"]
+output = "Ctrl+S"
+
+[[combos]]
+keys = ["J", "K"]
+layer = "caps"
+output = "Enter"
+"#,
+        );
+        let output = run(
+            &mut engine,
+            &[(KEY_CAPSLOCK, true), (KEY_J, true), (KEY_K, true)],
+        );
+        assert_eq!(output, [Out::press(KEY_ENTER), Out::release(KEY_ENTER)]);
+        // 没有写层的组合在层生效时不触发，S 立即输出
+        assert_eq!(press(&mut engine, KEY_S), [Out::press(KEY_S)]);
+    }
+
+    const SEQUENCE_TIMEOUT: Duration = Duration::from_millis(500);
+
+    #[test]
+    fn sequence_erases_typed_keys_with_backspace() {
+        let (mut engine, _) = engine_from(
+            r#"
+[sequences]
+rollback = "backspace"
+
+[sequences.bindings]
+jk = "Esc"
+"#,
+        );
+        assert_eq!(
+            run(&mut engine, &[(KEY_J, true), (KEY_J, false)]),
+            [Out::press(KEY_J), Out::release(KEY_J)]
+        );
+        assert_eq!(
+            press(&mut engine, KEY_K),
+            [
+                Out::press(KEY_BACKSPACE),
+                Out::release(KEY_BACKSPACE),
+                Out::press(KEY_ESC),
+                Out::release(KEY_ESC),
+            ]
+        );
+        // 触发序列的键的抬起不输出
+        assert!(release(&mut engine, KEY_K).is_empty());
+    }
+
+    #[test]
+    fn key_repeat_aborts_backspace_sequence() {
+        let (mut engine, _) = engine_from(
+            r#"
+[sequences]
+rollback = "backspace"
+
+[sequences.bindings]
+jk = "Esc"
+"#,
+        );
+        assert_eq!(
+            run(&mut engine, &[(KEY_J, true), (KEY_J, true), (KEY_J, false)]),
+            [Out::press(KEY_J), Out::press(KEY_J), Out::release(KEY_J)]
+        );
+        // 已输入了两个 j，不再删除字符输出序列
+        assert_eq!(press(&mut engine, KEY_K), [Out::press(KEY_K)]);
+    }
+
+    #[test]
+    fn sequence_holds_back_typed_keys() {
+        let (mut engine, _) = engine_from(
+            r#"
+[sequences]
+rollback = "hold"
+
+[sequences.bindings]
+jk = "Esc"
+"#,
+        );
+        assert!(press(&mut engine, KEY_J).is_empty());
+        assert_eq!(
+            press(&mut engine, KEY_K),
+            [Out::press(KEY_ESC), Out::release(KEY_ESC)]
+        );
+        // 仍按住的 J 之后的抬起同样不输出
+        assert!(run(&mut engine, &[(KEY_J, false), (KEY_K, false)]).is_empty());
+    }
+
+    #[test]
+    fn mismatched_sequence_is_flushed_in_order() {
+        let src = r#"
+[sequences]
+rollback = "hold"
+
+[sequences.bindings]
+jk = "Esc"
+gg = "Home"
+"#;
+        let (mut engine, _) = engine_from(src);
+        let output = run(&mut engine, &[(KEY_J, true), (KEY_J, false), (KEY_A, true)]);
+        assert_eq!(
+            output,
+            [Out::press(KEY_J), Out::release(KEY_J), Out::press(KEY_A)]
+        );
+
+        // 走不通的键从头重新匹配：J G G 中的 G G 组成序列
+        let (mut engine, _) = engine_from(src);
+        let output = run(
+            &mut engine,
+            &[(KEY_J, true), (KEY_J, false), (KEY_G, true), (KEY_G, false)],
+        );
+        assert_eq!(output, [Out::press(KEY_J), Out::release(KEY_J)]);
+        assert_eq!(
+            press(&mut engine, KEY_G),
+            [Out::press(KEY_HOME), Out::release(KEY_HOME)]
+        );
+    }
+
+    #[test]
+    fn sequence_times_out() {
+        let (mut engine, clock) = engine_from(
+            r#"
+[sequences]
+rollback = "hold"
+
+[sequences.bindings]
+jk = "Esc"
+"#,
+        );
+        run(&mut engine, &[(KEY_J, true), (KEY_J, false)]);
+        clock.advance(SEQUENCE_TIMEOUT - Duration::from_millis(1));
+        assert!(engine.tick().is_empty());
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(
+            engine.tick(),
+            [(DEVICE, vec![Out::press(KEY_J), Out::release(KEY_J)])]
+        );
+        assert_eq!(press(&mut engine, KEY_K), [Out::press(KEY_K)]);
+
+        // 退格模式下超时后重新开始匹配
+        let (mut engine, clock) = engine_from(
+            r#"
+[sequences]
+rollback = "backspace"
+
+[sequences.bindings]
+jk = "Esc"
+"#,
+        );
+        run(&mut engine, &[(KEY_J, true), (KEY_J, false)]);
+        clock.advance(SEQUENCE_TIMEOUT);
+        assert_eq!(press(&mut engine, KEY_K), [Out::press(KEY_K)]);
+    }
+
+    #[test]
+    fn sequences_are_disabled_in_listed_apps() {
+        let app = Arc::new(std::sync::Mutex::new("keepass.exe".to_string()));
+        let query = Arc::clone(&app);
+        let (engine, _) = engine_from(
+            r#"
+[sequences]
+rollback = "hold"
+disabled_apps = ["KeePass.exe"]
+
+[sequences.bindings]
+jk = "Esc"
+"#,
+        );
+        let mut engine = engine.with_foreground_app(move || Some(query.lock().unwrap().clone()));
+        let output = run(&mut engine, &[(KEY_J, true), (KEY_K, true)]);
+        assert_eq!(output, [Out::press(KEY_J), Out::press(KEY_K)]);
+        run(&mut engine, &[(KEY_J, false), (KEY_K, false)]);
+
+        *app.lock().unwrap() = "notepad.exe".to_string();
+        let output = run(&mut engine, &[(KEY_J, true), (KEY_K, true)]);
+        assert_eq!(output, [Out::press(KEY_ESC), Out::release(KEY_ESC)]);
+    }
+
+    #[test]
+    fn sequences_need_no_layer_or_modifier() {
+        let (mut engine, _) = engine_from(
+            r#"
+[sequences]
+rollback = "hold"
+
+[sequences.bindings]
+jk = "Esc"
+"#,
+        );
+        let output = run(
+            &mut engine,
+            &[
+                (KEY_LEFTSHIFT, true),
+                (KEY_J, true),
+                (KEY_J, false),
+                (KEY_K, true),
+            ],
+        );
+        assert_eq!(
+            output,
+            [
+                Out::press(KEY_LEFTSHIFT),
+                Out::press(KEY_J),
+                Out::release(KEY_J),
+                Out::press(KEY_K),
+            ]
+        );
+    }
+
+    const LEADER_TIMEOUT: Duration = Duration::from_millis(1000);
+
+    #[test]
+    fn leader_runs_sequence_after_caps_tap() {
+        let (mut engine, _) = engine_from(
+            r#"
+[leader]
+key = "CapsLock"
+
+[leader.bindings]
+"g s" = "Ctrl+S"
+"#,
+        );
+        assert!(run(&mut engine, &[(KEY_CAPSLOCK, true), (KEY_CAPSLOCK, false)]).is_empty());
+        assert!(run(&mut engine, &[(KEY_G, true), (KEY_G, false)]).is_empty());
+        assert_eq!(
+            press(&mut engine, KEY_S),
+            [
+                Out::press(KEY_LEFTCTRL),
+                Out::press(KEY_S),
+                Out::release(KEY_S),
+                Out::release(KEY_LEFTCTRL),
+            ]
+        );
+        assert!(release(&mut engine, KEY_S).is_empty());
+        // leader 结束后照常输出
+        assert_eq!(press(&mut engine, KEY_S), [Out::press(KEY_S)]);
+    }
+
+    #[test]
+    fn leader_supports_nested_prefixes() {
+        let (mut engine, _) = engine_from(
+            r#"
+[leader]
+key = "CapsLock"
+
+[leader.bindings.w]
+q = "Alt+F4"
+"#,
+        );
+        let output = run(
+            &mut engine,
+            &[
+                (KEY_CAPSLOCK, true),
+                (KEY_CAPSLOCK, false),
+                (KEY_W, true),
+                (KEY_Q, true),
+            ],
+        );
+        assert_eq!(
+            output,
+            [
+                Out::press(KEY_LEFTALT),
+                Out::press(KEY_F4),
+                Out::release(KEY_F4),
+                Out::release(KEY_LEFTALT),
+            ]
+        );
+    }
+
+    #[test]
+    fn caps_hold_still_activates_layer_with_leader() {
+        let (mut engine, clock) = engine_from(
+            r#"
+[bindings]
+H = "Left"
+
+[leader]
+key = "CapsLock"
+
+[leader.bindings]
+"g s" = "Ctrl+S"
+"#,
+        );
+        assert!(run(&mut engine, &[(KEY_CAPSLOCK, true), (KEY_H, true)]).is_empty());
+        clock.advance(Duration::from_millis(200));
+        assert_eq!(engine.tick(), [(DEVICE, vec![Out::press(KEY_LEFT)])]);
+    }
+
+    #[test]
+    fn quick_caps_roll_uses_layer_instead_of_leader() {
+        let (mut engine, _) = engine_from(
+            r#"
+[bindings]
+H = "Left"
+
+[leader]
+key = "CapsLock"
+
+[leader.bindings]
+"g s" = "Ctrl+S"
+"#,
+        );
+        let output = run(
+            &mut engine,
+            &[
+                (KEY_CAPSLOCK, true),
+                (KEY_H, true),
+                (KEY_H, false),
+                (KEY_CAPSLOCK, false),
+            ],
+        );
+        assert_eq!(output, [Out::press(KEY_LEFT), Out::release(KEY_LEFT)]);
+        // 没有启动 leader
+        assert_eq!(press(&mut engine, KEY_G), [Out::press(KEY_G)]);
+    }
+
+    #[test]
+    fn leader_is_cancelled_by_unknown_sequence_timeout_or_esc() {
+        let (mut engine, clock) = engine_from(
+            r#"
+[leader]
+key = "CapsLock"
+
+[leader.bindings]
+"g s" = "Ctrl+S"
+"#,
+        );
+        let tap_caps = [(KEY_CAPSLOCK, true), (KEY_CAPSLOCK, false)];
+
+        // 未定义的序列：已按下的键不输出
+        run(&mut engine, &tap_caps);
+        assert!(run(&mut engine, &[(KEY_G, true), (KEY_X, true)]).is_empty());
+        assert_eq!(press(&mut engine, KEY_A), [Out::press(KEY_A)]);
+        run(
+            &mut engine,
+            &[(KEY_G, false), (KEY_X, false), (KEY_A, false)],
+        );
+
+        run(&mut engine, &tap_caps);
+        clock.advance(LEADER_TIMEOUT);
+        assert!(engine.tick().is_empty());
+        assert_eq!(press(&mut engine, KEY_G), [Out::press(KEY_G)]);
+        release(&mut engine, KEY_G);
+
+        run(&mut engine, &tap_caps);
+        assert!(run(&mut engine, &[(KEY_ESC, true), (KEY_ESC, false)]).is_empty());
+        assert_eq!(press(&mut engine, KEY_G), [Out::press(KEY_G)]);
+    }
+
+    #[test]
+    fn any_key_can_be_leader() {
+        let (mut engine, _) = engine_from(
+            r#"
+[leader]
+key = "ScrollLock"
+
+[leader.bindings]
+"g s" = "Ctrl+S"
+"#,
+        );
+        let output = run(
+            &mut engine,
+            &[
+                (KEY_SCROLLLOCK, true),
+                (KEY_SCROLLLOCK, false),
+                (KEY_G, true),
+                (KEY_S, true),
+            ],
+        );
+        assert_eq!(
+            output,
+            [
+                Out::press(KEY_LEFTCTRL),
+                Out::press(KEY_S),
+                Out::release(KEY_S),
+                Out::release(KEY_LEFTCTRL),
+            ]
+        );
+        // 修饰键在 leader 期间照常输出
+        let output = run(
+            &mut engine,
+            &[(KEY_SCROLLLOCK, true), (KEY_LEFTSHIFT, true)],
+        );
+        assert_eq!(output, [Out::press(KEY_LEFTSHIFT)]);
+    }
+
+    /// 按住 CapsLock 按下 M 触发宏，再抬起
+    fn trigger_macro(engine: &mut Engine) -> Vec<OutputEvent> {
+        run(
+            engine,
+            &[
+                (KEY_CAPSLOCK, true),
+                (KEY_M, true),
+                (KEY_M, false),
+                (KEY_CAPSLOCK, false),
+            ],
+        )
+    }
+
+    #[test]
+    fn macro_runs_on_its_own_timeline() {
+        let (mut engine, clock) = engine_from(
+            r#"
+[bindings]
+M = { macro = "demo" }
+
+[macros]
+demo = [{ tap = "Ctrl+A" }, { delay = 50 }, { text = "Hi" }]
+"#,
+        );
+        assert_eq!(
+            trigger_macro(&mut engine),
+            [
+                Out::press(KEY_LEFTCTRL),
+                Out::press(KEY_A),
+                Out::release(KEY_A),
+                Out::release(KEY_LEFTCTRL),
+            ]
+        );
+        // 等待期间其他键照常输出
+        assert_eq!(press(&mut engine, KEY_X), [Out::press(KEY_X)]);
+        clock.advance(Duration::from_millis(49));
+        assert!(engine.tick().is_empty());
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(
+            engine.tick(),
+            [(
+                DEVICE,
+                vec![
+                    Out::press(KEY_LEFTSHIFT),
+                    Out::press(KEY_H),
+                    Out::release(KEY_H),
+                    Out::release(KEY_LEFTSHIFT),
+                    Out::press(KEY_I),
+                    Out::release(KEY_I),
+                ]
+            )]
+        );
+        clock.advance(Duration::from_millis(50));
+        assert!(engine.tick().is_empty());
+    }
+
+    #[test]
+    fn macro_holds_keys_for_a_while() {
+        let (mut engine, clock) = engine_from(
+            r#"
+[bindings]
+M = { macro = "demo" }
+
+[macros]
+demo = [{ hold = "Space", ms = 300 }]
+"#,
+        );
+        assert_eq!(trigger_macro(&mut engine), [Out::press(KEY_SPACE)]);
+        clock.advance(Duration::from_millis(300));
+        assert_eq!(engine.tick(), [(DEVICE, vec![Out::release(KEY_SPACE)])]);
+    }
+
+    #[test]
+    fn macro_releases_held_keys_when_finished() {
+        let (mut engine, _) = engine_from(
+            r#"
+[bindings]
+M = { macro = "demo" }
+
+[macros]
+demo = [{ press = "Ctrl" }, { tap = "C" }, { release_all = true }, { press = "Shift" }]
+"#,
+        );
+        assert_eq!(
+            trigger_macro(&mut engine),
+            [
+                Out::press(KEY_LEFTCTRL),
+                Out::press(KEY_C),
+                Out::release(KEY_C),
+                Out::release(KEY_LEFTCTRL),
+                Out::press(KEY_LEFTSHIFT),
+                Out::release(KEY_LEFTSHIFT),
+            ]
+        );
+    }
+
+    #[test]
+    fn pressing_trigger_again_aborts_macro() {
+        let (mut engine, clock) = engine_from(
+            r#"
+[bindings]
+M = { macro = "demo" }
+
+[macros]
+demo = [{ press = "Shift" }, { press = "A" }, { delay = 1000 }, { release = "A" }, { tap = "B" }]
+"#,
+        );
+        assert_eq!(
+            trigger_macro(&mut engine),
+            [Out::press(KEY_LEFTSHIFT), Out::press(KEY_A)]
+        );
+        // 中止时按相反的顺序抬起宏按住的键，之后不再执行
+        assert_eq!(
+            trigger_macro(&mut engine),
+            [Out::release(KEY_A), Out::release(KEY_LEFTSHIFT)]
+        );
+        clock.advance(Duration::from_millis(1000));
+        assert!(engine.tick().is_empty());
+
+        // 退出拦截时同样抬起
+        trigger_macro(&mut engine);
+        assert_eq!(
+            engine.stop_macros(),
+            [(
+                DEVICE,
+                vec![Out::release(KEY_A), Out::release(KEY_LEFTSHIFT)]
+            )]
+        );
+    }
+
+    #[test]
+    fn macro_runs_without_held_modifiers() {
+        let (mut engine, _) = engine_from(
+            r#"
+[bindings]
+M = { macro = "demo" }
+
+[macros]
+demo = [{ text = "a" }]
+"#,
+        );
+        assert_eq!(
+            press(&mut engine, KEY_LEFTSHIFT),
+            [Out::press(KEY_LEFTSHIFT)]
+        );
+        assert_eq!(
+            run(&mut engine, &[(KEY_CAPSLOCK, true), (KEY_M, true)]),
+            [
+                Out::release(KEY_LEFTSHIFT),
+                Out::press(KEY_A),
+                Out::release(KEY_A),
+                Out::press(KEY_LEFTSHIFT),
+            ]
+        );
+    }
+
+    #[test]
+    fn modifiers_typed_during_macro_delay_are_kept() {
+        let (mut engine, clock) = engine_from(
+            r#"
+[bindings]
+M = { macro = "demo" }
+
+[macros]
+demo = [{ tap = "A" }, { delay = 100 }, { tap = "B" }]
+"#,
+        );
+        trigger_macro(&mut engine);
+        assert_eq!(
+            press(&mut engine, KEY_LEFTSHIFT),
+            [Out::press(KEY_LEFTSHIFT)]
+        );
+        assert_eq!(press(&mut engine, KEY_X), [Out::press(KEY_X)]);
+        // 执行步骤时暂时抬起 Shift，之后恢复
+        clock.advance(Duration::from_millis(100));
+        assert_eq!(
+            engine.tick(),
+            [(
+                DEVICE,
+                vec![
+                    Out::release(KEY_LEFTSHIFT),
+                    Out::press(KEY_B),
+                    Out::release(KEY_B),
+                    Out::press(KEY_LEFTSHIFT),
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn macro_modifiers_are_recorded_in_ledger() {
+        let (mut engine, clock) = engine_from(
+            r#"
+[bindings]
+M = { macro = "demo" }
+
+[macros]
+demo = [{ press = "Ctrl" }, { delay = 100 }, { release = "Ctrl" }]
+"#,
+        );
+        assert_eq!(trigger_macro(&mut engine), [Out::press(KEY_LEFTCTRL)]);
+        assert!(
+            engine.devices[&DEVICE]
+                .modifiers
+                .is_nuna_pressed(KEY_LEFTCTRL)
+        );
+        // 物理 Ctrl 的按下与抬起不影响宏按住的 Ctrl
+        assert!(press(&mut engine, KEY_LEFTCTRL).is_empty());
+        assert!(release(&mut engine, KEY_LEFTCTRL).is_empty());
+        clock.advance(Duration::from_millis(100));
+        assert_eq!(engine.tick(), [(DEVICE, vec![Out::release(KEY_LEFTCTRL)])]);
+        assert!(
+            !engine.devices[&DEVICE]
+                .modifiers
+                .is_nuna_pressed(KEY_LEFTCTRL)
+        );
+    }
+
+    #[test]
+    fn resyncs_stuck_ctrl() {
+        let ctrl_down = Arc::new(AtomicBool::new(true));
+        let query = Arc::clone(&ctrl_down);
+        let mut engine = Engine::new(Keymap::builtin())
+            .with_key_state(move |code| code == KEY_LEFTCTRL && query.load(Ordering::SeqCst));
+        run(&mut engine, &[(KEY_CAPSLOCK, true), (KEY_B, true)]);
+        release(&mut engine, KEY_CAPSLOCK);
+
+        // 系统仍认为 Ctrl 按下时不做修正
+        assert_eq!(press(&mut engine, KEY_H), [Out::press(KEY_H)]);
+        ctrl_down.store(false, Ordering::SeqCst);
+        assert_eq!(
+            press(&mut engine, KEY_H),
+            [Out::release(KEY_LEFTCTRL), Out::press(KEY_H)]
+        );
+        // 修正只发生一次
+        assert_eq!(press(&mut engine, KEY_H), [Out::press(KEY_H)]);
+    }
+}
@@ -0,0 +1,120 @@
+//! 修饰键账本：记录每个修饰键的物理状态，以及系统中实际是否按下。
+//!
+//! 两者不一致时说明 nuna 改变了它：物理键抬起而系统中按下，是映射按下的；
+//! 物理键按下而系统中抬起，是映射暂时抬起的。映射结束后按物理状态恢复。
+
+use super::OutputEvent;
+use crate::oscode::OsCode;
+
+/// 所有修饰键，也是恢复物理状态时的按下顺序
+const MODIFIERS: [OsCode; 8] = [
+    OsCode::KEY_LEFTCTRL,
+    OsCode::KEY_RIGHTCTRL,
+    OsCode::KEY_LEFTSHIFT,
+    OsCode::KEY_RIGHTSHIFT,
+    OsCode::KEY_LEFTALT,
+    OsCode::KEY_RIGHTALT,
+    OsCode::KEY_LEFTMETA,
+    OsCode::KEY_RIGHTMETA,
+];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct ModifierState {
+    /// 物理键是否按下
+    physical: bool,
+    /// 系统中是否按下，即最后一次发送的是按下还是抬起
+    down: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct ModifierLedger {
+    states: [ModifierState; MODIFIERS.len()],
+}
+
+impl ModifierLedger {
+    #[cfg(test)]
+    fn state(&self, code: OsCode) -> ModifierState {
+        MODIFIERS
+            .iter()
+            .position(|&m| m == code)
+            .map_or_else(ModifierState::default, |i| self.states[i])
+    }
+
+    fn state_mut(&mut self, code: OsCode) -> Option<&mut ModifierState> {
+        let i = MODIFIERS.iter().position(|&m| m == code)?;
+        Some(&mut self.states[i])
+    }
+
+    /// 记录修饰键的物理状态，不产生输出
+    pub fn set_physical(&mut self, code: OsCode, down: bool) {
+        if let Some(state) = self.state_mut(code) {
+            state.physical = down;
+        }
+    }
+
+    /// 记录一次直接发送给系统的修饰键事件（例如启动前就已按下的键抬起）
+    pub fn set_sent(&mut self, code: OsCode, down: bool) {
+        if let Some(state) = self.state_mut(code) {
+            state.down = down;
+        }
+    }
+
+    /// 物理键是否按下
+    #[cfg(test)]
+    pub fn is_physical(&self, code: OsCode) -> bool {
+        self.state(code).physical
+    }
+
+    /// 是否由 nuna 按下（物理键没有按下）
+    #[cfg(test)]
+    pub fn is_nuna_pressed(&self, code: OsCode) -> bool {
+        let state = self.state(code);
+        state.down && !state.physical
+    }
+
+    /// 是否被 nuna 暂时抬起（物理键仍按下）
+    #[cfg(test)]
+    pub fn is_lifted(&self, code: OsCode) -> bool {
+        let state = self.state(code);
+        state.physical && !state.down
+    }
+
+    /// 物理上按下的修饰键
+    pub fn physical(&self) -> Vec<OsCode> {
+        MODIFIERS
+            .iter()
+            .zip(&self.states)
+            .filter(|(_, state)| state.physical)
+            .map(|(&code, _)| code)
+            .collect()
+    }
+
+    /// 让系统中按下的修饰键恰好为 `desired`：先抬起多余的，再按 `desired` 的顺序按下缺少的
+    pub fn sync(&mut self, desired: &[OsCode], output: &mut Vec<OutputEvent>) {
+        for (&code, state) in MODIFIERS.iter().zip(&mut self.states) {
+            if state.down && !desired.contains(&code) {
+                state.down = false;
+                output.push(OutputEvent::release(code));
+            }
+        }
+        for &code in desired {
+            if let Some(state) = self.state_mut(code)
+                && !state.down
+            {
+                state.down = true;
+                output.push(OutputEvent::press(code));
+            }
+        }
+    }
+
+    /// 系统中已抬起、账本却认为由 nuna 按下的修饰键，强制发送一次抬起以保持同步
+    pub fn resync(&mut self, is_down: impl Fn(OsCode) -> bool, output: &mut Vec<OutputEvent>) {
+        for (&code, state) in MODIFIERS.iter().zip(&mut self.states) {
+            if state.down && !state.physical && !is_down(code) {
+                state.down = false;
+                output.push(OutputEvent::release(code));
+                log::warn!("{code} 卡在按下状态，已发送抬起");
+            }
+        }
+    }
+}
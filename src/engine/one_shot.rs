@@ -0,0 +1,78 @@
+//! 一次性修饰键与层：触发后只作用于之后按下的一个键，超时或按 Esc 取消；
+//! 未使用前再触发一次则锁定，作用于之后所有的键，直到再次触发或按 Esc。
+
+use super::layers::LayerId;
+use crate::oscode::OsCode;
+use std::time::{Duration, Instant};
+
+/// 一次性作用的对象
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Modifier(OsCode),
+    Layer(LayerId),
+}
+
+#[derive(Debug, Default)]
+pub struct OneShots {
+    /// 已触发的对象及是否已锁定，按触发顺序排列
+    active: Vec<(Target, bool)>,
+    /// 最后一次触发的时间，未锁定的对象从此时开始计时
+    since: Option<Instant>,
+}
+
+impl OneShots {
+    pub fn is_empty(&self) -> bool {
+        self.active.is_empty()
+    }
+
+    /// 触发一次：未触发时激活，已激活时锁定，已锁定时取消
+    pub fn trigger(&mut self, target: Target, now: Instant) {
+        match self.active.iter().position(|&(t, _)| t == target) {
+            Some(index) if self.active[index].1 => {
+                self.active.remove(index);
+            }
+            Some(index) => self.active[index].1 = true,
+            None => self.active.push((target, false)),
+        }
+        self.since = Some(now);
+    }
+
+    /// 超时未使用的对象失效，锁定的不受影响
+    pub fn expire(&mut self, now: Instant, timeout: Duration) {
+        if self
+            .since
+            .is_some_and(|since| now.duration_since(since) >= timeout)
+        {
+            self.consume();
+        }
+    }
+
+    /// 按下一个键后，未锁定的对象失效
+    pub fn consume(&mut self) {
+        self.active.retain(|&(_, locked)| locked);
+    }
+
+    /// 全部取消，包括锁定的
+    pub fn cancel(&mut self) {
+        self.active.clear();
+    }
+
+    /// 生效的修饰键
+    pub fn modifiers(&self) -> impl Iterator<Item = OsCode> + '_ {
+        self.active.iter().filter_map(|&(target, _)| match target {
+            Target::Modifier(code) => Some(code),
+            Target::Layer(_) => None,
+        })
+    }
+
+    /// 生效的层，后触发的在上面
+    pub fn layers(&self) -> impl Iterator<Item = LayerId> + '_ {
+        self.active
+            .iter()
+            .rev()
+            .filter_map(|&(target, _)| match target {
+                Target::Layer(layer) => Some(layer),
+                Target::Modifier(_) => None,
+            })
+    }
+}
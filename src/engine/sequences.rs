@@ -0,0 +1,115 @@
+//! 序列的匹配：所有序列组成一棵前缀树，依次按下的键沿树向下走，
+//! 走到叶子即匹配；走不通则重新从根开始。
+
+use super::{DeviceId, KeyEvent};
+use crate::config::Sequence;
+use crate::oscode::{KeyChord, OsCode};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// 已输入的键的匹配结果
+#[derive(Debug, PartialEq, Eq)]
+pub enum Match<'a> {
+    /// 是某个序列的前缀，继续等待
+    Prefix,
+    /// 恰好组成序列
+    Complete(&'a KeyChord),
+    /// 不是任何序列的前缀
+    Mismatch,
+}
+
+/// 序列的前缀树
+#[derive(Debug, Default)]
+pub struct Trie {
+    children: HashMap<OsCode, Trie>,
+    output: Option<KeyChord>,
+}
+
+impl Trie {
+    /// 配置加载时已保证没有序列是另一个的前缀
+    pub fn new(sequences: &[Sequence]) -> Self {
+        let mut root = Trie::default();
+        for sequence in sequences {
+            let node = sequence.keys.iter().fold(&mut root, |node, &key| {
+                node.children.entry(key).or_default()
+            });
+            node.output = Some(sequence.output.clone());
+        }
+        root
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    pub fn find(&self, keys: &[OsCode]) -> Match<'_> {
+        let mut node = self;
+        for key in keys {
+            match node.children.get(key) {
+                Some(child) => node = child,
+                None => return Match::Mismatch,
+            }
+        }
+        match &node.output {
+            Some(output) => Match::Complete(output),
+            None => Match::Prefix,
+        }
+    }
+}
+
+/// 正在输入的序列
+#[derive(Debug, Default)]
+pub struct Typed {
+    /// 已按下且是某个序列前缀的键
+    pub keys: Vec<OsCode>,
+    /// 最后一个键按下的时间
+    last: Option<Instant>,
+    /// 暂不输出的事件，按原来的顺序排列
+    held: Vec<KeyEvent>,
+}
+
+impl Typed {
+    pub fn push(&mut self, code: OsCode, now: Instant) {
+        self.keys.push(code);
+        self.last = Some(now);
+    }
+
+    /// 暂不输出一个事件
+    pub fn hold(&mut self, event: KeyEvent) {
+        self.held.push(event);
+    }
+
+    /// 该键最后一个暂存的事件是否是按下，是则它的抬起也需要暂存
+    pub fn holds_press(&self, event: &KeyEvent) -> bool {
+        self.held
+            .iter()
+            .rev()
+            .find(|e| e.device == event.device && e.code == event.code)
+            .is_some_and(|e| e.down)
+    }
+
+    /// 产生暂存事件的设备
+    pub fn device(&self) -> Option<DeviceId> {
+        self.held.first().map(|event| event.device)
+    }
+
+    /// 相邻两个键的间隔是否已超过超时
+    pub fn is_expired(&self, now: Instant, timeout: Duration) -> bool {
+        self.last
+            .is_some_and(|last| now.duration_since(last) >= timeout)
+    }
+
+    /// 暂存的事件中仍按住的键（只有按下没有抬起）
+    pub fn still_pressed(&self) -> Vec<KeyEvent> {
+        self.held
+            .iter()
+            .filter(|event| event.down && self.holds_press(event))
+            .copied()
+            .collect()
+    }
+
+    /// 暂存的事件
+    pub fn into_held(self) -> Vec<KeyEvent> {
+        self.held
+    }
+}
@@ -0,0 +1,67 @@
+//! 层键的 tap-hold 判定：层键按下后先进入待定状态，期间的其他按键暂存起来，
+//! 判定为轻触或按住后再按判定结果依次处理。
+
+use super::layers::LayerId;
+use super::{KeyEvent, PhysicalKey};
+use crate::config::TapHold;
+use std::time::Instant;
+
+/// 尚未判定轻触还是按住的层键
+#[derive(Debug)]
+pub struct PendingTap {
+    pub key: PhysicalKey,
+    pub layer: LayerId,
+    since: Instant,
+    /// 判定期间暂存的其他按键事件
+    buffer: Vec<KeyEvent>,
+}
+
+/// 判定期间其他键事件的处理结果
+#[derive(Debug, PartialEq, Eq)]
+pub enum Decision {
+    /// 已暂存，继续等待判定
+    Wait,
+    /// 判定为按住，暂存的事件（包括本事件）需要在激活层后依次处理
+    Hold,
+    /// 与判定无关，立即处理
+    Now(KeyEvent),
+}
+
+impl PendingTap {
+    pub fn new(key: PhysicalKey, layer: LayerId, now: Instant) -> Self {
+        PendingTap {
+            key,
+            layer,
+            since: now,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// 是否已超过判定时间，超过即判定为按住
+    pub fn is_expired(&self, now: Instant, config: &TapHold) -> bool {
+        now.duration_since(self.since) >= config.tapping_term
+    }
+
+    /// 判定期间其他键的事件
+    pub fn other_key(&mut self, event: KeyEvent, config: &TapHold) -> Decision {
+        let pressed_while_pending = self
+            .buffer
+            .iter()
+            .any(|e| e.down && e.device == event.device && e.code == event.code);
+        if !event.down && !pressed_while_pending {
+            // 层键按下前就已按住的键，抬起与判定无关
+            return Decision::Now(event);
+        }
+        self.buffer.push(event);
+        if event.down && config.hold_on_other_key_press || !event.down && config.permissive_hold {
+            Decision::Hold
+        } else {
+            Decision::Wait
+        }
+    }
+
+    /// 暂存的事件
+    pub fn into_buffer(self) -> Vec<KeyEvent> {
+        self.buffer
+    }
+}
@@ -0,0 +1,131 @@
+//! Interception 驱动的薄封装。
+//!
+//! kanata_interception 把 `KeyState::E1` 定义成了 3（驱动中实际为 0x04），
+//! 接收时还会把带 E1 位的事件当作无效事件直接丢弃，导致 Pause 键无法识别。
+//! 这里改为通过 interception-sys 直接收发原始事件，保留完整的状态位。
+
+use kanata_interception::{Device, KeyFilter, KeyState, ScanCode, Stroke, raw};
+use std::os::raw::{c_uint, c_ulong};
+use std::time::Duration;
+
+/// Interception 上下文，只拦截键盘设备
+pub struct Interception {
+    ctx: raw::InterceptionContext,
+}
+
+impl Interception {
+    /// 创建上下文，驱动未安装时返回 `None`
+    pub fn new() -> Option<Self> {
+        let ctx = unsafe { raw::interception_create_context() };
+        if ctx.is_null() {
+            None
+        } else {
+            Some(Interception { ctx })
+        }
+    }
+
+    /// 设置所有键盘设备的拦截过滤器
+    pub fn set_filter(&self, filter: KeyFilter) {
+        unsafe {
+            raw::interception_set_filter(
+                self.ctx,
+                Some(raw::interception_is_keyboard),
+                filter.bits(),
+            )
+        }
+    }
+
+    /// 等待任意设备产生事件，超时返回 0
+    pub fn wait_with_timeout(&self, duration: Duration) -> Device {
+        let millis = c_ulong::try_from(duration.as_millis()).unwrap_or(c_ulong::MAX);
+        unsafe { raw::interception_wait_with_timeout(self.ctx, millis) }
+    }
+
+    /// 按顺序接收键盘事件。
+    /// 扫描码无法识别的事件无法用 `Stroke` 表示，以原始事件返回，由调用方按原来的位置发回。
+    pub fn receive(&self, device: Device) -> Vec<Result<Stroke, raw::InterceptionKeyStroke>> {
+        let mut raw_strokes = [raw::InterceptionKeyStroke::default(); 32];
+        let len = c_uint::try_from(raw_strokes.len()).unwrap_or(c_uint::MAX);
+        let num_read = unsafe {
+            raw::interception_receive(self.ctx, device, raw_strokes.as_mut_ptr().cast(), len)
+        };
+        raw_strokes[..num_read.max(0) as usize]
+            .iter()
+            .map(|&raw_stroke| {
+                let code = ScanCode::try_from(raw_stroke.code).map_err(|_| raw_stroke)?;
+                // 保留 E1 (0x04) 等 KeyState 中没有正确定义的位
+                let state = unsafe { KeyState::from_bits_unchecked(raw_stroke.state) };
+                Ok(Stroke::Keyboard {
+                    code,
+                    state,
+                    information: raw_stroke.information,
+                })
+            })
+            .collect()
+    }
+
+    /// 接收设备上的键盘事件并原样发回
+    pub fn forward(&self, device: Device) {
+        let mut raw_strokes = [raw::InterceptionKeyStroke::default(); 32];
+        let len = c_uint::try_from(raw_strokes.len()).unwrap_or(c_uint::MAX);
+        let num_read = unsafe {
+            raw::interception_receive(self.ctx, device, raw_strokes.as_mut_ptr().cast(), len)
+        };
+        self.send_raw(device, &raw_strokes[..num_read.max(0) as usize]);
+    }
+
+    /// 发送键盘事件，非键盘事件会被忽略
+    pub fn send(&self, device: Device, strokes: &[Stroke]) {
+        let raw_strokes: Vec<raw::InterceptionKeyStroke> =
+            strokes.iter().filter_map(to_raw).collect();
+        self.send_raw(device, &raw_strokes);
+    }
+
+    /// 设备的硬件 ID，例如 `HID\VID_046D&PID_C31C&REV_6400&MI_00`；设备不存在时返回 `None`
+    pub fn get_hardware_id(&self, device: Device) -> Option<String> {
+        let mut buffer = [0u16; 512];
+        let size = unsafe {
+            raw::interception_get_hardware_id(
+                self.ctx,
+                device,
+                buffer.as_mut_ptr().cast(),
+                std::mem::size_of_val(&buffer) as c_uint,
+            )
+        };
+        // 返回的是以 NUL 分隔的 UTF-16 字符串列表，只取第一个
+        let len = (size as usize / 2).min(buffer.len());
+        let id = buffer[..len].split(|&c| c == 0).next()?;
+        (!id.is_empty()).then(|| String::from_utf16_lossy(id))
+    }
+
+    /// 原样发送驱动格式的键盘事件
+    pub fn send_raw(&self, device: Device, raw_strokes: &[raw::InterceptionKeyStroke]) {
+        if raw_strokes.is_empty() {
+            return;
+        }
+        let len = c_uint::try_from(raw_strokes.len()).unwrap_or(c_uint::MAX);
+        unsafe { raw::interception_send(self.ctx, device, raw_strokes.as_ptr().cast(), len) };
+    }
+}
+
+/// 转换为驱动格式的键盘事件，非键盘事件返回 `None`
+pub fn to_raw(stroke: &Stroke) -> Option<raw::InterceptionKeyStroke> {
+    match *stroke {
+        Stroke::Keyboard {
+            code,
+            state,
+            information,
+        } => Some(raw::InterceptionKeyStroke {
+            code: code as u16,
+            state: state.bits(),
+            information,
+        }),
+        Stroke::Mouse { .. } => None,
+    }
+}
+
+impl Drop for Interception {
+    fn drop(&mut self) {
+        unsafe { raw::interception_destroy_context(self.ctx) }
+    }
+}
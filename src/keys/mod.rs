@@ -1,8 +1,6 @@
 
 #![allow(unused)]
 
-use kanata_interception::ScanCode;
-
 // Taken from:
 // https://github.com/retep998/winapi-rs/blob/0.3/src/um/winuser.rs#L253
 pub const VK_LBUTTON: u16 = 0x01;
@@ -197,264 +195,3 @@ pub const VK_ZOOM: u16 = 0xFB;
 pub const VK_NONAME: u16 = 0xFC;
 pub const VK_PA1: u16 = 0xFD;
 pub const VK_OEM_CLEAR: u16 = 0xFE;
-
-
-pub fn all_scan_codes()->Vec<ScanCode>{
-    vec![
-        ScanCode::Esc,
-        ScanCode::Num1,
-        ScanCode::Num2,
-        ScanCode::Num3,
-        ScanCode::Num4,
-        ScanCode::Num5,
-        ScanCode::Num6,
-        ScanCode::Num7,
-        ScanCode::Num8,
-        ScanCode::Num9,
-        ScanCode::Num0,
-        ScanCode::Minus,
-        ScanCode::Equals,
-        ScanCode::Backspace,
-        ScanCode::Tab,
-        ScanCode::Q,
-        ScanCode::W,
-        ScanCode::E,
-        ScanCode::R,
-        ScanCode::T,
-        ScanCode::Y,
-        ScanCode::U,
-        ScanCode::I,
-        ScanCode::O,
-        ScanCode::P,
-        ScanCode::LeftBracket,
-        ScanCode::RightBracket,
-        ScanCode::Enter,
-        ScanCode::LeftControl,
-        ScanCode::A,
-        ScanCode::S,
-        ScanCode::D,
-        ScanCode::F,
-        ScanCode::G,
-        ScanCode::H,
-        ScanCode::J,
-        ScanCode::K,
-        ScanCode::L,
-        ScanCode::SemiColon,
-        ScanCode::Apostrophe,
-        ScanCode::Grave,
-        ScanCode::LeftShift,
-        ScanCode::BackSlash,
-        ScanCode::Z,
-        ScanCode::X,
-        ScanCode::C,
-        ScanCode::V,
-        ScanCode::B,
-        ScanCode::N,
-        ScanCode::M,
-        ScanCode::Comma,
-        ScanCode::Period,
-        ScanCode::Slash,
-        ScanCode::RightShift,
-        ScanCode::NumpadMultiply,
-        ScanCode::LeftAlt,
-        ScanCode::Space,
-        ScanCode::CapsLock,
-        ScanCode::F1,
-        ScanCode::F2,
-        ScanCode::F3,
-        ScanCode::F4,
-        ScanCode::F5,
-        ScanCode::F6,
-        ScanCode::F7,
-        ScanCode::F8,
-        ScanCode::F9,
-        ScanCode::F10,
-        ScanCode::NumLock,
-        ScanCode::ScrollLock,
-        ScanCode::Numpad7,
-        ScanCode::Numpad8,
-        ScanCode::Numpad9,
-        ScanCode::NumpadMinus,
-        ScanCode::Numpad4,
-        ScanCode::Numpad5,
-        ScanCode::Numpad6,
-        ScanCode::NumpadPlus,
-        ScanCode::Numpad1,
-        ScanCode::Numpad2,
-        ScanCode::Numpad3,
-        ScanCode::Numpad0,
-        ScanCode::NumpadPeriod,
-        ScanCode::AltPrintScreen,
-        ScanCode::SC_55,
-        ScanCode::Int1,
-        ScanCode::F11,
-        ScanCode::F12,
-        ScanCode::SC_59,
-        ScanCode::Oem1,
-        ScanCode::Oem2,
-        ScanCode::Oem3,
-        ScanCode::EraseEOF,
-        ScanCode::Oem4,
-        ScanCode::Oem5,
-        ScanCode::SC_60,
-        ScanCode::SC_61,
-        ScanCode::Zoom,
-        ScanCode::Help,
-        ScanCode::F13,
-        ScanCode::F14,
-        ScanCode::F15,
-        ScanCode::F16,
-        ScanCode::F17,
-        ScanCode::F18,
-        ScanCode::F19,
-        ScanCode::F20,
-        ScanCode::F21,
-        ScanCode::F22,
-        ScanCode::F23,
-        ScanCode::Oem6,
-        ScanCode::Katakana,
-        ScanCode::Oem7,
-        ScanCode::SC_72,
-        ScanCode::SC_73,
-        ScanCode::SC_74,
-        ScanCode::SC_75,
-        ScanCode::F24,
-        ScanCode::SBCSChar,
-        ScanCode::SC_78,
-        ScanCode::Convert,
-        ScanCode::SC_7A,
-        ScanCode::NonConvert,
-        ScanCode::SC_7C,
-        ScanCode::SC_7D,
-        ScanCode::SC_7E,
-        ScanCode::SC_7F,
-        ScanCode::SC_80,
-        ScanCode::SC_81,
-        ScanCode::SC_82,
-        ScanCode::SC_83,
-        ScanCode::SC_84,
-        ScanCode::SC_85,
-        ScanCode::SC_86,
-        ScanCode::SC_87,
-        ScanCode::SC_88,
-        ScanCode::SC_89,
-        ScanCode::SC_8A,
-        ScanCode::SC_8B,
-        ScanCode::SC_8C,
-        ScanCode::SC_8D,
-        ScanCode::SC_8E,
-        ScanCode::SC_8F,
-        ScanCode::SC_90,
-        ScanCode::SC_91,
-        ScanCode::SC_92,
-        ScanCode::SC_93,
-        ScanCode::SC_94,
-        ScanCode::SC_95,
-        ScanCode::SC_96,
-        ScanCode::SC_97,
-        ScanCode::SC_98,
-        ScanCode::SC_99,
-        ScanCode::SC_9A,
-        ScanCode::SC_9B,
-        ScanCode::SC_9C,
-        ScanCode::SC_9D,
-        ScanCode::SC_9E,
-        ScanCode::SC_9F,
-        ScanCode::SC_A0,
-        ScanCode::SC_A1,
-        ScanCode::SC_A2,
-        ScanCode::SC_A3,
-        ScanCode::SC_A4,
-        ScanCode::SC_A5,
-        ScanCode::SC_A6,
-        ScanCode::SC_A7,
-        ScanCode::SC_A8,
-        ScanCode::SC_A9,
-        ScanCode::SC_AA,
-        ScanCode::SC_AB,
-        ScanCode::SC_AC,
-        ScanCode::SC_AD,
-        ScanCode::SC_AE,
-        ScanCode::SC_AF,
-        ScanCode::SC_B0,
-        ScanCode::SC_B1,
-        ScanCode::SC_B2,
-        ScanCode::SC_B3,
-        ScanCode::SC_B4,
-        ScanCode::SC_B5,
-        ScanCode::SC_B6,
-        ScanCode::SC_B7,
-        ScanCode::SC_B8,
-        ScanCode::SC_B9,
-        ScanCode::SC_BA,
-        ScanCode::SC_BB,
-        ScanCode::SC_BC,
-        ScanCode::SC_BD,
-        ScanCode::SC_BE,
-        ScanCode::SC_BF,
-        ScanCode::SC_C0,
-        ScanCode::SC_C1,
-        ScanCode::SC_C2,
-        ScanCode::SC_C3,
-        ScanCode::SC_C4,
-        ScanCode::SC_C5,
-        ScanCode::SC_C6,
-        ScanCode::SC_C7,
-        ScanCode::SC_C8,
-        ScanCode::SC_C9,
-        ScanCode::SC_CA,
-        ScanCode::SC_CB,
-        ScanCode::SC_CC,
-        ScanCode::SC_CD,
-        ScanCode::SC_CE,
-        ScanCode::SC_CF,
-        ScanCode::SC_D0,
-        ScanCode::SC_D1,
-        ScanCode::SC_D2,
-        ScanCode::SC_D3,
-        ScanCode::SC_D4,
-        ScanCode::SC_D5,
-        ScanCode::SC_D6,
-        ScanCode::SC_D7,
-        ScanCode::SC_D8,
-        ScanCode::SC_D9,
-        ScanCode::SC_DA,
-        ScanCode::SC_DB,
-        ScanCode::SC_DC,
-        ScanCode::SC_DD,
-        ScanCode::SC_DE,
-        ScanCode::SC_DF,
-        ScanCode::SC_E0,
-        ScanCode::SC_E1,
-        ScanCode::SC_E2,
-        ScanCode::SC_E3,
-        ScanCode::SC_E4,
-        ScanCode::SC_E5,
-        ScanCode::SC_E6,
-        ScanCode::SC_E7,
-        ScanCode::SC_E8,
-        ScanCode::SC_E9,
-        ScanCode::SC_EA,
-        ScanCode::SC_EB,
-        ScanCode::SC_EC,
-        ScanCode::SC_ED,
-        ScanCode::SC_EE,
-        ScanCode::SC_EF,
-        ScanCode::SC_F0,
-        ScanCode::SC_F1,
-        ScanCode::SC_F2,
-        ScanCode::SC_F3,
-        ScanCode::SC_F4,
-        ScanCode::SC_F5,
-        ScanCode::SC_F6,
-        ScanCode::SC_F7,
-        ScanCode::SC_F8,
-        ScanCode::SC_F9,
-        ScanCode::SC_FA,
-        ScanCode::SC_FB,
-        ScanCode::SC_FC,
-        ScanCode::SC_FD,
-        ScanCode::SC_FE,
-        ScanCode::SC_NonExtendMax,
-    ]
-}
\ No newline at end of file
@@ -0,0 +1,331 @@
+//! Interception 键盘事件与 OsCode 之间的转换，包括多事件序列的解码与编码。
+//! 仅在启用 `interception` 功能的 Windows 构建中编译。
+//!
+//! 键盘的扫描码并不总是一键一码：
+//! - NumLock 打开时，导航键前后会夹带 E0 2A / E0 AA（右 Shift 按住时为 E0 36 / E0 B6）这样的"假 Shift"，
+//!   它们不对应任何物理按键，不能触发映射，也不应转发；
+//! - Pause 键没有自己的扫描码，按下时发送 E1 1D 45，即带 E1 前缀的 Ctrl 加上 NumLock。
+//!
+//! `StrokeDecoder` 负责吞掉假 Shift 并把 E1 序列合并为 `KEY_PAUSE`，
+//! `OsCode::to_strokes` 则反过来把 `KEY_PAUSE` 展开为 E1 序列。输出时导航键只带 E0 标志、从不附加假 Shift，
+//! 因此无论 NumLock 是否打开，系统收到的都是真正的导航键。
+
+use super::OsCode;
+use kanata_interception::{KeyState, ScanCode, Stroke};
+
+/// Interception 驱动中的 E1 标志位。kanata_interception 的 `KeyState::E1` 被错误地定义为 3（即 `UP | E0`），
+/// 不能用它判断或构造 E1 事件
+const E1_BIT: u16 = 0x04;
+
+/// 事件是否带有 E1 前缀
+fn is_e1(state: KeyState) -> bool {
+    state.bits() & E1_BIT != 0
+}
+
+/// 构造带 E1 前缀的状态
+fn e1_state(down: bool) -> KeyState {
+    let state = if down { KeyState::DOWN } else { KeyState::UP };
+    // KeyState 中没有 0x04 这一位的定义，只能绕过 from_bits 的校验
+    unsafe { KeyState::from_bits_unchecked(state.bits() | E1_BIT) }
+}
+
+impl TryFrom<Stroke> for OsCode {
+    type Error = ();
+
+    fn try_from(item: Stroke) -> Result<Self, Self::Error> {
+        match item {
+            Stroke::Keyboard { code, state, .. } => {
+                // E1 前缀单独无法解码，由 `StrokeDecoder` 与后续事件合并为 Pause
+                if is_e1(state) {
+                    return Err(());
+                }
+                OsCode::from_scan_code(code as u16, state.contains(KeyState::E0)).ok_or(())
+            }
+            _ => Err(()),
+        }
+    }
+}
+
+impl OsCode {
+    /// 将键码编码为单个 Interception 键盘事件，扩展键带上 E0 标志。
+    /// Pause 需要多个事件，见 `to_strokes`。
+    pub fn to_stroke(self, down: bool) -> Option<Stroke> {
+        let (code, extended) = self.to_scan_code()?;
+        let code = ScanCode::try_from(code).ok()?;
+        let mut state = if down { KeyState::DOWN } else { KeyState::UP };
+        if extended {
+            state |= KeyState::E0;
+        }
+        Some(Stroke::Keyboard {
+            code,
+            state,
+            information: 0,
+        })
+    }
+}
+
+/// 是否为 E0 2A / E0 AA / E0 36 / E0 B6 假 Shift
+fn is_fake_shift(code: ScanCode, state: KeyState) -> bool {
+    state.contains(KeyState::E0)
+        && !is_e1(state)
+        && matches!(code, ScanCode::LeftShift | ScanCode::RightShift)
+}
+
+/// 解码结果
+#[derive(Debug, Clone, Copy)]
+pub enum Decoded {
+    /// 一个完整的按键事件
+    Key { code: OsCode, down: bool },
+    /// 假 Shift 或 E1 前缀，既不触发映射也不转发
+    Swallowed,
+    /// 无法识别的事件，应原样转发
+    Unknown,
+}
+
+/// 有状态的键盘事件解码器，每个设备一个
+#[derive(Debug, Default)]
+pub struct StrokeDecoder {
+    /// 已收到、等待 NumLock 扫描码的 E1 1D 前缀
+    e1_prefix: Option<Stroke>,
+}
+
+impl StrokeDecoder {
+    pub fn decode(&mut self, stroke: Stroke) -> Decoded {
+        let Stroke::Keyboard { code, state, .. } = stroke else {
+            return Decoded::Unknown;
+        };
+        let down = !state.contains(KeyState::UP);
+
+        if let Some(prefix) = self.e1_prefix.take() {
+            let prefix_down =
+                matches!(prefix, Stroke::Keyboard { state, .. } if !state.contains(KeyState::UP));
+            let plain = !state.contains(KeyState::E0) && !is_e1(state);
+            if code == ScanCode::NumLock && plain && prefix_down == down {
+                return Decoded::Key {
+                    code: OsCode::KEY_PAUSE,
+                    down,
+                };
+            }
+            // E1 1D 只会出现在 Pause 序列中，后面跟的不是 NumLock 说明序列已损坏
+            log::warn!("丢弃不完整的 E1 序列: {prefix:?}");
+        }
+
+        if is_e1(state) {
+            if code == ScanCode::LeftControl {
+                self.e1_prefix = Some(stroke);
+                return Decoded::Swallowed;
+            }
+            return Decoded::Unknown;
+        }
+        if is_fake_shift(code, state) {
+            return Decoded::Swallowed;
+        }
+        match OsCode::try_from(stroke) {
+            Ok(code) => Decoded::Key { code, down },
+            Err(()) => Decoded::Unknown,
+        }
+    }
+}
+
+impl OsCode {
+    /// 将键码编码为 Interception 键盘事件序列：Pause 展开为 E1 1D 45，其他键为单个事件。
+    /// 无法编码时返回空序列
+    pub fn to_strokes(self, down: bool) -> Vec<Stroke> {
+        if self == OsCode::KEY_PAUSE {
+            let state = if down { KeyState::DOWN } else { KeyState::UP };
+            return vec![
+                Stroke::Keyboard {
+                    code: ScanCode::LeftControl,
+                    state: e1_state(down),
+                    information: 0,
+                },
+                Stroke::Keyboard {
+                    code: ScanCode::NumLock,
+                    state,
+                    information: 0,
+                },
+            ];
+        }
+        self.to_stroke(down).into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stroke(code: ScanCode, state: KeyState) -> Stroke {
+        Stroke::Keyboard {
+            code,
+            state,
+            information: 0,
+        }
+    }
+
+    /// 依次解码，返回得到的按键事件
+    fn decode_all(strokes: &[Stroke]) -> Vec<(OsCode, bool)> {
+        let mut decoder = StrokeDecoder::default();
+        strokes
+            .iter()
+            .filter_map(|&s| match decoder.decode(s) {
+                Decoded::Key { code, down } => Some((code, down)),
+                Decoded::Swallowed | Decoded::Unknown => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn decodes_pause_sequence() {
+        let keys = decode_all(&[
+            stroke(ScanCode::LeftControl, e1_state(true)),
+            stroke(ScanCode::NumLock, KeyState::DOWN),
+            stroke(ScanCode::LeftControl, e1_state(false)),
+            stroke(ScanCode::NumLock, KeyState::UP),
+        ]);
+        assert_eq!(
+            keys,
+            [(OsCode::KEY_PAUSE, true), (OsCode::KEY_PAUSE, false)]
+        );
+    }
+
+    #[test]
+    fn plain_numlock_and_ctrl_are_unaffected() {
+        let keys = decode_all(&[
+            stroke(ScanCode::NumLock, KeyState::DOWN),
+            stroke(ScanCode::LeftControl, KeyState::DOWN),
+            stroke(ScanCode::LeftControl, KeyState::UP),
+            stroke(ScanCode::NumLock, KeyState::UP),
+        ]);
+        assert_eq!(
+            keys,
+            [
+                (OsCode::KEY_NUMLOCK, true),
+                (OsCode::KEY_LEFTCTRL, true),
+                (OsCode::KEY_LEFTCTRL, false),
+                (OsCode::KEY_NUMLOCK, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn broken_e1_sequence_is_dropped() {
+        let keys = decode_all(&[
+            stroke(ScanCode::LeftControl, e1_state(true)),
+            stroke(ScanCode::H, KeyState::DOWN),
+        ]);
+        assert_eq!(keys, [(OsCode::KEY_H, true)]);
+    }
+
+    #[test]
+    fn swallows_fake_shifts_around_navigation_keys() {
+        let e0_up = KeyState::E0 | KeyState::UP;
+        // NumLock 打开时按下再松开 Home
+        let keys = decode_all(&[
+            stroke(ScanCode::LeftShift, KeyState::E0),
+            stroke(ScanCode::Numpad7, KeyState::E0),
+            stroke(ScanCode::Numpad7, e0_up),
+            stroke(ScanCode::LeftShift, e0_up),
+        ]);
+        assert_eq!(keys, [(OsCode::KEY_HOME, true), (OsCode::KEY_HOME, false)]);
+
+        // 按住右 Shift 时按下 Left
+        let keys = decode_all(&[
+            stroke(ScanCode::RightShift, KeyState::DOWN),
+            stroke(ScanCode::RightShift, e0_up),
+            stroke(ScanCode::Numpad4, KeyState::E0),
+            stroke(ScanCode::Numpad4, e0_up),
+            stroke(ScanCode::RightShift, KeyState::E0),
+            stroke(ScanCode::RightShift, KeyState::UP),
+        ]);
+        assert_eq!(
+            keys,
+            [
+                (OsCode::KEY_RIGHTSHIFT, true),
+                (OsCode::KEY_LEFT, true),
+                (OsCode::KEY_LEFT, false),
+                (OsCode::KEY_RIGHTSHIFT, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn pause_round_trips_through_encoder() {
+        for down in [true, false] {
+            let keys = decode_all(&OsCode::KEY_PAUSE.to_strokes(down));
+            assert_eq!(keys, [(OsCode::KEY_PAUSE, down)]);
+        }
+    }
+
+    #[test]
+    fn navigation_keys_are_encoded_without_fake_shifts() {
+        for key in [OsCode::KEY_HOME, OsCode::KEY_LEFT, OsCode::KEY_DELETE] {
+            let strokes = key.to_strokes(true);
+            assert_eq!(strokes.len(), 1, "{key:?}");
+            let Stroke::Keyboard { code, state, .. } = strokes[0] else {
+                unreachable!()
+            };
+            assert!(!is_fake_shift(code, state), "{key:?}");
+            assert_eq!(state, KeyState::E0, "{key:?}");
+        }
+    }
+
+    #[test]
+    fn encodable_keys_match_the_table() {
+        for &os_code in OsCode::ALL {
+            assert_eq!(
+                !os_code.to_strokes(true).is_empty(),
+                os_code.is_encodable(),
+                "{os_code:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn stroke_round_trip_is_identity() {
+        let mut encodable = 0;
+        for &os_code in OsCode::ALL {
+            for down in [true, false] {
+                let Some(stroke) = os_code.to_stroke(down) else {
+                    continue;
+                };
+                encodable += 1;
+                assert_eq!(OsCode::try_from(stroke), Ok(os_code), "{os_code:?}");
+                let Stroke::Keyboard { state, .. } = stroke else {
+                    unreachable!()
+                };
+                assert_eq!(state.contains(KeyState::UP), !down, "{os_code:?}");
+            }
+        }
+        assert!(encodable > 0);
+    }
+
+    #[test]
+    fn every_decodable_stroke_is_encodable() {
+        for code in (0..=0xFF).filter_map(|code: u16| ScanCode::try_from(code).ok()) {
+            for state in [
+                KeyState::DOWN,
+                KeyState::E0,
+                KeyState::UP,
+                KeyState::UP | KeyState::E0,
+            ] {
+                let stroke = Stroke::Keyboard {
+                    code,
+                    state,
+                    information: 0,
+                };
+                if let Ok(os_code) = OsCode::try_from(stroke) {
+                    assert!(os_code.to_stroke(true).is_some(), "{os_code:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn navigation_keys_use_e0_without_e1() {
+        let Some(Stroke::Keyboard { code, state, .. }) = OsCode::KEY_LEFT.to_stroke(false) else {
+            panic!("Left 无法编码");
+        };
+        assert_eq!(code, ScanCode::Numpad4);
+        assert_eq!(state, KeyState::UP | KeyState::E0);
+    }
+}
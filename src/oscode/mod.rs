@@ -1,5 +1,16 @@
-use kanata_interception::{KeyState, ScanCode, Stroke};
-use crate::keys::*;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+#[cfg(all(windows, feature = "interception"))]
+mod decoder;
+mod names;
+mod table;
+#[cfg(all(windows, feature = "interception"))]
+pub use decoder::{Decoded, StrokeDecoder};
+pub use names::KeyChord;
+#[allow(unused)]
+pub use table::all_scan_codes;
+
 #[allow(unused)]
 #[allow(non_camel_case_types)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -553,330 +564,651 @@ pub enum OsCode {
     BTN_TRIGGER_HAPPY40 = 743,
     BTN_MAX = 744,
 }
-impl TryFrom<Stroke> for OsCode {
-    type Error = ();
-
-    fn try_from(item: Stroke) -> anyhow::Result<Self, Self::Error> {
-        Ok(match item {
-            Stroke::Keyboard { code, state, .. } => {
-                match (state.contains(KeyState::E0), state.contains(KeyState::E1)) {
-                    (false, false) => {
-                        match code {
-                            ScanCode::Esc => OsCode::KEY_ESC,
-                            ScanCode::Num1 => OsCode::KEY_1,
-                            ScanCode::Num2 => OsCode::KEY_2,
-                            ScanCode::Num3 => OsCode::KEY_3,
-                            ScanCode::Num4 => OsCode::KEY_4,
-                            ScanCode::Num5 => OsCode::KEY_5,
-                            ScanCode::Num6 => OsCode::KEY_6,
-                            ScanCode::Num7 => OsCode::KEY_7,
-                            ScanCode::Num8 => OsCode::KEY_8,
-                            ScanCode::Num9 => OsCode::KEY_9,
-                            ScanCode::Num0 => OsCode::KEY_0,
-                            ScanCode::Minus => OsCode::KEY_MINUS,
-                            ScanCode::Equals => OsCode::KEY_EQUAL,
-                            ScanCode::Backspace => OsCode::KEY_BACKSPACE,
-                            ScanCode::Tab => OsCode::KEY_TAB,
-                            ScanCode::Q => OsCode::KEY_Q,
-                            ScanCode::W => OsCode::KEY_W,
-                            ScanCode::E => OsCode::KEY_E,
-                            ScanCode::R => OsCode::KEY_R,
-                            ScanCode::T => OsCode::KEY_T,
-                            ScanCode::Y => OsCode::KEY_Y,
-                            ScanCode::U => OsCode::KEY_U,
-                            ScanCode::I => OsCode::KEY_I,
-                            ScanCode::O => OsCode::KEY_O,
-                            ScanCode::P => OsCode::KEY_P,
-                            ScanCode::LeftBracket => OsCode::KEY_LEFTBRACE,
-                            ScanCode::RightBracket => OsCode::KEY_RIGHTBRACE,
-                            ScanCode::Enter => OsCode::KEY_ENTER,
-                            ScanCode::LeftControl => OsCode::KEY_LEFTCTRL,
-                            ScanCode::A => OsCode::KEY_A,
-                            ScanCode::S => OsCode::KEY_S,
-                            ScanCode::D => OsCode::KEY_D,
-                            ScanCode::F => OsCode::KEY_F,
-                            ScanCode::G => OsCode::KEY_G,
-                            ScanCode::H => OsCode::KEY_H,
-                            ScanCode::J => OsCode::KEY_J,
-                            ScanCode::K => OsCode::KEY_K,
-                            ScanCode::L => OsCode::KEY_L,
-                            ScanCode::SemiColon => OsCode::KEY_SEMICOLON,
-                            ScanCode::Apostrophe => OsCode::KEY_APOSTROPHE,
-                            ScanCode::Grave => OsCode::KEY_GRAVE,
-                            ScanCode::LeftShift => OsCode::KEY_LEFTSHIFT,
-                            ScanCode::BackSlash => OsCode::KEY_BACKSLASH,
-                            ScanCode::Z => OsCode::KEY_Z,
-                            ScanCode::X => OsCode::KEY_X,
-                            ScanCode::C => OsCode::KEY_C,
-                            ScanCode::V => OsCode::KEY_V,
-                            ScanCode::B => OsCode::KEY_B,
-                            ScanCode::N => OsCode::KEY_N,
-                            ScanCode::M => OsCode::KEY_M,
-                            ScanCode::Comma => OsCode::KEY_COMMA,
-                            ScanCode::Period => OsCode::KEY_DOT,
-                            ScanCode::Slash => OsCode::KEY_SLASH,
-                            ScanCode::RightShift => OsCode::KEY_RIGHTSHIFT,
-                            ScanCode::NumpadMultiply => OsCode::KEY_KPASTERISK,
-                            ScanCode::LeftAlt => OsCode::KEY_LEFTALT,
-                            ScanCode::Space => OsCode::KEY_SPACE,
-                            ScanCode::CapsLock => OsCode::KEY_CAPSLOCK,
-                            ScanCode::F1 => OsCode::KEY_F1,
-                            ScanCode::F2 => OsCode::KEY_F2,
-                            ScanCode::F3 => OsCode::KEY_F3,
-                            ScanCode::F4 => OsCode::KEY_F4,
-                            ScanCode::F5 => OsCode::KEY_F5,
-                            ScanCode::F6 => OsCode::KEY_F6,
-                            ScanCode::F7 => OsCode::KEY_F7,
-                            ScanCode::F8 => OsCode::KEY_F8,
-                            ScanCode::F9 => OsCode::KEY_F9,
-                            ScanCode::F10 => OsCode::KEY_F10,
-                            ScanCode::NumLock => OsCode::KEY_NUMLOCK,
-                            ScanCode::ScrollLock => OsCode::KEY_SCROLLLOCK,
-                            ScanCode::Numpad7 => OsCode::KEY_KP7,
-                            ScanCode::Numpad8 => OsCode::KEY_KP8,
-                            ScanCode::Numpad9 => OsCode::KEY_KP9,
-                            ScanCode::NumpadMinus => OsCode::KEY_KPMINUS,
-                            ScanCode::Numpad4 => OsCode::KEY_KP4,
-                            ScanCode::Numpad5 => OsCode::KEY_KP5,
-                            ScanCode::Numpad6 => OsCode::KEY_KP6,
-                            ScanCode::NumpadPlus => OsCode::KEY_KPPLUS,
-                            ScanCode::Numpad1 => OsCode::KEY_KP1,
-                            ScanCode::Numpad2 => OsCode::KEY_KP2,
-                            ScanCode::Numpad3 => OsCode::KEY_KP3,
-                            ScanCode::Numpad0 => OsCode::KEY_KP0,
-                            ScanCode::NumpadPeriod => OsCode::KEY_KPDOT,
-                            ScanCode::Int1 => OsCode::KEY_102ND, /* Key between the left shift and Z. */
-                            ScanCode::F11 => OsCode::KEY_F11,
-                            ScanCode::F12 => OsCode::KEY_F12,
-                            ScanCode::F13 => OsCode::KEY_F13,
-                            ScanCode::F14 => OsCode::KEY_F14,
-                            ScanCode::F15 => OsCode::KEY_F15,
-                            ScanCode::F16 => OsCode::KEY_F16,
-                            ScanCode::F17 => OsCode::KEY_F17,
-                            ScanCode::F18 => OsCode::KEY_F18,
-                            ScanCode::F19 => OsCode::KEY_F19,
-                            ScanCode::F20 => OsCode::KEY_F20,
-                            ScanCode::F21 => OsCode::KEY_F21,
-                            ScanCode::F22 => OsCode::KEY_F22,
-                            ScanCode::F23 => OsCode::KEY_F23,
-                            ScanCode::F24 => OsCode::KEY_F24,
-                            ScanCode::Katakana => OsCode::KEY_KATAKANA,
-                            // Note: the OEM keys below don't seem to correspond to the same VK OEM
-                            // mappings as the LLHOOK codes.
-                            // ScanCode::Oem1 = 0x5A, /* VK_OEM_WSCTRL */
-                            // ScanCode::Oem2 = 0x5B, /* VK_OEM_FINISH */
-                            // ScanCode::Oem3 = 0x5C, /* VK_OEM_JUMP */
-                            // ScanCode::Oem4 = 0x5E, /* VK_OEM_BACKTAB */
-                            // ScanCode::Oem5 = 0x5F, /* VK_OEM_AUTO */
-                            // ScanCode::Oem6 = 0x6F, /* VK_OEM_PA3 */
-                            // ScanCode::Oem7 = 0x71, /* VK_OEM_RESET */
-                            // ScanCode::EraseEOF = 0x5D,
-                            // ScanCode::Zoom => 0x62,
-                            // ScanCode::Help => 0x63,
-                            // ScanCode::AltPrintScreen = 0x55, /* Alt + print screen. */
-                            // ScanCode::SBCSChar = 0x77,
-                            // ScanCode::Convert = 0x79,
-                            // ScanCode::NonConvert = 0x7B,
-                            _ => return Err(()),
-                        }
-                    }
 
-                    (true, _) => {
-                        match code as u8 {
-                            0x10 => OsCode::KEY_PREVIOUSSONG,
-                            0x19 => OsCode::KEY_NEXTSONG,
-                            0x1C => OsCode::KEY_KPENTER,
-                            0x1D => OsCode::KEY_RIGHTCTRL,
-                            0x20 => OsCode::KEY_MUTE,
-                            0x22 => OsCode::KEY_PLAYPAUSE, // sc_media_play
-                            // 0x24 => OsCode::KEY_TODO, // sc_media_stop
-                            0x2E => OsCode::KEY_VOLUMEDOWN, // sc_volume_down
-                            0x30 => OsCode::KEY_VOLUMEUP,   // sc_volume_up
-                            // 0x32 => OsCode::KEY_TODO, // sc_browser_home
-                            0x35 => OsCode::KEY_KPSLASH, // sc_numpad_divide
-                            0x37 => OsCode::KEY_PRINT,   // sc_printScreen
-                            0x38 => OsCode::KEY_RIGHTALT, // sc_altRight
-                            // 0x46 => OsCode::KEY_TODO, // sc_cancel
-                            0x47 => OsCode::KEY_HOME,      // sc_home
-                            0x48 => OsCode::KEY_UP,        // sc_arrowUp
-                            0x49 => OsCode::KEY_PAGEUP,    // sc_pageUp
-                            0x4B => OsCode::KEY_LEFT,      // sc_arrowLeft
-                            0x4D => OsCode::KEY_RIGHT,     // sc_arrowRight
-                            0x4F => OsCode::KEY_END,       // sc_end
-                            0x50 => OsCode::KEY_DOWN,      // sc_arrowDown
-                            0x51 => OsCode::KEY_PAGEDOWN,  // sc_pageDown
-                            0x52 => OsCode::KEY_INSERT,    // sc_insert
-                            0x53 => OsCode::KEY_DELETE,    // sc_delete
-                            0x5B => OsCode::KEY_LEFTMETA,  // sc_metaLeft
-                            0x5C => OsCode::KEY_RIGHTMETA, // sc_metaRight
-                            // 0x5D => OsCode::KEY_TODO, // sc_application
-                            // 0x5E => OsCode::KEY_TODO, // sc_power
-                            // 0x5F => OsCode::KEY_TODO, // sc_sleep
-                            // 0x63 => OsCode::KEY_TODO, // sc_wake
-                            // 0x65 => OsCode::KEY_TODO, // sc_browser_search
-                            // 0x66 => OsCode::KEY_TODO, // sc_browser_favorites
-                            // 0x67 => OsCode::KEY_TODO, // sc_browser_refresh
-                            // 0x68 => OsCode::KEY_TODO, // sc_browser_stop
-                            0x69 => OsCode::KEY_FORWARD, // sc_browser_forward
-                            0x6A => OsCode::KEY_BACK,    // sc_browser_back
-                            // 0x6B => OsCode::KEY_TODO, // sc_launch_app1
-                            // 0x6C => OsCode::KEY_TODO, // sc_launch_email
-                            // 0x6D => OsCode::KEY_TODO, // sc_launch_media
-                            _ => return Err(()),
-                        }
-                    }
+impl OsCode {
+    /// 所有的键码，按枚举定义顺序排列
+    pub const ALL: &[OsCode] = &[
+        OsCode::KEY_RESERVED,
+        OsCode::KEY_ESC,
+        OsCode::KEY_1,
+        OsCode::KEY_2,
+        OsCode::KEY_3,
+        OsCode::KEY_4,
+        OsCode::KEY_5,
+        OsCode::KEY_6,
+        OsCode::KEY_7,
+        OsCode::KEY_8,
+        OsCode::KEY_9,
+        OsCode::KEY_0,
+        OsCode::KEY_MINUS,
+        OsCode::KEY_EQUAL,
+        OsCode::KEY_BACKSPACE,
+        OsCode::KEY_TAB,
+        OsCode::KEY_Q,
+        OsCode::KEY_W,
+        OsCode::KEY_E,
+        OsCode::KEY_R,
+        OsCode::KEY_T,
+        OsCode::KEY_Y,
+        OsCode::KEY_U,
+        OsCode::KEY_I,
+        OsCode::KEY_O,
+        OsCode::KEY_P,
+        OsCode::KEY_LEFTBRACE,
+        OsCode::KEY_RIGHTBRACE,
+        OsCode::KEY_ENTER,
+        OsCode::KEY_LEFTCTRL,
+        OsCode::KEY_A,
+        OsCode::KEY_S,
+        OsCode::KEY_D,
+        OsCode::KEY_F,
+        OsCode::KEY_G,
+        OsCode::KEY_H,
+        OsCode::KEY_J,
+        OsCode::KEY_K,
+        OsCode::KEY_L,
+        OsCode::KEY_SEMICOLON,
+        OsCode::KEY_APOSTROPHE,
+        OsCode::KEY_GRAVE,
+        OsCode::KEY_LEFTSHIFT,
+        OsCode::KEY_BACKSLASH,
+        OsCode::KEY_Z,
+        OsCode::KEY_X,
+        OsCode::KEY_C,
+        OsCode::KEY_V,
+        OsCode::KEY_B,
+        OsCode::KEY_N,
+        OsCode::KEY_M,
+        OsCode::KEY_COMMA,
+        OsCode::KEY_DOT,
+        OsCode::KEY_SLASH,
+        OsCode::KEY_RIGHTSHIFT,
+        OsCode::KEY_KPASTERISK,
+        OsCode::KEY_LEFTALT,
+        OsCode::KEY_SPACE,
+        OsCode::KEY_CAPSLOCK,
+        OsCode::KEY_F1,
+        OsCode::KEY_F2,
+        OsCode::KEY_F3,
+        OsCode::KEY_F4,
+        OsCode::KEY_F5,
+        OsCode::KEY_F6,
+        OsCode::KEY_F7,
+        OsCode::KEY_F8,
+        OsCode::KEY_F9,
+        OsCode::KEY_F10,
+        OsCode::KEY_NUMLOCK,
+        OsCode::KEY_SCROLLLOCK,
+        OsCode::KEY_KP7,
+        OsCode::KEY_KP8,
+        OsCode::KEY_KP9,
+        OsCode::KEY_KPMINUS,
+        OsCode::KEY_KP4,
+        OsCode::KEY_KP5,
+        OsCode::KEY_KP6,
+        OsCode::KEY_KPPLUS,
+        OsCode::KEY_KP1,
+        OsCode::KEY_KP2,
+        OsCode::KEY_KP3,
+        OsCode::KEY_KP0,
+        OsCode::KEY_KPDOT,
+        OsCode::KEY_ZENKAKUHANKAKU,
+        OsCode::KEY_102ND,
+        OsCode::KEY_F11,
+        OsCode::KEY_F12,
+        OsCode::KEY_RO,
+        OsCode::KEY_KATAKANA,
+        OsCode::KEY_HIRAGANA,
+        OsCode::KEY_HENKAN,
+        OsCode::KEY_KATAKANAHIRAGANA,
+        OsCode::KEY_MUHENKAN,
+        OsCode::KEY_KPJPCOMMA,
+        OsCode::KEY_KPENTER,
+        OsCode::KEY_RIGHTCTRL,
+        OsCode::KEY_KPSLASH,
+        OsCode::KEY_SYSRQ,
+        OsCode::KEY_RIGHTALT,
+        OsCode::KEY_LINEFEED,
+        OsCode::KEY_HOME,
+        OsCode::KEY_UP,
+        OsCode::KEY_PAGEUP,
+        OsCode::KEY_LEFT,
+        OsCode::KEY_RIGHT,
+        OsCode::KEY_END,
+        OsCode::KEY_DOWN,
+        OsCode::KEY_PAGEDOWN,
+        OsCode::KEY_INSERT,
+        OsCode::KEY_DELETE,
+        OsCode::KEY_MACRO,
+        OsCode::KEY_MUTE,
+        OsCode::KEY_VOLUMEDOWN,
+        OsCode::KEY_VOLUMEUP,
+        OsCode::KEY_POWER,
+        OsCode::KEY_KPEQUAL,
+        OsCode::KEY_KPPLUSMINUS,
+        OsCode::KEY_PAUSE,
+        OsCode::KEY_SCALE,
+        OsCode::KEY_KPCOMMA,
+        OsCode::KEY_HANGEUL,
+        OsCode::KEY_HANJA,
+        OsCode::KEY_YEN,
+        OsCode::KEY_LEFTMETA,
+        OsCode::KEY_RIGHTMETA,
+        OsCode::KEY_COMPOSE,
+        OsCode::KEY_STOP,
+        OsCode::KEY_AGAIN,
+        OsCode::KEY_PROPS,
+        OsCode::KEY_UNDO,
+        OsCode::KEY_FRONT,
+        OsCode::KEY_COPY,
+        OsCode::KEY_OPEN,
+        OsCode::KEY_PASTE,
+        OsCode::KEY_FIND,
+        OsCode::KEY_CUT,
+        OsCode::KEY_HELP,
+        OsCode::KEY_MENU,
+        OsCode::KEY_CALC,
+        OsCode::KEY_SETUP,
+        OsCode::KEY_SLEEP,
+        OsCode::KEY_WAKEUP,
+        OsCode::KEY_FILE,
+        OsCode::KEY_SENDFILE,
+        OsCode::KEY_DELETEFILE,
+        OsCode::KEY_XFER,
+        OsCode::KEY_PROG1,
+        OsCode::KEY_PROG2,
+        OsCode::KEY_WWW,
+        OsCode::KEY_MSDOS,
+        OsCode::KEY_COFFEE,
+        OsCode::KEY_ROTATE_DISPLAY,
+        OsCode::KEY_CYCLEWINDOWS,
+        OsCode::KEY_MAIL,
+        OsCode::KEY_BOOKMARKS,
+        OsCode::KEY_COMPUTER,
+        OsCode::KEY_BACK,
+        OsCode::KEY_FORWARD,
+        OsCode::KEY_CLOSECD,
+        OsCode::KEY_EJECTCD,
+        OsCode::KEY_EJECTCLOSECD,
+        OsCode::KEY_NEXTSONG,
+        OsCode::KEY_PLAYPAUSE,
+        OsCode::KEY_PREVIOUSSONG,
+        OsCode::KEY_STOPCD,
+        OsCode::KEY_RECORD,
+        OsCode::KEY_REWIND,
+        OsCode::KEY_PHONE,
+        OsCode::KEY_ISO,
+        OsCode::KEY_CONFIG,
+        OsCode::KEY_HOMEPAGE,
+        OsCode::KEY_REFRESH,
+        OsCode::KEY_EXIT,
+        OsCode::KEY_MOVE,
+        OsCode::KEY_EDIT,
+        OsCode::KEY_SCROLLUP,
+        OsCode::KEY_SCROLLDOWN,
+        OsCode::KEY_KPLEFTPAREN,
+        OsCode::KEY_KPRIGHTPAREN,
+        OsCode::KEY_NEW,
+        OsCode::KEY_REDO,
+        OsCode::KEY_F13,
+        OsCode::KEY_F14,
+        OsCode::KEY_F15,
+        OsCode::KEY_F16,
+        OsCode::KEY_F17,
+        OsCode::KEY_F18,
+        OsCode::KEY_F19,
+        OsCode::KEY_F20,
+        OsCode::KEY_F21,
+        OsCode::KEY_F22,
+        OsCode::KEY_F23,
+        OsCode::KEY_F24,
+        OsCode::KEY_PLAYCD,
+        OsCode::KEY_PAUSECD,
+        OsCode::KEY_PROG3,
+        OsCode::KEY_PROG4,
+        OsCode::KEY_DASHBOARD,
+        OsCode::KEY_SUSPEND,
+        OsCode::KEY_CLOSE,
+        OsCode::KEY_PLAY,
+        OsCode::KEY_FASTFORWARD,
+        OsCode::KEY_BASSBOOST,
+        OsCode::KEY_PRINT,
+        OsCode::KEY_HP,
+        OsCode::KEY_CAMERA,
+        OsCode::KEY_SOUND,
+        OsCode::KEY_QUESTION,
+        OsCode::KEY_EMAIL,
+        OsCode::KEY_CHAT,
+        OsCode::KEY_SEARCH,
+        OsCode::KEY_CONNECT,
+        OsCode::KEY_FINANCE,
+        OsCode::KEY_SPORT,
+        OsCode::KEY_SHOP,
+        OsCode::KEY_ALTERASE,
+        OsCode::KEY_CANCEL,
+        OsCode::KEY_BRIGHTNESSDOWN,
+        OsCode::KEY_BRIGHTNESSUP,
+        OsCode::KEY_MEDIA,
+        OsCode::KEY_SWITCHVIDEOMODE,
+        OsCode::KEY_KBDILLUMTOGGLE,
+        OsCode::KEY_KBDILLUMDOWN,
+        OsCode::KEY_KBDILLUMUP,
+        OsCode::KEY_SEND,
+        OsCode::KEY_REPLY,
+        OsCode::KEY_FORWARDMAIL,
+        OsCode::KEY_SAVE,
+        OsCode::KEY_DOCUMENTS,
+        OsCode::KEY_BATTERY,
+        OsCode::KEY_BLUETOOTH,
+        OsCode::KEY_WLAN,
+        OsCode::KEY_UWB,
+        OsCode::KEY_UNKNOWN,
+        OsCode::KEY_VIDEO_NEXT,
+        OsCode::KEY_VIDEO_PREV,
+        OsCode::KEY_BRIGHTNESS_CYCLE,
+        OsCode::KEY_BRIGHTNESS_AUTO,
+        OsCode::KEY_DISPLAY_OFF,
+        OsCode::KEY_WWAN,
+        OsCode::KEY_RFKILL,
+        OsCode::KEY_MICMUTE,
+        OsCode::KEY_OK,
+        OsCode::KEY_SELECT,
+        OsCode::KEY_GOTO,
+        OsCode::KEY_CLEAR,
+        OsCode::KEY_POWER2,
+        OsCode::KEY_OPTION,
+        OsCode::KEY_INFO,
+        OsCode::KEY_TIME,
+        OsCode::KEY_VENDOR,
+        OsCode::KEY_ARCHIVE,
+        OsCode::KEY_PROGRAM,
+        OsCode::KEY_CHANNEL,
+        OsCode::KEY_FAVORITES,
+        OsCode::KEY_EPG,
+        OsCode::KEY_PVR,
+        OsCode::KEY_MHP,
+        OsCode::KEY_LANGUAGE,
+        OsCode::KEY_TITLE,
+        OsCode::KEY_SUBTITLE,
+        OsCode::KEY_ANGLE,
+        OsCode::KEY_FULL_SCREEN,
+        OsCode::KEY_MODE,
+        OsCode::KEY_KEYBOARD,
+        OsCode::KEY_ASPECT_RATIO,
+        OsCode::KEY_PC,
+        OsCode::KEY_TV,
+        OsCode::KEY_TV2,
+        OsCode::KEY_VCR,
+        OsCode::KEY_VCR2,
+        OsCode::KEY_SAT,
+        OsCode::KEY_SAT2,
+        OsCode::KEY_CD,
+        OsCode::KEY_TAPE,
+        OsCode::KEY_RADIO,
+        OsCode::KEY_TUNER,
+        OsCode::KEY_PLAYER,
+        OsCode::KEY_TEXT,
+        OsCode::KEY_DVD,
+        OsCode::KEY_AUX,
+        OsCode::KEY_MP3,
+        OsCode::KEY_AUDIO,
+        OsCode::KEY_VIDEO,
+        OsCode::KEY_DIRECTORY,
+        OsCode::KEY_LIST,
+        OsCode::KEY_MEMO,
+        OsCode::KEY_CALENDAR,
+        OsCode::KEY_RED,
+        OsCode::KEY_GREEN,
+        OsCode::KEY_YELLOW,
+        OsCode::KEY_BLUE,
+        OsCode::KEY_CHANNELUP,
+        OsCode::KEY_CHANNELDOWN,
+        OsCode::KEY_FIRST,
+        OsCode::KEY_LAST,
+        OsCode::KEY_AB,
+        OsCode::KEY_NEXT,
+        OsCode::KEY_RESTART,
+        OsCode::KEY_SLOW,
+        OsCode::KEY_SHUFFLE,
+        OsCode::KEY_BREAK,
+        OsCode::KEY_PREVIOUS,
+        OsCode::KEY_DIGITS,
+        OsCode::KEY_TEEN,
+        OsCode::KEY_TWEN,
+        OsCode::KEY_VIDEOPHONE,
+        OsCode::KEY_GAMES,
+        OsCode::KEY_ZOOMIN,
+        OsCode::KEY_ZOOMOUT,
+        OsCode::KEY_ZOOMRESET,
+        OsCode::KEY_WORDPROCESSOR,
+        OsCode::KEY_EDITOR,
+        OsCode::KEY_SPREADSHEET,
+        OsCode::KEY_GRAPHICSEDITOR,
+        OsCode::KEY_PRESENTATION,
+        OsCode::KEY_DATABASE,
+        OsCode::KEY_NEWS,
+        OsCode::KEY_VOICEMAIL,
+        OsCode::KEY_ADDRESSBOOK,
+        OsCode::KEY_MESSENGER,
+        OsCode::KEY_DISPLAYTOGGLE,
+        OsCode::KEY_SPELLCHECK,
+        OsCode::KEY_LOGOFF,
+        OsCode::KEY_DOLLAR,
+        OsCode::KEY_EURO,
+        OsCode::KEY_FRAMEBACK,
+        OsCode::KEY_FRAMEFORWARD,
+        OsCode::KEY_CONTEXT_MENU,
+        OsCode::KEY_MEDIA_REPEAT,
+        OsCode::KEY_10CHANNELSUP,
+        OsCode::KEY_10CHANNELSDOWN,
+        OsCode::KEY_IMAGES,
+        OsCode::KEY_DEL_EOL,
+        OsCode::KEY_DEL_EOS,
+        OsCode::KEY_INS_LINE,
+        OsCode::KEY_DEL_LINE,
+        OsCode::KEY_FN,
+        OsCode::KEY_FN_ESC,
+        OsCode::KEY_FN_F1,
+        OsCode::KEY_FN_F2,
+        OsCode::KEY_FN_F3,
+        OsCode::KEY_FN_F4,
+        OsCode::KEY_FN_F5,
+        OsCode::KEY_FN_F6,
+        OsCode::KEY_FN_F7,
+        OsCode::KEY_FN_F8,
+        OsCode::KEY_FN_F9,
+        OsCode::KEY_FN_F10,
+        OsCode::KEY_FN_F11,
+        OsCode::KEY_FN_F12,
+        OsCode::KEY_FN_1,
+        OsCode::KEY_FN_2,
+        OsCode::KEY_FN_D,
+        OsCode::KEY_FN_E,
+        OsCode::KEY_FN_F,
+        OsCode::KEY_FN_S,
+        OsCode::KEY_FN_B,
+        OsCode::KEY_BRL_DOT1,
+        OsCode::KEY_BRL_DOT2,
+        OsCode::KEY_BRL_DOT3,
+        OsCode::KEY_BRL_DOT4,
+        OsCode::KEY_BRL_DOT5,
+        OsCode::KEY_BRL_DOT6,
+        OsCode::KEY_BRL_DOT7,
+        OsCode::KEY_BRL_DOT8,
+        OsCode::KEY_BRL_DOT9,
+        OsCode::KEY_BRL_DOT10,
+        OsCode::KEY_NUMERIC_0,
+        OsCode::KEY_NUMERIC_1,
+        OsCode::KEY_NUMERIC_2,
+        OsCode::KEY_NUMERIC_3,
+        OsCode::KEY_NUMERIC_4,
+        OsCode::KEY_NUMERIC_5,
+        OsCode::KEY_NUMERIC_6,
+        OsCode::KEY_NUMERIC_7,
+        OsCode::KEY_NUMERIC_8,
+        OsCode::KEY_NUMERIC_9,
+        OsCode::KEY_NUMERIC_STAR,
+        OsCode::KEY_NUMERIC_POUND,
+        OsCode::KEY_NUMERIC_A,
+        OsCode::KEY_NUMERIC_B,
+        OsCode::KEY_NUMERIC_C,
+        OsCode::KEY_NUMERIC_D,
+        OsCode::KEY_CAMERA_FOCUS,
+        OsCode::KEY_WPS_BUTTON,
+        OsCode::KEY_TOUCHPAD_TOGGLE,
+        OsCode::KEY_TOUCHPAD_ON,
+        OsCode::KEY_TOUCHPAD_OFF,
+        OsCode::KEY_CAMERA_ZOOMIN,
+        OsCode::KEY_CAMERA_ZOOMOUT,
+        OsCode::KEY_CAMERA_UP,
+        OsCode::KEY_CAMERA_DOWN,
+        OsCode::KEY_CAMERA_LEFT,
+        OsCode::KEY_CAMERA_RIGHT,
+        OsCode::KEY_ATTENDANT_ON,
+        OsCode::KEY_ATTENDANT_OFF,
+        OsCode::KEY_ATTENDANT_TOGGLE,
+        OsCode::KEY_LIGHTS_TOGGLE,
+        OsCode::KEY_ALS_TOGGLE,
+        OsCode::KEY_ROTATE_LOCK_TOGGLE,
+        OsCode::KEY_BUTTONCONFIG,
+        OsCode::KEY_TASKMANAGER,
+        OsCode::KEY_JOURNAL,
+        OsCode::KEY_CONTROLPANEL,
+        OsCode::KEY_APPSELECT,
+        OsCode::KEY_SCREENSAVER,
+        OsCode::KEY_VOICECOMMAND,
+        OsCode::KEY_ASSISTANT,
+        OsCode::KEY_KBD_LAYOUT_NEXT,
+        OsCode::KEY_BRIGHTNESS_MIN,
+        OsCode::KEY_BRIGHTNESS_MAX,
+        OsCode::KEY_KBDINPUTASSIST_PREV,
+        OsCode::KEY_KBDINPUTASSIST_NEXT,
+        OsCode::KEY_KBDINPUTASSIST_PREVGROUP,
+        OsCode::KEY_KBDINPUTASSIST_NEXTGROUP,
+        OsCode::KEY_KBDINPUTASSIST_ACCEPT,
+        OsCode::KEY_KBDINPUTASSIST_CANCEL,
+        OsCode::KEY_RIGHT_UP,
+        OsCode::KEY_RIGHT_DOWN,
+        OsCode::KEY_LEFT_UP,
+        OsCode::KEY_LEFT_DOWN,
+        OsCode::KEY_ROOT_MENU,
+        OsCode::KEY_MEDIA_TOP_MENU,
+        OsCode::KEY_NUMERIC_11,
+        OsCode::KEY_NUMERIC_12,
+        OsCode::KEY_AUDIO_DESC,
+        OsCode::KEY_3D_MODE,
+        OsCode::KEY_NEXT_FAVORITE,
+        OsCode::KEY_STOP_RECORD,
+        OsCode::KEY_PAUSE_RECORD,
+        OsCode::KEY_VOD,
+        OsCode::KEY_UNMUTE,
+        OsCode::KEY_FASTREVERSE,
+        OsCode::KEY_SLOWREVERSE,
+        OsCode::KEY_DATA,
+        OsCode::KEY_ONSCREEN_KEYBOARD,
+        OsCode::KEY_MAX,
+        OsCode::BTN_0,
+        OsCode::BTN_1,
+        OsCode::BTN_2,
+        OsCode::BTN_3,
+        OsCode::BTN_4,
+        OsCode::BTN_5,
+        OsCode::BTN_6,
+        OsCode::BTN_7,
+        OsCode::BTN_8,
+        OsCode::BTN_9,
+        OsCode::BTN_LEFT,
+        OsCode::BTN_RIGHT,
+        OsCode::BTN_MIDDLE,
+        OsCode::BTN_SIDE,
+        OsCode::BTN_EXTRA,
+        OsCode::BTN_FORWARD,
+        OsCode::BTN_BACK,
+        OsCode::BTN_TASK,
+        OsCode::BTN_TRIGGER,
+        OsCode::BTN_THUMB,
+        OsCode::BTN_THUMB2,
+        OsCode::BTN_TOP,
+        OsCode::BTN_TOP2,
+        OsCode::BTN_PINKIE,
+        OsCode::BTN_BASE,
+        OsCode::BTN_BASE2,
+        OsCode::BTN_BASE3,
+        OsCode::BTN_BASE4,
+        OsCode::BTN_BASE5,
+        OsCode::BTN_BASE6,
+        OsCode::BTN_DEAD,
+        OsCode::BTN_SOUTH,
+        OsCode::BTN_EAST,
+        OsCode::BTN_C,
+        OsCode::BTN_NORTH,
+        OsCode::BTN_WEST,
+        OsCode::BTN_Z,
+        OsCode::BTN_TL,
+        OsCode::BTN_TR,
+        OsCode::BTN_TL2,
+        OsCode::BTN_TR2,
+        OsCode::BTN_SELECT,
+        OsCode::BTN_START,
+        OsCode::BTN_MODE,
+        OsCode::BTN_THUMBL,
+        OsCode::BTN_THUMBR,
+        OsCode::BTN_TOOL_PEN,
+        OsCode::BTN_TOOL_RUBBER,
+        OsCode::BTN_TOOL_BRUSH,
+        OsCode::BTN_TOOL_PENCIL,
+        OsCode::BTN_TOOL_AIRBRUSH,
+        OsCode::BTN_TOOL_FINGER,
+        OsCode::BTN_TOOL_MOUSE,
+        OsCode::BTN_TOOL_LENS,
+        OsCode::BTN_TOOL_QUINTTAP,
+        OsCode::BTN_STYLUS3,
+        OsCode::BTN_TOUCH,
+        OsCode::BTN_STYLUS,
+        OsCode::BTN_STYLUS2,
+        OsCode::BTN_TOOL_DOUBLETAP,
+        OsCode::BTN_TOOL_TRIPLETAP,
+        OsCode::BTN_TOOL_QUADTAP,
+        OsCode::BTN_GEAR_DOWN,
+        OsCode::BTN_GEAR_UP,
+        OsCode::BTN_DPAD_UP,
+        OsCode::BTN_DPAD_DOWN,
+        OsCode::BTN_DPAD_LEFT,
+        OsCode::BTN_DPAD_RIGHT,
+        OsCode::BTN_TRIGGER_HAPPY1,
+        OsCode::BTN_TRIGGER_HAPPY2,
+        OsCode::BTN_TRIGGER_HAPPY3,
+        OsCode::BTN_TRIGGER_HAPPY4,
+        OsCode::BTN_TRIGGER_HAPPY5,
+        OsCode::BTN_TRIGGER_HAPPY6,
+        OsCode::BTN_TRIGGER_HAPPY7,
+        OsCode::BTN_TRIGGER_HAPPY8,
+        OsCode::BTN_TRIGGER_HAPPY9,
+        OsCode::BTN_TRIGGER_HAPPY10,
+        OsCode::BTN_TRIGGER_HAPPY11,
+        OsCode::BTN_TRIGGER_HAPPY12,
+        OsCode::BTN_TRIGGER_HAPPY13,
+        OsCode::BTN_TRIGGER_HAPPY14,
+        OsCode::BTN_TRIGGER_HAPPY15,
+        OsCode::BTN_TRIGGER_HAPPY16,
+        OsCode::BTN_TRIGGER_HAPPY17,
+        OsCode::BTN_TRIGGER_HAPPY18,
+        OsCode::BTN_TRIGGER_HAPPY19,
+        OsCode::BTN_TRIGGER_HAPPY20,
+        OsCode::BTN_TRIGGER_HAPPY21,
+        OsCode::BTN_TRIGGER_HAPPY22,
+        OsCode::BTN_TRIGGER_HAPPY23,
+        OsCode::BTN_TRIGGER_HAPPY24,
+        OsCode::BTN_TRIGGER_HAPPY25,
+        OsCode::BTN_TRIGGER_HAPPY26,
+        OsCode::BTN_TRIGGER_HAPPY27,
+        OsCode::BTN_TRIGGER_HAPPY28,
+        OsCode::BTN_TRIGGER_HAPPY29,
+        OsCode::BTN_TRIGGER_HAPPY30,
+        OsCode::BTN_TRIGGER_HAPPY31,
+        OsCode::BTN_TRIGGER_HAPPY32,
+        OsCode::BTN_TRIGGER_HAPPY33,
+        OsCode::BTN_TRIGGER_HAPPY34,
+        OsCode::BTN_TRIGGER_HAPPY35,
+        OsCode::BTN_TRIGGER_HAPPY36,
+        OsCode::BTN_TRIGGER_HAPPY37,
+        OsCode::BTN_TRIGGER_HAPPY38,
+        OsCode::BTN_TRIGGER_HAPPY39,
+        OsCode::BTN_TRIGGER_HAPPY40,
+        OsCode::BTN_MAX,
+    ];
 
-                    _ => return Err(()),
-                }
-            }
-            _ => return Err(()),
-        })
+    /// 按 Linux `input-event-codes.h` 中的编号查找键码
+    #[allow(unused)]
+    pub fn from_u16(code: u16) -> Option<OsCode> {
+        static BY_CODE: OnceLock<HashMap<u16, OsCode>> = OnceLock::new();
+        BY_CODE
+            .get_or_init(|| OsCode::ALL.iter().map(|&c| (c as u16, c)).collect())
+            .get(&code)
+            .copied()
     }
 }
 
-impl OsCode {
-    #[allow(unused)]
-    pub fn as_u16(self) -> u16 {
-        match self {
-            OsCode::KEY_0 => 0x30,
-            OsCode::KEY_1 => 0x31,
-            OsCode::KEY_2 => 0x32,
-            OsCode::KEY_3 => 0x33,
-            OsCode::KEY_4 => 0x34,
-            OsCode::KEY_5 => 0x35,
-            OsCode::KEY_6 => 0x36,
-            OsCode::KEY_7 => 0x37,
-            OsCode::KEY_8 => 0x38,
-            OsCode::KEY_9 => 0x39,
-            OsCode::KEY_A => 0x41,
-            OsCode::KEY_B => 0x42,
-            OsCode::KEY_C => 0x43,
-            OsCode::KEY_D => 0x44,
-            OsCode::KEY_E => 0x45,
-            OsCode::KEY_F => 0x46,
-            OsCode::KEY_G => 0x47,
-            OsCode::KEY_H => 0x48,
-            OsCode::KEY_I => 0x49,
-            OsCode::KEY_J => 0x4A,
-            OsCode::KEY_K => 0x4B,
-            OsCode::KEY_L => 0x4C,
-            OsCode::KEY_M => 0x4D,
-            OsCode::KEY_N => 0x4E,
-            OsCode::KEY_O => 0x4F,
-            OsCode::KEY_P => 0x50,
-            OsCode::KEY_Q => 0x51,
-            OsCode::KEY_R => 0x52,
-            OsCode::KEY_S => 0x53,
-            OsCode::KEY_T => 0x54,
-            OsCode::KEY_U => 0x55,
-            OsCode::KEY_V => 0x56,
-            OsCode::KEY_W => 0x57,
-            OsCode::KEY_X => 0x58,
-            OsCode::KEY_Y => 0x59,
-            OsCode::KEY_Z => 0x5A,
-            OsCode::KEY_SEMICOLON => VK_OEM_1,
-            OsCode::KEY_SLASH => VK_OEM_2,
-            OsCode::KEY_GRAVE => VK_OEM_3,
-            OsCode::KEY_LEFTBRACE => VK_OEM_4,
-            OsCode::KEY_BACKSLASH => VK_OEM_5,
-            OsCode::KEY_RIGHTBRACE => VK_OEM_6,
-            OsCode::KEY_APOSTROPHE => VK_OEM_7,
-            OsCode::KEY_MINUS => VK_OEM_MINUS,
-            OsCode::KEY_DOT => VK_OEM_PERIOD,
-            OsCode::KEY_EQUAL => VK_OEM_PLUS,
-            OsCode::KEY_BACKSPACE => VK_BACK,
-            OsCode::KEY_ESC => VK_ESCAPE,
-            OsCode::KEY_TAB => VK_TAB,
-            OsCode::KEY_ENTER => VK_RETURN,
-            OsCode::KEY_LEFTCTRL => VK_LCONTROL,
-            OsCode::KEY_LEFTSHIFT => VK_LSHIFT,
-            OsCode::KEY_COMMA => VK_OEM_COMMA,
-            OsCode::KEY_RIGHTSHIFT => VK_RSHIFT,
-            OsCode::KEY_KPASTERISK => VK_MULTIPLY,
-            OsCode::KEY_LEFTALT => VK_LMENU,
-            OsCode::KEY_SPACE => VK_SPACE,
-            OsCode::KEY_CAPSLOCK => VK_CAPITAL,
-            OsCode::KEY_F1 => VK_F1,
-            OsCode::KEY_F2 => VK_F2,
-            OsCode::KEY_F3 => VK_F3,
-            OsCode::KEY_F4 => VK_F4,
-            OsCode::KEY_F5 => VK_F5,
-            OsCode::KEY_F6 => VK_F6,
-            OsCode::KEY_F7 => VK_F7,
-            OsCode::KEY_F8 => VK_F8,
-            OsCode::KEY_F9 => VK_F9,
-            OsCode::KEY_F10 => VK_F10,
-            OsCode::KEY_F11 => VK_F11,
-            OsCode::KEY_F12 => VK_F12,
-            OsCode::KEY_NUMLOCK => VK_NUMLOCK,
-            OsCode::KEY_CLEAR => VK_CLEAR,
-            OsCode::KEY_SCROLLLOCK => VK_SCROLL,
-            OsCode::KEY_KP0 => VK_NUMPAD0,
-            OsCode::KEY_KP1 => VK_NUMPAD1,
-            OsCode::KEY_KP2 => VK_NUMPAD2,
-            OsCode::KEY_KP3 => VK_NUMPAD3,
-            OsCode::KEY_KP4 => VK_NUMPAD4,
-            OsCode::KEY_KP5 => VK_NUMPAD5,
-            OsCode::KEY_KP6 => VK_NUMPAD6,
-            OsCode::KEY_KP7 => VK_NUMPAD7,
-            OsCode::KEY_KP8 => VK_NUMPAD8,
-            OsCode::KEY_KP9 => VK_NUMPAD9,
-            OsCode::KEY_KPMINUS => VK_SUBTRACT,
-            OsCode::KEY_KPPLUS => VK_ADD,
-            OsCode::KEY_KPDOT => VK_DECIMAL,
-            OsCode::KEY_RIGHTCTRL => VK_RCONTROL,
-            OsCode::KEY_KPSLASH => VK_DIVIDE,
-            OsCode::KEY_RIGHTALT => VK_RMENU,
-            OsCode::KEY_HOME => VK_HOME,
-            OsCode::KEY_UP => VK_UP,
-            OsCode::KEY_PAGEUP => VK_PRIOR,
-            OsCode::KEY_LEFT => VK_LEFT,
-            OsCode::KEY_RIGHT => VK_RIGHT,
-            OsCode::KEY_END => VK_END,
-            OsCode::KEY_DOWN => VK_DOWN,
-            OsCode::KEY_PAGEDOWN => VK_NEXT,
-            OsCode::KEY_INSERT => VK_INSERT,
-            OsCode::KEY_DELETE => VK_DELETE,
-            OsCode::KEY_MUTE => VK_VOLUME_MUTE,
-            OsCode::KEY_VOLUMEDOWN => VK_VOLUME_DOWN,
-            OsCode::KEY_VOLUMEUP => VK_VOLUME_UP,
-            OsCode::KEY_PAUSE => VK_PAUSE,
-            OsCode::KEY_LEFTMETA => VK_LWIN,
-            OsCode::KEY_RIGHTMETA => VK_RWIN,
-            OsCode::KEY_COMPOSE => VK_APPS,
-            OsCode::KEY_BACK => VK_BROWSER_BACK,
-            OsCode::KEY_FORWARD => VK_BROWSER_FORWARD,
-            OsCode::KEY_NEXTSONG => VK_MEDIA_NEXT_TRACK,
-            OsCode::KEY_PLAYPAUSE => VK_MEDIA_PLAY_PAUSE,
-            OsCode::KEY_PREVIOUSSONG => VK_MEDIA_PREV_TRACK,
-            OsCode::KEY_STOP => VK_MEDIA_STOP,
-            OsCode::KEY_HOMEPAGE => VK_BROWSER_HOME,
-            OsCode::KEY_MAIL => VK_LAUNCH_MAIL,
-            OsCode::KEY_MEDIA => VK_LAUNCH_MEDIA_SELECT,
-            OsCode::KEY_REFRESH => VK_BROWSER_REFRESH,
-            OsCode::KEY_F13 => VK_F13,
-            OsCode::KEY_F14 => VK_F14,
-            OsCode::KEY_F15 => VK_F15,
-            OsCode::KEY_F16 => VK_F16,
-            OsCode::KEY_F17 => VK_F17,
-            OsCode::KEY_F18 => VK_F18,
-            OsCode::KEY_F19 => VK_F19,
-            OsCode::KEY_F20 => VK_F20,
-            OsCode::KEY_F21 => VK_F21,
-            OsCode::KEY_F22 => VK_F22,
-            OsCode::KEY_F23 => VK_F23,
-            OsCode::KEY_F24 => VK_F24,
-            OsCode::KEY_HANGEUL => VK_HANGEUL,
-            OsCode::KEY_HANJA => VK_HANJA,
-            OsCode::KEY_102ND => VK_OEM_102,
-            OsCode::KEY_PLAY => VK_PLAY,
-            OsCode::KEY_PRINT => VK_SNAPSHOT,
-            OsCode::KEY_SEARCH => VK_BROWSER_SEARCH,
-            OsCode::KEY_FAVORITES => VK_BROWSER_FAVORITES,
-            OsCode::KEY_RO => 0xC1,
-            OsCode::KEY_HENKAN => VK_CONVERT,
-            OsCode::KEY_MUHENKAN => VK_NONCONVERT,
-            _ => 0,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::*;
+
+    #[test]
+    fn names_resolve_to_scan_codes() {
+        let cases = [
+            ("Home", 0x47, true),
+            ("Left", 0x4B, true),
+            ("KP4", 0x4B, false),
+            ("Ctrl", 0x1D, false),
+            ("RCtrl", 0x1D, true),
+            ("Delete", 0x53, true),
+            ("H", 0x23, false),
+        ];
+        for (name, code, extended) in cases {
+            let os_code: OsCode = name.parse().unwrap();
+            assert_eq!(os_code.to_scan_code(), Some((code, extended)), "{name}");
+        }
+    }
+
+    #[test]
+    fn decodes_media_browser_launch_power_and_jis_keys() {
+        #[rustfmt::skip]
+        let cases = [
+            (0x24, true, OsCode::KEY_STOPCD, Some(VK_MEDIA_STOP)),
+            (0x32, true, OsCode::KEY_HOMEPAGE, Some(VK_BROWSER_HOME)),
+            (0x21, true, OsCode::KEY_CALC, Some(VK_LAUNCH_APP2)),
+            (0x5D, true, OsCode::KEY_COMPOSE, Some(VK_APPS)),
+            (0x5E, true, OsCode::KEY_POWER, None),
+            (0x5F, true, OsCode::KEY_SLEEP, Some(VK_SLEEP)),
+            (0x63, true, OsCode::KEY_WAKEUP, None),
+            (0x65, true, OsCode::KEY_SEARCH, Some(VK_BROWSER_SEARCH)),
+            (0x66, true, OsCode::KEY_FAVORITES, Some(VK_BROWSER_FAVORITES)),
+            (0x67, true, OsCode::KEY_REFRESH, Some(VK_BROWSER_REFRESH)),
+            (0x68, true, OsCode::KEY_STOP, Some(VK_BROWSER_STOP)),
+            (0x69, true, OsCode::KEY_FORWARD, Some(VK_BROWSER_FORWARD)),
+            (0x6A, true, OsCode::KEY_BACK, Some(VK_BROWSER_BACK)),
+            (0x6B, true, OsCode::KEY_COMPUTER, Some(VK_LAUNCH_APP1)),
+            (0x6C, true, OsCode::KEY_MAIL, Some(VK_LAUNCH_MAIL)),
+            (0x6D, true, OsCode::KEY_MEDIA, Some(VK_LAUNCH_MEDIA_SELECT)),
+            (0x79, false, OsCode::KEY_HENKAN, Some(VK_CONVERT)),
+            (0x7B, false, OsCode::KEY_MUHENKAN, Some(VK_NONCONVERT)),
+            (0x70, false, OsCode::KEY_KATAKANAHIRAGANA, None),
+            (0x77, false, OsCode::KEY_HIRAGANA, Some(VK_OEM_COPY)),
+            (0x78, false, OsCode::KEY_KATAKANA, Some(VK_OEM_FINISH)),
+            (0x73, false, OsCode::KEY_RO, Some(0xC1)),
+            (0x7D, false, OsCode::KEY_YEN, None),
+            (0x5C, false, OsCode::KEY_KPJPCOMMA, None),
+            (0x59, false, OsCode::KEY_KPEQUAL, Some(VK_OEM_NEC_EQUAL)),
+            (0x7E, false, OsCode::KEY_KPCOMMA, Some(VK_SEPARATOR)),
+            (0x71, false, OsCode::KEY_HANJA, Some(VK_HANJA)),
+            (0x72, false, OsCode::KEY_HANGEUL, Some(VK_HANGEUL)),
+            (0x62, false, OsCode::KEY_FULL_SCREEN, Some(VK_ZOOM)),
+            (0x63, false, OsCode::KEY_HELP, Some(VK_HELP)),
+            (0x5D, false, OsCode::KEY_DEL_EOL, Some(VK_EREOF)),
+            (0x54, false, OsCode::KEY_SYSRQ, None),
+        ];
+        for (code, extended, os_code, vk) in cases {
+            assert_eq!(
+                OsCode::from_scan_code(code, extended),
+                Some(os_code),
+                "{code:#04X}"
+            );
+            assert_eq!(
+                os_code.to_scan_code(),
+                Some((code, extended)),
+                "{os_code:?}"
+            );
+            assert_eq!(os_code.as_u16(), vk, "{os_code:?}");
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn linux_codes_round_trip() {
+        for &os_code in OsCode::ALL {
+            assert_eq!(OsCode::from_u16(os_code as u16), Some(os_code));
+        }
+        assert_eq!(OsCode::from_u16(58), Some(OsCode::KEY_CAPSLOCK));
+        assert_eq!(OsCode::from_u16(0x300), None);
+    }
+}
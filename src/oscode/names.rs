@@ -0,0 +1,432 @@
+//! 键名解析：OsCode 与可读键名之间的相互转换，以及 "Ctrl+Shift+Left" 形式的组合键解析
+
+use super::OsCode;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// 键名别名表（不区分大小写），同一个键的第一个别名作为显示名称。
+/// 未出现在表中的键以去掉 `KEY_` 前缀的枚举名作为名称，例如 `KEY_BRIGHTNESSUP` 为 "BRIGHTNESSUP"，
+/// 完整的枚举名同样可以解析。
+const ALIASES: &[(&str, OsCode)] = &[
+    ("Esc", OsCode::KEY_ESC),
+    ("Escape", OsCode::KEY_ESC),
+    ("Minus", OsCode::KEY_MINUS),
+    ("-", OsCode::KEY_MINUS),
+    ("Equal", OsCode::KEY_EQUAL),
+    ("=", OsCode::KEY_EQUAL),
+    ("Backspace", OsCode::KEY_BACKSPACE),
+    ("BS", OsCode::KEY_BACKSPACE),
+    ("退格", OsCode::KEY_BACKSPACE),
+    ("Tab", OsCode::KEY_TAB),
+    ("LeftBracket", OsCode::KEY_LEFTBRACE),
+    ("[", OsCode::KEY_LEFTBRACE),
+    ("RightBracket", OsCode::KEY_RIGHTBRACE),
+    ("]", OsCode::KEY_RIGHTBRACE),
+    ("Enter", OsCode::KEY_ENTER),
+    ("Return", OsCode::KEY_ENTER),
+    ("回车", OsCode::KEY_ENTER),
+    ("Ctrl", OsCode::KEY_LEFTCTRL),
+    ("LCtrl", OsCode::KEY_LEFTCTRL),
+    ("Control", OsCode::KEY_LEFTCTRL),
+    ("RCtrl", OsCode::KEY_RIGHTCTRL),
+    ("Shift", OsCode::KEY_LEFTSHIFT),
+    ("LShift", OsCode::KEY_LEFTSHIFT),
+    ("RShift", OsCode::KEY_RIGHTSHIFT),
+    ("Alt", OsCode::KEY_LEFTALT),
+    ("LAlt", OsCode::KEY_LEFTALT),
+    ("RAlt", OsCode::KEY_RIGHTALT),
+    ("AltGr", OsCode::KEY_RIGHTALT),
+    ("Win", OsCode::KEY_LEFTMETA),
+    ("LWin", OsCode::KEY_LEFTMETA),
+    ("Meta", OsCode::KEY_LEFTMETA),
+    ("Super", OsCode::KEY_LEFTMETA),
+    ("Cmd", OsCode::KEY_LEFTMETA),
+    ("RWin", OsCode::KEY_RIGHTMETA),
+    ("RMeta", OsCode::KEY_RIGHTMETA),
+    ("RSuper", OsCode::KEY_RIGHTMETA),
+    ("Semicolon", OsCode::KEY_SEMICOLON),
+    (";", OsCode::KEY_SEMICOLON),
+    ("Apostrophe", OsCode::KEY_APOSTROPHE),
+    ("Quote", OsCode::KEY_APOSTROPHE),
+    ("'", OsCode::KEY_APOSTROPHE),
+    ("Grave", OsCode::KEY_GRAVE),
+    ("Backtick", OsCode::KEY_GRAVE),
+    ("`", OsCode::KEY_GRAVE),
+    ("Backslash", OsCode::KEY_BACKSLASH),
+    ("\\", OsCode::KEY_BACKSLASH),
+    ("Comma", OsCode::KEY_COMMA),
+    (",", OsCode::KEY_COMMA),
+    ("Period", OsCode::KEY_DOT),
+    (".", OsCode::KEY_DOT),
+    ("Slash", OsCode::KEY_SLASH),
+    ("/", OsCode::KEY_SLASH),
+    ("Space", OsCode::KEY_SPACE),
+    ("空格", OsCode::KEY_SPACE),
+    ("CapsLock", OsCode::KEY_CAPSLOCK),
+    ("Caps", OsCode::KEY_CAPSLOCK),
+    ("大写锁定", OsCode::KEY_CAPSLOCK),
+    ("NumLock", OsCode::KEY_NUMLOCK),
+    ("ScrollLock", OsCode::KEY_SCROLLLOCK),
+    ("KpMultiply", OsCode::KEY_KPASTERISK),
+    ("KpMinus", OsCode::KEY_KPMINUS),
+    ("KpPlus", OsCode::KEY_KPPLUS),
+    ("KpDot", OsCode::KEY_KPDOT),
+    ("KpEnter", OsCode::KEY_KPENTER),
+    ("KpDivide", OsCode::KEY_KPSLASH),
+    ("Home", OsCode::KEY_HOME),
+    ("行首", OsCode::KEY_HOME),
+    ("End", OsCode::KEY_END),
+    ("行尾", OsCode::KEY_END),
+    ("PageUp", OsCode::KEY_PAGEUP),
+    ("PgUp", OsCode::KEY_PAGEUP),
+    ("PageDown", OsCode::KEY_PAGEDOWN),
+    ("PgDn", OsCode::KEY_PAGEDOWN),
+    ("Left", OsCode::KEY_LEFT),
+    ("左", OsCode::KEY_LEFT),
+    ("Right", OsCode::KEY_RIGHT),
+    ("右", OsCode::KEY_RIGHT),
+    ("Up", OsCode::KEY_UP),
+    ("上", OsCode::KEY_UP),
+    ("Down", OsCode::KEY_DOWN),
+    ("下", OsCode::KEY_DOWN),
+    ("Insert", OsCode::KEY_INSERT),
+    ("Ins", OsCode::KEY_INSERT),
+    ("Delete", OsCode::KEY_DELETE),
+    ("Del", OsCode::KEY_DELETE),
+    ("删除", OsCode::KEY_DELETE),
+    ("PrintScreen", OsCode::KEY_PRINT),
+    ("PrtSc", OsCode::KEY_PRINT),
+    ("Pause", OsCode::KEY_PAUSE),
+    ("Apps", OsCode::KEY_COMPOSE),
+    ("Application", OsCode::KEY_COMPOSE),
+    ("Mute", OsCode::KEY_MUTE),
+    ("VolumeMute", OsCode::KEY_MUTE),
+    ("静音", OsCode::KEY_MUTE),
+    ("VolumeUp", OsCode::KEY_VOLUMEUP),
+    ("VolUp", OsCode::KEY_VOLUMEUP),
+    ("音量加", OsCode::KEY_VOLUMEUP),
+    ("VolumeDown", OsCode::KEY_VOLUMEDOWN),
+    ("VolDown", OsCode::KEY_VOLUMEDOWN),
+    ("音量减", OsCode::KEY_VOLUMEDOWN),
+    ("PlayPause", OsCode::KEY_PLAYPAUSE),
+    ("MediaPlay", OsCode::KEY_PLAYPAUSE),
+    ("NextTrack", OsCode::KEY_NEXTSONG),
+    ("MediaNext", OsCode::KEY_NEXTSONG),
+    ("PrevTrack", OsCode::KEY_PREVIOUSSONG),
+    ("MediaPrev", OsCode::KEY_PREVIOUSSONG),
+    ("MediaStop", OsCode::KEY_STOPCD),
+    ("BrowserBack", OsCode::KEY_BACK),
+    ("BrowserForward", OsCode::KEY_FORWARD),
+    ("BrowserHome", OsCode::KEY_HOMEPAGE),
+];
+
+/// 小写键名 -> 键码
+fn name_table() -> &'static HashMap<String, OsCode> {
+    static TABLE: OnceLock<HashMap<String, OsCode>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = HashMap::new();
+        for &code in OsCode::ALL {
+            table.insert(format!("{code:?}").to_lowercase(), code);
+            table.insert(enum_name(code).to_lowercase(), code);
+        }
+        for &(alias, code) in ALIASES {
+            table.insert(alias.to_lowercase(), code);
+        }
+        table
+    })
+}
+
+/// 去掉 `KEY_` 前缀的枚举名
+fn enum_name(code: OsCode) -> String {
+    let name = format!("{code:?}");
+    match name.strip_prefix("KEY_") {
+        Some(stripped) => stripped.to_string(),
+        None => name,
+    }
+}
+
+impl fmt::Display for OsCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match ALIASES.iter().find(|(_, code)| code == self) {
+            Some((alias, _)) => f.write_str(alias),
+            None => f.write_str(&enum_name(*self)),
+        }
+    }
+}
+
+impl FromStr for OsCode {
+    type Err = ParseKeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim();
+        if name.is_empty() {
+            return Err(ParseKeyError::Empty);
+        }
+        name_table()
+            .get(&name.to_lowercase())
+            .copied()
+            .ok_or_else(|| ParseKeyError::Unknown {
+                name: name.to_string(),
+                suggestion: suggest(name),
+            })
+    }
+}
+
+impl OsCode {
+    /// 是否为修饰键（Ctrl / Shift / Alt / Win，区分左右）
+    pub fn is_modifier(self) -> bool {
+        matches!(
+            self,
+            OsCode::KEY_LEFTCTRL
+                | OsCode::KEY_RIGHTCTRL
+                | OsCode::KEY_LEFTSHIFT
+                | OsCode::KEY_RIGHTSHIFT
+                | OsCode::KEY_LEFTALT
+                | OsCode::KEY_RIGHTALT
+                | OsCode::KEY_LEFTMETA
+                | OsCode::KEY_RIGHTMETA
+        )
+    }
+
+    /// 修饰键的种类，右侧修饰键视为对应的左侧修饰键；其他键原样返回
+    pub fn modifier_kind(self) -> OsCode {
+        match self {
+            OsCode::KEY_RIGHTCTRL => OsCode::KEY_LEFTCTRL,
+            OsCode::KEY_RIGHTSHIFT => OsCode::KEY_LEFTSHIFT,
+            OsCode::KEY_RIGHTALT => OsCode::KEY_LEFTALT,
+            OsCode::KEY_RIGHTMETA => OsCode::KEY_LEFTMETA,
+            code => code,
+        }
+    }
+}
+
+/// 找出与输入最接近的合法键名，用于报错提示
+fn suggest(name: &str) -> Option<String> {
+    let lower = name.to_lowercase();
+    let candidates = ALIASES
+        .iter()
+        .map(|(alias, _)| alias.to_string())
+        .chain(OsCode::ALL.iter().map(|&code| enum_name(code)));
+    candidates
+        .map(|candidate| (edit_distance(&lower, &candidate.to_lowercase()), candidate))
+        .min_by_key(|(distance, _)| *distance)
+        // 允许的编辑距离随长度增长，但不超过长度的三分之一，避免提示不相关的键名
+        .filter(|(distance, _)| *distance <= (lower.chars().count() / 3).max(1))
+        .map(|(_, candidate)| candidate)
+}
+
+/// 两个字符串的编辑距离（Levenshtein）
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != cb);
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+/// 组合键：若干修饰键加一个主键，例如 "Ctrl+Shift+Left"
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub modifiers: Vec<OsCode>,
+    pub key: OsCode,
+}
+
+impl KeyChord {
+    /// 不带修饰键的单个键
+    #[allow(unused)]
+    pub fn single(key: OsCode) -> Self {
+        KeyChord {
+            modifiers: Vec::new(),
+            key,
+        }
+    }
+
+    /// 按下顺序排列的所有键：先修饰键，最后是主键
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = OsCode> + '_ {
+        self.modifiers
+            .iter()
+            .copied()
+            .chain(std::iter::once(self.key))
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for modifier in &self.modifiers {
+            write!(f, "{modifier}+")?;
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+impl FromStr for KeyChord {
+    type Err = ParseKeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut codes = s
+            .split('+')
+            .map(str::parse::<OsCode>)
+            .collect::<Result<Vec<_>, _>>()?;
+        let key = codes.pop().ok_or(ParseKeyError::Empty)?;
+        if let Some(&code) = codes.iter().find(|code| !code.is_modifier()) {
+            return Err(ParseKeyError::NotModifier(code));
+        }
+        Ok(KeyChord {
+            modifiers: codes,
+            key,
+        })
+    }
+}
+
+/// 键名解析错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseKeyError {
+    /// 键名为空，例如 "Ctrl+" 或 ""
+    Empty,
+    /// 未知的键名，附带最接近的合法键名
+    Unknown {
+        name: String,
+        suggestion: Option<String>,
+    },
+    /// 组合键中除最后一个键外都必须是修饰键
+    NotModifier(OsCode),
+}
+
+impl fmt::Display for ParseKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseKeyError::Empty => write!(f, "键名不能为空"),
+            ParseKeyError::Unknown {
+                name,
+                suggestion: Some(suggestion),
+            } => write!(f, "未知的键名 \"{name}\"，是否想输入 \"{suggestion}\"？"),
+            ParseKeyError::Unknown {
+                name,
+                suggestion: None,
+            } => write!(f, "未知的键名 \"{name}\""),
+            ParseKeyError::NotModifier(code) => {
+                write!(f, "\"{code}\" 不是修饰键，只能作为组合键的最后一个键")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseKeyError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_round_trips_for_every_code() {
+        for &code in OsCode::ALL {
+            let name = code.to_string();
+            assert_eq!(name.parse::<OsCode>(), Ok(code), "{name}");
+            assert_eq!(name.to_uppercase().parse::<OsCode>(), Ok(code), "{name}");
+        }
+    }
+
+    #[test]
+    fn aliases_do_not_shadow_other_codes() {
+        for &code in OsCode::ALL {
+            let name = enum_name(code);
+            if let Some(&(alias, other)) = ALIASES
+                .iter()
+                .find(|(alias, _)| alias.eq_ignore_ascii_case(&name))
+            {
+                assert_eq!(other, code, "别名 {alias} 覆盖了 {code:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn parses_aliases() {
+        let cases = [
+            ("Esc", OsCode::KEY_ESC),
+            ("escape", OsCode::KEY_ESC),
+            ("Del", OsCode::KEY_DELETE),
+            ("Delete", OsCode::KEY_DELETE),
+            ("Win", OsCode::KEY_LEFTMETA),
+            ("Meta", OsCode::KEY_LEFTMETA),
+            ("Super", OsCode::KEY_LEFTMETA),
+            ("PgDn", OsCode::KEY_PAGEDOWN),
+            ("VolumeUp", OsCode::KEY_VOLUMEUP),
+            ("Home", OsCode::KEY_HOME),
+            ("左", OsCode::KEY_LEFT),
+            ("回车", OsCode::KEY_ENTER),
+            ("KEY_F5", OsCode::KEY_F5),
+            ("f5", OsCode::KEY_F5),
+            ("btn_left", OsCode::BTN_LEFT),
+        ];
+        for (name, code) in cases {
+            assert_eq!(name.parse::<OsCode>(), Ok(code), "{name}");
+        }
+    }
+
+    #[test]
+    fn parses_chords() {
+        let chord: KeyChord = "Ctrl+Shift+Left".parse().unwrap();
+        assert_eq!(
+            chord.modifiers,
+            vec![OsCode::KEY_LEFTCTRL, OsCode::KEY_LEFTSHIFT]
+        );
+        assert_eq!(chord.key, OsCode::KEY_LEFT);
+        assert_eq!(chord.to_string(), "Ctrl+Shift+Left");
+        assert_eq!(
+            " Home ".parse::<KeyChord>(),
+            Ok(KeyChord::single(OsCode::KEY_HOME))
+        );
+    }
+
+    #[test]
+    fn right_modifiers_share_kind_with_left() {
+        assert_eq!(
+            OsCode::KEY_RIGHTSHIFT.modifier_kind(),
+            OsCode::KEY_LEFTSHIFT
+        );
+        assert_eq!(OsCode::KEY_LEFTCTRL.modifier_kind(), OsCode::KEY_LEFTCTRL);
+        assert_eq!(OsCode::KEY_A.modifier_kind(), OsCode::KEY_A);
+    }
+
+    #[test]
+    fn rejects_bad_chords() {
+        assert_eq!("Ctrl+".parse::<KeyChord>(), Err(ParseKeyError::Empty));
+        assert_eq!(
+            "A+B".parse::<KeyChord>(),
+            Err(ParseKeyError::NotModifier(OsCode::KEY_A))
+        );
+    }
+
+    #[test]
+    fn suggests_closest_name() {
+        match "Ctrl+PgDwn".parse::<KeyChord>() {
+            Err(ParseKeyError::Unknown { name, suggestion }) => {
+                assert_eq!(name, "PgDwn");
+                assert_eq!(suggestion.as_deref(), Some("PgDn"));
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        let err = "Escpae".parse::<OsCode>().unwrap_err();
+        assert!(err.to_string().contains("Escape"), "{err}");
+    }
+
+    #[test]
+    fn nonsense_name_has_no_suggestion() {
+        for name in ["Blorptastic", "Qwx", "Hjklmn"] {
+            match name.parse::<OsCode>() {
+                Err(ParseKeyError::Unknown { suggestion, .. }) => {
+                    assert_eq!(suggestion, None, "{name}");
+                }
+                other => panic!("unexpected {other:?}"),
+            }
+        }
+    }
+}
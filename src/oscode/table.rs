@@ -0,0 +1,344 @@
+//! OsCode、Windows 虚拟键码与扫描码之间的对照表。
+//!
+//! 这是三者相互转换的唯一依据：`OsCode::as_u16`、`OsCode::from_vk`、`OsCode::to_scan_code`、
+//! `OsCode::from_scan_code`（以及基于它的 `TryFrom<Stroke>`）和 `all_scan_codes` 都由此表生成。
+//! 表中任意两项的 OsCode、虚拟键码、扫描码都不能重复。
+//! 扫描码为 Set 1 扫描码（不含 E0 前缀），不依赖 Interception，其他平台同样可以使用。
+
+use super::OsCode;
+use crate::keys::*;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// 对照表中的一项
+#[derive(Debug, Clone, Copy)]
+pub struct KeyEntry {
+    pub code: OsCode,
+    /// Windows 虚拟键码
+    pub vk: Option<u16>,
+    /// 扫描码，以及是否带 E0 前缀
+    pub scan: Option<(u16, bool)>,
+}
+
+const fn key(code: OsCode, vk: u16, scan: u16) -> KeyEntry {
+    KeyEntry {
+        code,
+        vk: Some(vk),
+        scan: Some((scan, false)),
+    }
+}
+
+/// E0 扩展键
+const fn ext(code: OsCode, vk: u16, scan: u16) -> KeyEntry {
+    KeyEntry {
+        code,
+        vk: Some(vk),
+        scan: Some((scan, true)),
+    }
+}
+
+/// 没有虚拟键码的键
+const fn scan_only(code: OsCode, scan: u16, extended: bool) -> KeyEntry {
+    KeyEntry {
+        code,
+        vk: None,
+        scan: Some((scan, extended)),
+    }
+}
+
+/// 没有单个扫描码的键
+const fn vk_only(code: OsCode, vk: u16) -> KeyEntry {
+    KeyEntry {
+        code,
+        vk: Some(vk),
+        scan: None,
+    }
+}
+
+#[rustfmt::skip]
+pub const KEY_TABLE: &[KeyEntry] = &[
+    key(OsCode::KEY_ESC, VK_ESCAPE, 0x01),
+    key(OsCode::KEY_1, 0x31, 0x02),
+    key(OsCode::KEY_2, 0x32, 0x03),
+    key(OsCode::KEY_3, 0x33, 0x04),
+    key(OsCode::KEY_4, 0x34, 0x05),
+    key(OsCode::KEY_5, 0x35, 0x06),
+    key(OsCode::KEY_6, 0x36, 0x07),
+    key(OsCode::KEY_7, 0x37, 0x08),
+    key(OsCode::KEY_8, 0x38, 0x09),
+    key(OsCode::KEY_9, 0x39, 0x0A),
+    key(OsCode::KEY_0, 0x30, 0x0B),
+    key(OsCode::KEY_MINUS, VK_OEM_MINUS, 0x0C),
+    key(OsCode::KEY_EQUAL, VK_OEM_PLUS, 0x0D),
+    key(OsCode::KEY_BACKSPACE, VK_BACK, 0x0E),
+    key(OsCode::KEY_TAB, VK_TAB, 0x0F),
+    key(OsCode::KEY_Q, 0x51, 0x10),
+    key(OsCode::KEY_W, 0x57, 0x11),
+    key(OsCode::KEY_E, 0x45, 0x12),
+    key(OsCode::KEY_R, 0x52, 0x13),
+    key(OsCode::KEY_T, 0x54, 0x14),
+    key(OsCode::KEY_Y, 0x59, 0x15),
+    key(OsCode::KEY_U, 0x55, 0x16),
+    key(OsCode::KEY_I, 0x49, 0x17),
+    key(OsCode::KEY_O, 0x4F, 0x18),
+    key(OsCode::KEY_P, 0x50, 0x19),
+    key(OsCode::KEY_LEFTBRACE, VK_OEM_4, 0x1A),
+    key(OsCode::KEY_RIGHTBRACE, VK_OEM_6, 0x1B),
+    key(OsCode::KEY_ENTER, VK_RETURN, 0x1C),
+    key(OsCode::KEY_LEFTCTRL, VK_LCONTROL, 0x1D),
+    key(OsCode::KEY_A, 0x41, 0x1E),
+    key(OsCode::KEY_S, 0x53, 0x1F),
+    key(OsCode::KEY_D, 0x44, 0x20),
+    key(OsCode::KEY_F, 0x46, 0x21),
+    key(OsCode::KEY_G, 0x47, 0x22),
+    key(OsCode::KEY_H, 0x48, 0x23),
+    key(OsCode::KEY_J, 0x4A, 0x24),
+    key(OsCode::KEY_K, 0x4B, 0x25),
+    key(OsCode::KEY_L, 0x4C, 0x26),
+    key(OsCode::KEY_SEMICOLON, VK_OEM_1, 0x27),
+    key(OsCode::KEY_APOSTROPHE, VK_OEM_7, 0x28),
+    key(OsCode::KEY_GRAVE, VK_OEM_3, 0x29),
+    key(OsCode::KEY_LEFTSHIFT, VK_LSHIFT, 0x2A),
+    key(OsCode::KEY_BACKSLASH, VK_OEM_5, 0x2B),
+    key(OsCode::KEY_Z, 0x5A, 0x2C),
+    key(OsCode::KEY_X, 0x58, 0x2D),
+    key(OsCode::KEY_C, 0x43, 0x2E),
+    key(OsCode::KEY_V, 0x56, 0x2F),
+    key(OsCode::KEY_B, 0x42, 0x30),
+    key(OsCode::KEY_N, 0x4E, 0x31),
+    key(OsCode::KEY_M, 0x4D, 0x32),
+    key(OsCode::KEY_COMMA, VK_OEM_COMMA, 0x33),
+    key(OsCode::KEY_DOT, VK_OEM_PERIOD, 0x34),
+    key(OsCode::KEY_SLASH, VK_OEM_2, 0x35),
+    key(OsCode::KEY_RIGHTSHIFT, VK_RSHIFT, 0x36),
+    key(OsCode::KEY_KPASTERISK, VK_MULTIPLY, 0x37),
+    key(OsCode::KEY_LEFTALT, VK_LMENU, 0x38),
+    key(OsCode::KEY_SPACE, VK_SPACE, 0x39),
+    key(OsCode::KEY_CAPSLOCK, VK_CAPITAL, 0x3A),
+    key(OsCode::KEY_F1, VK_F1, 0x3B),
+    key(OsCode::KEY_F2, VK_F2, 0x3C),
+    key(OsCode::KEY_F3, VK_F3, 0x3D),
+    key(OsCode::KEY_F4, VK_F4, 0x3E),
+    key(OsCode::KEY_F5, VK_F5, 0x3F),
+    key(OsCode::KEY_F6, VK_F6, 0x40),
+    key(OsCode::KEY_F7, VK_F7, 0x41),
+    key(OsCode::KEY_F8, VK_F8, 0x42),
+    key(OsCode::KEY_F9, VK_F9, 0x43),
+    key(OsCode::KEY_F10, VK_F10, 0x44),
+    key(OsCode::KEY_NUMLOCK, VK_NUMLOCK, 0x45),
+    key(OsCode::KEY_SCROLLLOCK, VK_SCROLL, 0x46),
+    key(OsCode::KEY_KP7, VK_NUMPAD7, 0x47),
+    key(OsCode::KEY_KP8, VK_NUMPAD8, 0x48),
+    key(OsCode::KEY_KP9, VK_NUMPAD9, 0x49),
+    key(OsCode::KEY_KPMINUS, VK_SUBTRACT, 0x4A),
+    key(OsCode::KEY_KP4, VK_NUMPAD4, 0x4B),
+    key(OsCode::KEY_KP5, VK_NUMPAD5, 0x4C),
+    key(OsCode::KEY_KP6, VK_NUMPAD6, 0x4D),
+    key(OsCode::KEY_KPPLUS, VK_ADD, 0x4E),
+    key(OsCode::KEY_KP1, VK_NUMPAD1, 0x4F),
+    key(OsCode::KEY_KP2, VK_NUMPAD2, 0x50),
+    key(OsCode::KEY_KP3, VK_NUMPAD3, 0x51),
+    key(OsCode::KEY_KP0, VK_NUMPAD0, 0x52),
+    key(OsCode::KEY_KPDOT, VK_DECIMAL, 0x53),
+    scan_only(OsCode::KEY_SYSRQ, 0x54, false), // Alt + print screen
+    key(OsCode::KEY_102ND, VK_OEM_102, 0x56), // Key between the left shift and Z.
+    key(OsCode::KEY_F11, VK_F11, 0x57),
+    key(OsCode::KEY_F12, VK_F12, 0x58),
+    // JIS / 巴西 ABNT 键盘的额外按键，参照 USB HID 到扫描码的转换表。
+    // Oem1 (5A)、Oem2 (5B)、Oem4 (5E)、Oem5 (5F)、Oem6 (6F) 在 input-event-codes.h 中没有对应的键
+    key(OsCode::KEY_KPEQUAL, VK_OEM_NEC_EQUAL, 0x59),
+    scan_only(OsCode::KEY_KPJPCOMMA, 0x5C, false), // Int'l 6
+    key(OsCode::KEY_DEL_EOL, VK_EREOF, 0x5D),
+    key(OsCode::KEY_FULL_SCREEN, VK_ZOOM, 0x62), // KEY_ZOOM
+    key(OsCode::KEY_HELP, VK_HELP, 0x63),
+    key(OsCode::KEY_F13, VK_F13, 0x64),
+    key(OsCode::KEY_F14, VK_F14, 0x65),
+    key(OsCode::KEY_F15, VK_F15, 0x66),
+    key(OsCode::KEY_F16, VK_F16, 0x67),
+    key(OsCode::KEY_F17, VK_F17, 0x68),
+    key(OsCode::KEY_F18, VK_F18, 0x69),
+    key(OsCode::KEY_F19, VK_F19, 0x6A),
+    key(OsCode::KEY_F20, VK_F20, 0x6B),
+    key(OsCode::KEY_F21, VK_F21, 0x6C),
+    key(OsCode::KEY_F22, VK_F22, 0x6D),
+    key(OsCode::KEY_F23, VK_F23, 0x6E),
+    scan_only(OsCode::KEY_KATAKANAHIRAGANA, 0x70, false), // Int'l 2
+    key(OsCode::KEY_HANJA, VK_HANJA, 0x71), // Lang 2
+    key(OsCode::KEY_HANGEUL, VK_HANGEUL, 0x72), // Lang 1
+    key(OsCode::KEY_RO, 0xC1, 0x73), // Int'l 1
+    key(OsCode::KEY_F24, VK_F24, 0x76),
+    key(OsCode::KEY_HIRAGANA, VK_OEM_COPY, 0x77), // Lang 4
+    key(OsCode::KEY_KATAKANA, VK_OEM_FINISH, 0x78), // Lang 3
+    key(OsCode::KEY_HENKAN, VK_CONVERT, 0x79), // Int'l 4
+    key(OsCode::KEY_MUHENKAN, VK_NONCONVERT, 0x7B), // Int'l 5
+    scan_only(OsCode::KEY_YEN, 0x7D, false), // Int'l 3
+    key(OsCode::KEY_KPCOMMA, VK_SEPARATOR, 0x7E), // Brazilian keypad .
+    // E0 扩展键
+    ext(OsCode::KEY_PREVIOUSSONG, VK_MEDIA_PREV_TRACK, 0x10), // E0 10 sc_media_prev
+    ext(OsCode::KEY_NEXTSONG, VK_MEDIA_NEXT_TRACK, 0x19), // E0 19 sc_media_next
+    scan_only(OsCode::KEY_KPENTER, 0x1C, true), // E0 1C sc_numpad_enter，与 Enter 共用 VK_RETURN
+    ext(OsCode::KEY_RIGHTCTRL, VK_RCONTROL, 0x1D), // E0 1D sc_controlRight
+    ext(OsCode::KEY_MUTE, VK_VOLUME_MUTE, 0x20), // E0 20 sc_volume_mute
+    ext(OsCode::KEY_CALC, VK_LAUNCH_APP2, 0x21), // E0 21 sc_launch_app2
+    ext(OsCode::KEY_PLAYPAUSE, VK_MEDIA_PLAY_PAUSE, 0x22), // E0 22 sc_media_play
+    ext(OsCode::KEY_STOPCD, VK_MEDIA_STOP, 0x24), // E0 24 sc_media_stop
+    ext(OsCode::KEY_VOLUMEDOWN, VK_VOLUME_DOWN, 0x2E), // E0 2E sc_volume_down
+    ext(OsCode::KEY_VOLUMEUP, VK_VOLUME_UP, 0x30), // E0 30 sc_volume_up
+    ext(OsCode::KEY_HOMEPAGE, VK_BROWSER_HOME, 0x32), // E0 32 sc_browser_home
+    ext(OsCode::KEY_KPSLASH, VK_DIVIDE, 0x35), // E0 35 sc_numpad_divide
+    ext(OsCode::KEY_PRINT, VK_SNAPSHOT, 0x37), // E0 37 sc_printScreen
+    ext(OsCode::KEY_RIGHTALT, VK_RMENU, 0x38), // E0 38 sc_altRight
+    ext(OsCode::KEY_CANCEL, VK_CANCEL, 0x46), // E0 46 sc_cancel
+    ext(OsCode::KEY_HOME, VK_HOME, 0x47), // E0 47 sc_home
+    ext(OsCode::KEY_UP, VK_UP, 0x48), // E0 48 sc_arrowUp
+    ext(OsCode::KEY_PAGEUP, VK_PRIOR, 0x49), // E0 49 sc_pageUp
+    ext(OsCode::KEY_LEFT, VK_LEFT, 0x4B), // E0 4B sc_arrowLeft
+    ext(OsCode::KEY_RIGHT, VK_RIGHT, 0x4D), // E0 4D sc_arrowRight
+    ext(OsCode::KEY_END, VK_END, 0x4F), // E0 4F sc_end
+    ext(OsCode::KEY_DOWN, VK_DOWN, 0x50), // E0 50 sc_arrowDown
+    ext(OsCode::KEY_PAGEDOWN, VK_NEXT, 0x51), // E0 51 sc_pageDown
+    ext(OsCode::KEY_INSERT, VK_INSERT, 0x52), // E0 52 sc_insert
+    ext(OsCode::KEY_DELETE, VK_DELETE, 0x53), // E0 53 sc_delete
+    ext(OsCode::KEY_LEFTMETA, VK_LWIN, 0x5B), // E0 5B sc_metaLeft
+    ext(OsCode::KEY_RIGHTMETA, VK_RWIN, 0x5C), // E0 5C sc_metaRight
+    ext(OsCode::KEY_COMPOSE, VK_APPS, 0x5D), // E0 5D sc_application
+    scan_only(OsCode::KEY_POWER, 0x5E, true), // E0 5E sc_power
+    ext(OsCode::KEY_SLEEP, VK_SLEEP, 0x5F), // E0 5F sc_sleep
+    scan_only(OsCode::KEY_WAKEUP, 0x63, true), // E0 63 sc_wake
+    ext(OsCode::KEY_SEARCH, VK_BROWSER_SEARCH, 0x65), // E0 65 sc_browser_search
+    ext(OsCode::KEY_FAVORITES, VK_BROWSER_FAVORITES, 0x66), // E0 66 sc_browser_favorites
+    ext(OsCode::KEY_REFRESH, VK_BROWSER_REFRESH, 0x67), // E0 67 sc_browser_refresh
+    ext(OsCode::KEY_STOP, VK_BROWSER_STOP, 0x68), // E0 68 sc_browser_stop
+    ext(OsCode::KEY_FORWARD, VK_BROWSER_FORWARD, 0x69), // E0 69 sc_browser_forward
+    ext(OsCode::KEY_BACK, VK_BROWSER_BACK, 0x6A), // E0 6A sc_browser_back
+    ext(OsCode::KEY_COMPUTER, VK_LAUNCH_APP1, 0x6B), // E0 6B sc_launch_app1
+    ext(OsCode::KEY_MAIL, VK_LAUNCH_MAIL, 0x6C), // E0 6C sc_launch_email
+    ext(OsCode::KEY_MEDIA, VK_LAUNCH_MEDIA_SELECT, 0x6D), // E0 6D sc_launch_media
+    // 没有单独扫描码的键
+    vk_only(OsCode::KEY_PAUSE, VK_PAUSE), // 扫描码为 E1 1D 45 序列，见 decoder.rs
+    vk_only(OsCode::KEY_PLAY, VK_PLAY),
+    vk_only(OsCode::KEY_CLEAR, VK_CLEAR), // NumLock 关闭时的小键盘 5
+];
+
+/// 对照表的三个索引
+struct Index {
+    by_code: HashMap<OsCode, KeyEntry>,
+    by_vk: HashMap<u16, OsCode>,
+    by_scan: HashMap<(u16, bool), OsCode>,
+}
+
+fn index() -> &'static Index {
+    static INDEX: OnceLock<Index> = OnceLock::new();
+    INDEX.get_or_init(|| {
+        let mut index = Index {
+            by_code: HashMap::new(),
+            by_vk: HashMap::new(),
+            by_scan: HashMap::new(),
+        };
+        for entry in KEY_TABLE {
+            index.by_code.insert(entry.code, *entry);
+            if let Some(vk) = entry.vk {
+                index.by_vk.insert(vk, entry.code);
+            }
+            if let Some(scan) = entry.scan {
+                index.by_scan.insert(scan, entry.code);
+            }
+        }
+        index
+    })
+}
+
+impl OsCode {
+    /// Windows 虚拟键码
+    #[allow(unused)]
+    pub fn as_u16(self) -> Option<u16> {
+        index().by_code.get(&self)?.vk
+    }
+
+    /// 由 Windows 虚拟键码查找键码
+    #[allow(unused)]
+    pub fn from_vk(vk: u16) -> Option<OsCode> {
+        index().by_vk.get(&vk).copied()
+    }
+
+    /// 扫描码以及是否为 E0 扩展键
+    pub fn to_scan_code(self) -> Option<(u16, bool)> {
+        index().by_code.get(&self)?.scan
+    }
+
+    /// 由扫描码查找键码，`extended` 表示是否带 E0 前缀
+    #[allow(unused)]
+    pub fn from_scan_code(code: u16, extended: bool) -> Option<OsCode> {
+        index().by_scan.get(&(code, extended)).copied()
+    }
+
+    /// 能否作为映射输出：有扫描码的键，以及以 E1 序列发送的 Pause
+    pub fn is_encodable(self) -> bool {
+        self == OsCode::KEY_PAUSE || self.to_scan_code().is_some()
+    }
+}
+
+/// 对照表中出现的所有扫描码，以及是否为 E0 扩展键
+#[allow(unused)]
+pub fn all_scan_codes() -> impl Iterator<Item = (u16, bool)> {
+    KEY_TABLE.iter().filter_map(|entry| entry.scan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn entries_are_unique() {
+        let mut codes = HashSet::new();
+        let mut vks = HashSet::new();
+        let mut scans = HashSet::new();
+        for entry in KEY_TABLE {
+            assert!(codes.insert(entry.code), "{:?} 重复", entry.code);
+            if let Some(vk) = entry.vk {
+                assert!(vks.insert(vk), "{:?} 的虚拟键码 {vk:#04X} 重复", entry.code);
+            }
+            if let Some(scan) = entry.scan {
+                assert!(
+                    scans.insert(scan),
+                    "{:?} 的扫描码 {scan:X?} 重复",
+                    entry.code
+                );
+            }
+            assert!(
+                entry.vk.is_some() || entry.scan.is_some(),
+                "{:?}",
+                entry.code
+            );
+        }
+    }
+
+    #[test]
+    fn entries_are_consistent_in_all_directions() {
+        for entry in KEY_TABLE {
+            let code = entry.code;
+            assert_eq!(code.as_u16(), entry.vk, "{code:?}");
+            assert_eq!(code.to_scan_code(), entry.scan, "{code:?}");
+            if let Some(vk) = entry.vk {
+                assert_eq!(OsCode::from_vk(vk), Some(code), "{vk:#04X}");
+            }
+            if let Some((scan, extended)) = entry.scan {
+                assert_eq!(
+                    OsCode::from_scan_code(scan, extended),
+                    Some(code),
+                    "{scan:X?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_values_are_none() {
+        assert_eq!(OsCode::KEY_MACRO.as_u16(), None);
+        assert_eq!(OsCode::KEY_MACRO.to_scan_code(), None);
+        assert_eq!(OsCode::from_vk(0), None);
+        assert_eq!(OsCode::from_vk(VK_LBUTTON), None);
+        assert_eq!(OsCode::from_scan_code(0x5A, false), None);
+    }
+}
@@ -0,0 +1,201 @@
+//! Win32 按键状态查询：启动时等待所有按键释放，运行时修正卡住的 Ctrl；
+//! 以及查询前台程序，用于按程序关闭序列。
+//! 仅在启用 `win32-state` 功能的 Windows 构建中编译。
+
+use crate::oscode::OsCode;
+use windows::Win32::Foundation::{CloseHandle, LPARAM};
+use windows::Win32::System::Threading::{
+    OpenProcess, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION, QueryFullProcessImageNameW,
+};
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    GetAsyncKeyState, GetKeyNameTextW, VIRTUAL_KEY, VK_DELETE, VK_DOWN, VK_END, VK_HOME, VK_INSERT,
+    VK_LCONTROL, VK_LEFT, VK_LMENU, VK_LSHIFT, VK_LWIN, VK_NEXT, VK_PRIOR, VK_RCONTROL, VK_RIGHT,
+    VK_RMENU, VK_RSHIFT, VK_RWIN, VK_UP,
+};
+use windows::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowThreadProcessId};
+use windows::core::PWSTR;
+
+/// 等待直到所有按键都已释放，避免拦截开始时有键卡在按下状态
+pub fn wait_for_keys_released() {
+    log::info!("等待所有的键释放");
+    init_keyboard_state(); // Call once
+    loop {
+        if are_all_keys_released() {
+            log::info!("所有按键均已释放，程序开始");
+            break;
+        }
+        // 短间隔轮询，减少 CPU 占用
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+}
+
+/// 系统中某个键当前是否处于按下状态，供引擎修正卡住的 Ctrl
+pub fn key_state(code: OsCode) -> bool {
+    code.as_u16().is_some_and(|vk| is_key_down(VIRTUAL_KEY(vk)))
+}
+
+/// 前台窗口所属程序的可执行文件名，例如 `KeePass.exe`；查询失败时为 `None`
+pub fn foreground_app() -> Option<String> {
+    let mut pid = 0u32;
+    unsafe {
+        let hwnd = GetForegroundWindow();
+        if hwnd.is_invalid() {
+            return None;
+        }
+        GetWindowThreadProcessId(hwnd, Some(&mut pid));
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+        let mut buffer = [0u16; 260];
+        let mut len = buffer.len() as u32;
+        let result = QueryFullProcessImageNameW(
+            process,
+            PROCESS_NAME_WIN32,
+            PWSTR(buffer.as_mut_ptr()),
+            &mut len,
+        );
+        let _ = CloseHandle(process);
+        result.ok()?;
+        let path = String::from_utf16_lossy(&buffer[..len as usize]);
+        path.rsplit('\\').next().map(str::to_string)
+    }
+}
+
+/// 检查当前是否所有按键都处于释放状态
+static CLEARED_WEIRD: std::sync::Once = std::sync::Once::new();
+
+pub fn init_keyboard_state() {
+    CLEARED_WEIRD.call_once(|| {
+        // Existing weird keys...
+        let weird = [
+            VK_HOME,
+            VK_END,
+            VK_PRIOR,
+            VK_NEXT,
+            VK_INSERT,
+            VK_DELETE,
+            VK_LEFT,
+            VK_RIGHT,
+            VK_UP,
+            VK_DOWN,
+            // NEW: Flush ALL modifiers to prevent ghosting
+            VK_LCONTROL,
+            VK_RCONTROL,
+            VK_LSHIFT,
+            VK_RSHIFT,
+            VK_LMENU,
+            VK_RMENU, // Alt
+            VK_LWIN,
+            VK_RWIN, // Win
+        ];
+        for &vk in &weird {
+            unsafe {
+                windows::Win32::UI::Input::KeyboardAndMouse::GetAsyncKeyState(i32::from(vk.0));
+            }
+        }
+        // Extra: Force-send UP for all modifiers (safe, as they're likely up)
+        // You'll need access to Interception here—move this to main() post-init if preferred
+    });
+}
+
+/// Ultra-fast version after init_keyboard_state() was called once
+pub fn are_all_keys_released() -> bool {
+    const WEIRD: [u16; 18] = [
+        0x21,
+        0x22,
+        0x23,
+        0x24, // Prior, Next, End, Home
+        0x25,
+        0x26,
+        0x27,
+        0x28, // Left, Up, Right, Down
+        0x2D,
+        0x2E,
+        // NEW: Modifiers (treat as "weird" to avoid false positives)
+        VK_LCONTROL.0,
+        VK_RCONTROL.0,
+        VK_LSHIFT.0,
+        VK_RSHIFT.0,
+        VK_LMENU.0,
+        VK_RMENU.0,
+        VK_LWIN.0,
+        VK_RWIN.0, // Insert, Delete
+    ];
+
+    for code in 1u16..=255u16 {
+        let state = unsafe {
+            windows::Win32::UI::Input::KeyboardAndMouse::GetAsyncKeyState(i32::from(code))
+        };
+        let pressed = if WEIRD.contains(&code) {
+            (state & 1) != 0 // "was pressed" bit
+        } else {
+            state < 0 // normal "currently down"
+        };
+        if pressed {
+            return false;
+        }
+    }
+    true
+}
+//  获取键位状态，false没有按下，true 按下
+pub fn is_key_down(vk: windows::Win32::UI::Input::KeyboardAndMouse::VIRTUAL_KEY) -> bool {
+    // These keys have bit 15 permanently set when up
+    const WEIRD_KEYS: [u16; 8] = [
+        VK_HOME.0,
+        VK_END.0,
+        VK_PRIOR.0,
+        VK_NEXT.0,
+        VK_INSERT.0,
+        VK_DELETE.0,
+        VK_LEFT.0,
+        VK_RIGHT.0,
+    ];
+
+    let code = vk.0;
+    unsafe {
+        let state = GetAsyncKeyState(i32::from(code));
+        if WEIRD_KEYS.contains(&code) {
+            // For these keys, bit 0 = was pressed since last call
+            // (you usually need to call it twice or clear it manually)
+            state & 1 != 0
+        } else {
+            state < 0 // normal keys: bit 15 = currently down
+        }
+    }
+}
+/// 通过虚拟键码获取键的名称（如 "A", "Left Ctrl", "Mouse Left" 等）
+#[allow(unused)]
+fn get_key_name(vk_code: u16) -> String {
+    // 构造 lParam 参数（低 16 位为虚拟键码，高 16 位为扩展键标志）
+    let lparam = LPARAM((vk_code as isize) << 16);
+    let mut buffer = [0u16; 256]; // 存储宽字符结果
+
+    // 调用 Windows API 获取键名
+    let length = unsafe { GetKeyNameTextW(lparam.0 as i32, &mut buffer) };
+
+    if length > 0 {
+        // 将宽字符串转换为 Rust 字符串
+        String::from_utf16_lossy(&buffer[..length as usize])
+    } else {
+        format!("未知键 (VK_CODE: 0x{:02X})", vk_code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_key_down;
+    use windows::Win32::UI::Input::KeyboardAndMouse::*;
+
+    #[test]
+    fn test_key_states() {
+        // Give you a moment to release any keys
+        std::thread::sleep(std::time::Duration::from_millis(200));
+
+        let home = is_key_down(VK_HOME);
+        let shift = is_key_down(VK_LSHIFT);
+        let a = is_key_down(VK_A);
+
+        println!("HOME: {home}   LSHIFT: {shift}   A: {a}");
+
+        // This will now PASS when Home is not pressed
+        assert!(!home, "Home key is reported as down but it should be up");
+    }
+}